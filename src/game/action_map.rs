@@ -0,0 +1,141 @@
+use winit::{
+    event::{ElementState, MouseButton},
+    keyboard::KeyCode,
+};
+
+use super::input_state::InputState;
+
+pub mod exports {
+    pub use super::{ActionEvent, ActionMap, ActionName, Binding};
+}
+
+///Name of a game action, eg. "jump" or "fire"
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct ActionName(String);
+impl ActionName {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+impl From<&str> for ActionName {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+impl From<String> for ActionName {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+///Delivered to the entities through `Entity::handle_action` when a bound key or button changes
+#[derive(Debug, Clone)]
+pub struct ActionEvent {
+    pub action: ActionName,
+    pub state: ElementState,
+}
+
+///Maps actions to the keys and mouse buttons triggering them, so controls can be rebound without
+///touching the entities. With the `serde` feature it can be saved with the settings
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActionMap {
+    actions: Vec<(ActionName, Vec<Binding>)>,
+}
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_binding(mut self, action: impl Into<ActionName>, binding: Binding) -> Self {
+        self.bind(action, binding);
+        self
+    }
+
+    ///Adds the binding to the ones of the action. A binding can trigger several actions
+    pub fn bind(&mut self, action: impl Into<ActionName>, binding: Binding) {
+        let action = action.into();
+        match self.actions.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, bindings)) if bindings.contains(&binding) => {}
+            Some((_, bindings)) => bindings.push(binding),
+            None => self.actions.push((action, vec![binding])),
+        }
+    }
+
+    ///Replaces all bindings of the action, eg. after the player picked a new key
+    pub fn rebind(&mut self, action: impl Into<ActionName>, bindings: Vec<Binding>) {
+        let action = action.into();
+        match self.actions.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, existing)) => *existing = bindings,
+            None => self.actions.push((action, bindings)),
+        }
+    }
+
+    pub fn unbind(&mut self, action: &ActionName, binding: &Binding) {
+        if let Some((_, bindings)) = self.actions.iter_mut().find(|(a, _)| a == action) {
+            bindings.retain(|b| b != binding);
+        }
+    }
+
+    ///Empty if the action is unknown
+    pub fn bindings(&self, action: &ActionName) -> &[Binding] {
+        self.actions
+            .iter()
+            .find(|(a, _)| a == action)
+            .map_or(&[], |(_, bindings)| bindings.as_slice())
+    }
+
+    ///The actions triggered by the binding, in the order they were added
+    pub fn actions(&self, binding: &Binding) -> impl Iterator<Item = &ActionName> {
+        let binding = *binding;
+        self.actions
+            .iter()
+            .filter(move |(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| action)
+    }
+
+    ///If any key bound to the action is held. Mouse buttons are not tracked by the InputState
+    pub fn is_pressed(&self, action: &ActionName, input: &InputState) -> bool {
+        self.bindings(action).iter().any(|binding| match binding {
+            Binding::Key(key) => input.is_pressed(*key),
+            Binding::Mouse(_) => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_and_rebind() {
+        let jump: ActionName = "jump".into();
+        let mut map = ActionMap::new()
+            .with_binding("jump", Binding::Key(KeyCode::Space))
+            .with_binding("jump", Binding::Key(KeyCode::Space))
+            .with_binding("fire", Binding::Mouse(MouseButton::Left));
+        assert_eq!(map.bindings(&jump), &[Binding::Key(KeyCode::Space)]);
+        assert_eq!(
+            map.actions(&Binding::Mouse(MouseButton::Left)).collect::<Vec<_>>(),
+            vec![&ActionName::from("fire")]
+        );
+        map.rebind("jump", vec![Binding::Key(KeyCode::KeyW)]);
+        let mut input = InputState::new();
+        input.set_key(KeyCode::KeyW, true);
+        assert!(map.is_pressed(&jump, &input));
+        map.unbind(&jump, &Binding::Key(KeyCode::KeyW));
+        assert!(!map.is_pressed(&jump, &input));
+    }
+}
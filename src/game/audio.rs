@@ -0,0 +1,84 @@
+use std::{fs, io::Cursor, path::Path, sync::Arc};
+
+use log::{error, warn};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+
+use crate::create_name_struct;
+
+pub mod exports {
+    pub use super::SoundName;
+}
+
+create_name_struct!(SoundName);
+
+///Encoded sound file shared by all playbacks of the sound
+#[derive(Clone)]
+struct SoundData(Arc<[u8]>);
+impl AsRef<[u8]> for SoundData {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+///Plays sounds on the default output device. Sounds are read once when first played
+pub(crate) struct AudioPlayer {
+    //Playback stops when the stream is dropped
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    sounds: Vec<(SoundName, SoundData)>,
+}
+impl AudioPlayer {
+    pub(crate) fn new() -> Option<Self> {
+        match OutputStream::try_default() {
+            Ok((stream, handle)) => Some(Self {
+                _stream: stream,
+                handle,
+                sounds: Vec::new(),
+            }),
+            Err(e) => {
+                error!("Could not open an audio output: {}", e);
+                None
+            }
+        }
+    }
+
+    fn load(&mut self, name: &SoundName, path: &Path) -> Option<SoundData> {
+        if let Some((_, data)) = self.sounds.iter().find(|(n, _)| n == name) {
+            return Some(data.clone());
+        }
+        match fs::read(path) {
+            Ok(bytes) => {
+                let data = SoundData(bytes.into());
+                self.sounds.push((name.clone(), data.clone()));
+                Some(data)
+            }
+            Err(e) => {
+                warn!("Could not read Sound {:?} at {:?}: {}", name, path, e);
+                None
+            }
+        }
+    }
+
+    ///Fire and forget. `volume` 1.0 plays the sound unchanged
+    pub(crate) fn play(&mut self, name: &SoundName, path: &Path, volume: f32) {
+        let data = match self.load(name, path) {
+            Some(data) => data,
+            None => return,
+        };
+        let source = match Decoder::new(Cursor::new(data)) {
+            Ok(source) => source,
+            Err(e) => {
+                warn!("Could not decode Sound {:?}: {}", name, e);
+                return;
+            }
+        };
+        match Sink::try_new(&self.handle) {
+            Ok(sink) => {
+                sink.set_volume(volume);
+                sink.append(source);
+                sink.detach();
+            }
+            Err(e) => warn!("Could not play Sound {:?}: {}", name, e),
+        }
+    }
+}
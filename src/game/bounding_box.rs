@@ -1,5 +1,5 @@
 use twod::Vector;
-use crate::Size;
+use crate::{Position, Size};
 
 pub mod exports {
     pub use super::BoundingBox;
@@ -7,13 +7,85 @@ pub mod exports {
 
 ///Bounding Box defined by middle point and width and height
 ///The negative sides (anchor - size/2) and the positive sides (anchor + size/2) are inclusive
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BoundingBox {
     ///Middle point
     pub anchor: Vector<f32>,
     pub size: Size<f32>,
 }
 impl BoundingBox {
+    ///Box around the middle point, in the 2D types of the crate
+    pub fn new(anchor: Position<f32>, size: Size<f32>) -> Self {
+        Self {
+            anchor: anchor.into(),
+            size,
+        }
+    }
+
+    pub fn center(&self) -> Vector<f32> {
+        self.anchor.clone()
+    }
+
+    ///The middle point as a Position
+    pub fn position(&self) -> Position<f32> {
+        self.anchor.clone().into()
+    }
+
+    ///Corner with the smallest x and y
+    pub fn min(&self) -> Vector<f32> {
+        Vector::new(
+            self.anchor.x - self.size.width() / 2.0,
+            self.anchor.y - self.size.height() / 2.0,
+        )
+    }
+
+    ///Corner with the biggest x and y
+    pub fn max(&self) -> Vector<f32> {
+        Vector::new(
+            self.anchor.x + self.size.width() / 2.0,
+            self.anchor.y + self.size.height() / 2.0,
+        )
+    }
+
+    ///Corners counter clockwise, starting at min
+    pub fn corners(&self) -> [Vector<f32>; 4] {
+        let min = self.min();
+        let max = self.max();
+        [
+            Vector::new(min.x, min.y),
+            Vector::new(max.x, min.y),
+            Vector::new(max.x, max.y),
+            Vector::new(min.x, max.y),
+        ]
+    }
+
+    pub fn area(&self) -> f32 {
+        self.size.width() * self.size.height()
+    }
+
+    ///Smallest box containing both boxes
+    pub fn merge(&self, other: &BoundingBox) -> BoundingBox {
+        let (s_min, s_max) = (self.min(), self.max());
+        let (o_min, o_max) = (other.min(), other.max());
+        let min = Vector::new(s_min.x.min(o_min.x), s_min.y.min(o_min.y));
+        let max = Vector::new(s_max.x.max(o_max.x), s_max.y.max(o_max.y));
+        BoundingBox {
+            anchor: Vector::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0),
+            size: Size::new(max.x - min.x, max.y - min.y),
+        }
+    }
+
+    ///Expands the box by margin on every side, a negative margin shrinks it
+    pub fn grow(&self, margin: f32) -> BoundingBox {
+        BoundingBox {
+            anchor: self.anchor.clone(),
+            size: Size::new(
+                self.size.width() + 2.0 * margin,
+                self.size.height() + 2.0 * margin,
+            ),
+        }
+    }
+
     fn contains_point(&self, point: &Vector<f32>) -> bool {
         let offset = point - &self.anchor;
         let width = self.size.width() / 2.0;
@@ -82,6 +154,17 @@ impl BoundingBox {
     }
 }
 
+impl From<(Position<f32>, Size<f32>)> for BoundingBox {
+    fn from((anchor, size): (Position<f32>, Size<f32>)) -> Self {
+        Self::new(anchor, size)
+    }
+}
+impl From<(Vector<f32>, Size<f32>)> for BoundingBox {
+    fn from((anchor, size): (Vector<f32>, Size<f32>)) -> Self {
+        Self { anchor, size }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,4 +180,74 @@ mod tests {
         assert!(bb.contains_point(&Vector::new(400.0, 300.0)));
         assert!(bb.contains_box(&bb));
     }
+
+    #[test]
+    fn new() {
+        let bb = BoundingBox::new(Position::new(10.0, 20.0), Size::new(4.0, 2.0));
+        assert_eq!((bb.anchor.x, bb.anchor.y), (10.0, 20.0));
+        assert_eq!((bb.position().x(), bb.position().y()), (10.0, 20.0));
+        let from_position = BoundingBox::from((Position::new(10.0, 20.0), Size::new(4.0, 2.0)));
+        let from_vector = BoundingBox::from((Vector::new(10.0, 20.0), Size::new(4.0, 2.0)));
+        for bb in [from_position, from_vector] {
+            assert_eq!((bb.min().x, bb.min().y), (8.0, 19.0));
+            assert_eq!((bb.size.width(), bb.size.height()), (4.0, 2.0));
+        }
+    }
+
+    #[test]
+    fn corners() {
+        let bb = BoundingBox {
+            anchor: Vector::new(10.0, 20.0),
+            size: Size::new(4.0, 2.0),
+        };
+        let [a, b, c, d] = bb.corners();
+        assert_eq!((a.x, a.y), (8.0, 19.0));
+        assert_eq!((b.x, b.y), (12.0, 19.0));
+        assert_eq!((c.x, c.y), (12.0, 21.0));
+        assert_eq!((d.x, d.y), (8.0, 21.0));
+        assert_eq!(bb.area(), 8.0);
+    }
+
+    #[test]
+    fn merge_disjoint() {
+        let a = BoundingBox {
+            anchor: Vector::new(-10.0, 0.0),
+            size: Size::new(2.0, 2.0),
+        };
+        let b = BoundingBox {
+            anchor: Vector::new(10.0, 5.0),
+            size: Size::new(4.0, 2.0),
+        };
+        let merged = a.merge(&b);
+        assert_eq!((merged.anchor.x, merged.anchor.y), (0.5, 2.5));
+        assert_eq!((merged.size.width(), merged.size.height()), (23.0, 7.0));
+        assert!(merged.contains_box(&a));
+        assert!(merged.contains_box(&b));
+    }
+
+    #[test]
+    fn merge_nested() {
+        let outer = BoundingBox {
+            anchor: Vector::new(0.0, 0.0),
+            size: Size::new(10.0, 10.0),
+        };
+        let inner = BoundingBox {
+            anchor: Vector::new(1.0, -1.0),
+            size: Size::new(2.0, 2.0),
+        };
+        let merged = inner.merge(&outer);
+        assert_eq!((merged.anchor.x, merged.anchor.y), (0.0, 0.0));
+        assert_eq!((merged.size.width(), merged.size.height()), (10.0, 10.0));
+    }
+
+    #[test]
+    fn grow() {
+        let bb = BoundingBox {
+            anchor: Vector::new(3.0, 4.0),
+            size: Size::new(2.0, 6.0),
+        }
+        .grow(1.5);
+        assert_eq!((bb.anchor.x, bb.anchor.y), (3.0, 4.0));
+        assert_eq!((bb.size.width(), bb.size.height()), (5.0, 9.0));
+    }
 }
@@ -4,27 +4,33 @@ use std::{
     time::Duration,
 };
 
-use log::error;
+use log::{info, warn};
 
-use crate::{graphics::UniformBufferName, Size};
+use crate::{graphics::UniformBufferName, Position, Size};
 use twod::Vector;
 use winit::{
     event::KeyEvent,
     keyboard::{KeyCode, PhysicalKey},
+    window::WindowId,
 };
 
-use crate::game_engine::{BoundingBox, Direction, VelocityController};
+use crate::game_engine::{BoundingBox, Direction, VelocityController, WorldView};
 
 use super::{
     entity::{EntityName, EntityType},
+    ressource_descriptor::WindowName,
     Entity, ExternalEvent,
 };
 
 pub mod exports {
-    pub use super::{static_camera, Camera, CameraDescriptor};
+    pub use super::{
+        static_camera, Camera, CameraAspect, CameraDescriptor, CameraEasing,
+        DEFAULT_CAMERA_DECELERATION_THRESHOLD,
+    };
 }
 
-const CAMERA_DECELERATION_THRESHOLD: f32 = 1e-4;
+///Velocity below which the camera offset starts returning to the target
+pub const DEFAULT_CAMERA_DECELERATION_THRESHOLD: f32 = 1e-4;
 
 pub fn static_camera(view_size: Size<f32>) -> [[f32; 2]; 3] {
     [
@@ -57,6 +63,59 @@ impl From<&Camera> for CameraUniform {
     }
 }
 
+///How the camera reacts to the aspect ratio of its window changing
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CameraAspect {
+    ///Keep the view_size, stretching the world with the window
+    #[default]
+    Stretch,
+    ///Keep the whole view_size visible and show more of the world along the longer axis
+    Expand,
+    ///Fill the window with the view_size and cut off the world along the shorter axis
+    Crop,
+}
+impl CameraAspect {
+    fn view_size(&self, design_size: &Size<f32>, window_size: &Size<f32>) -> Size<f32> {
+        if window_size.width() <= 0.0 || window_size.height() <= 0.0 {
+            return design_size.clone();
+        }
+        let window_aspect = window_size.width() / window_size.height();
+        let design_aspect = design_size.width() / design_size.height();
+        let keep_height = match self {
+            CameraAspect::Stretch => return design_size.clone(),
+            CameraAspect::Expand => window_aspect > design_aspect,
+            CameraAspect::Crop => window_aspect <= design_aspect,
+        };
+        if keep_height {
+            Size::new(design_size.height() * window_aspect, design_size.height())
+        } else {
+            Size::new(design_size.width(), design_size.width() / window_aspect)
+        }
+    }
+}
+
+///How the camera offset returns to the target once it stops moving
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CameraEasing {
+    ///Shrink the offset by 1 / acceleration_steps of itself every frame, slowing down near the
+    ///target
+    #[default]
+    Exponential,
+    ///Shrink the offset by max_offset_position / acceleration_steps every frame, stopping
+    ///abruptly at the target
+    Linear,
+}
+impl CameraEasing {
+    fn decelerate(&self, offset: f32, steps: f32, max_offset: f32) -> f32 {
+        match self {
+            CameraEasing::Exponential => offset * (1.0 - 1.0 / steps),
+            CameraEasing::Linear => {
+                offset - offset.signum() * (max_offset / steps).min(offset.abs())
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CameraDescriptor {
     pub name: UniformBufferName,
@@ -68,7 +127,13 @@ pub struct CameraDescriptor {
     ///The cameras bounding box described by position and view_size will stay inside this
     ///bounding box
     pub bound_entity: Option<EntityName>,
+    ///World rectangle restricting the camera like bound_entity, eg. the size of a fixed level.
+    ///Takes precedence over bound_entity
+    pub bound_rect: Option<BoundingBox>,
     pub max_offset_position: f32,
+    ///Velocity below which the offset returns, usually DEFAULT_CAMERA_DECELERATION_THRESHOLD
+    pub deceleration_threshold: f32,
+    pub easing: CameraEasing,
 }
 impl From<&CameraDescriptor> for Camera {
     fn from(descriptor: &CameraDescriptor) -> Self {
@@ -76,7 +141,7 @@ impl From<&CameraDescriptor> for Camera {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum CameraUpdateFailed {
     NoTargetEntity(EntityName),
     NOBoundEntity(EntityName),
@@ -105,11 +170,19 @@ pub struct Camera {
     position: Vector<f32>,
     offset_position: Vector<f32>,
     max_offset: f32,
-    decceleration_factor: f32,
+    acceleration_steps: f32,
+    deceleration_threshold: f32,
+    easing: CameraEasing,
     velocity: VelocityController,
     view_size: Size<f32>,
+    design_size: Size<f32>,
+    aspect: CameraAspect,
     target_entity: EntityName,
+    target_position: Option<Vector<f32>>,
     bound_entity: Option<EntityName>,
+    bound_rect: Option<BoundingBox>,
+    ///Logged once, until the update succeeds again
+    failure: Option<CameraUpdateFailed>,
 }
 impl Camera {
     fn new(descriptor: &CameraDescriptor) -> Self {
@@ -119,13 +192,39 @@ impl Camera {
             position: Vector::new(0.0, 0.0),
             offset_position: Vector::new(0.0, 0.0),
             max_offset: descriptor.max_offset_position,
-            decceleration_factor: 1.0 - 1.0 / descriptor.acceleration_steps as f32,
+            acceleration_steps: descriptor.acceleration_steps as f32,
+            deceleration_threshold: descriptor.deceleration_threshold,
+            easing: descriptor.easing,
             velocity: VelocityController::new(
                 descriptor.speed / descriptor.acceleration_steps as f32,
             ),
             view_size: descriptor.view_size.clone(),
+            design_size: descriptor.view_size.clone(),
+            aspect: CameraAspect::default(),
             bound_entity: descriptor.bound_entity.clone(),
+            bound_rect: descriptor.bound_rect.clone(),
             target_entity: descriptor.target_entity.clone(),
+            target_position: None,
+            failure: None,
+        }
+    }
+
+    ///Adapt the view_size to the window's aspect ratio whenever it is resized
+    pub fn with_aspect(mut self, aspect: CameraAspect) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    ///Follow a world position instead of the target entity, eg. for a scripted pan. None
+    ///follows the target entity again
+    pub fn set_target_position(&mut self, target_position: Option<Vector<f32>>) {
+        self.target_position = target_position;
+    }
+
+    fn report_failure(&mut self, failure: CameraUpdateFailed) {
+        if self.failure.as_ref() != Some(&failure) {
+            warn!("{}", failure);
+            self.failure = Some(failure);
         }
     }
 
@@ -134,6 +233,29 @@ impl Camera {
         self.offset_position = Vector::scalar(0.0);
     }
 
+    ///Invert the camera's projection of a window-centered pixel position
+    pub fn window_to_world(
+        &self,
+        position: &Position<i32>,
+        window_size: &Size<u32>,
+    ) -> Vector<f32> {
+        let center = &self.position + &self.offset_position;
+        let scale_x = self.view_size.width() / window_size.width().max(1) as f32;
+        let scale_y = self.view_size.height() / window_size.height().max(1) as f32;
+        Vector::new(
+            center.x + position.x() as f32 * scale_x,
+            center.y - position.y() as f32 * scale_y,
+        )
+    }
+
+    ///The area of the world that is visible
+    pub fn view_rect(&self) -> BoundingBox {
+        BoundingBox {
+            anchor: &self.position + &self.offset_position,
+            size: self.view_size.clone(),
+        }
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut v = Vec::new();
         v.extend_from_slice(bytemuck::cast_slice(&CameraUniform::from(self).view));
@@ -153,47 +275,59 @@ impl Debug for Camera {
 impl<T: EntityType, E: ExternalEvent> Entity<T, E> for Camera {
     fn update(
         &mut self,
-        entities: &Vec<&Box<dyn Entity<T, E>>>,
+        world: &mut WorldView<T, E>,
         _delta_t: &Duration,
         _scene: &super::SceneName,
     ) -> Vec<E> {
-        let target_entity = match entities
-            .iter()
-            .find(|entity| entity.name() == &self.target_entity)
+        let target_position = match self
+            .target_position
+            .clone()
+            .or_else(|| world.get(&self.target_entity).map(|e| e.position()))
         {
-            Some(entity) => entity,
+            Some(position) => position,
             None => {
-                error!(
-                    "{}",
-                    CameraUpdateFailed::NoTargetEntity(self.target_entity.clone(),)
-                );
+                self.report_failure(CameraUpdateFailed::NoTargetEntity(
+                    self.target_entity.clone(),
+                ));
                 return vec![];
             }
         };
         let velocity = self.velocity.get_velocity();
-        if velocity.x.abs() <= CAMERA_DECELERATION_THRESHOLD {
-            self.offset_position.x *= self.decceleration_factor;
+        if velocity.x.abs() <= self.deceleration_threshold {
+            self.offset_position.x = self.easing.decelerate(
+                self.offset_position.x,
+                self.acceleration_steps,
+                self.max_offset,
+            );
         }
-        if velocity.y.abs() <= CAMERA_DECELERATION_THRESHOLD {
-            self.offset_position.y *= self.decceleration_factor;
+        if velocity.y.abs() <= self.deceleration_threshold {
+            self.offset_position.y = self.easing.decelerate(
+                self.offset_position.y,
+                self.acceleration_steps,
+                self.max_offset,
+            );
         }
         self.offset_position += velocity;
         if self.offset_position.magnitude_squared() >= self.max_offset.powi(2) {
             self.offset_position = self.offset_position.normalize() * self.max_offset;
         }
-        self.position = target_entity.position();
-        if let Some(bound_entity) = &self.bound_entity {
-            let bound_entity = match entities.iter().find(|entity| entity.name() == bound_entity) {
-                Some(entity) => entity,
+        self.position = target_position;
+        let bound = match (self.bound_rect.clone(), self.bound_entity.clone()) {
+            (Some(bound_rect), _) => Some(bound_rect),
+            (None, Some(bound_entity)) => match world.get(&bound_entity) {
+                Some(entity) => Some(entity.bounding_box()),
                 None => {
-                    error!(
-                        "{}",
-                        CameraUpdateFailed::NOBoundEntity(bound_entity.clone())
-                    );
+                    self.report_failure(CameraUpdateFailed::NOBoundEntity(bound_entity));
                     return vec![];
                 }
-            };
-            match bound_entity.bounding_box().clamp_box_inside(&BoundingBox {
+            },
+            (None, None) => None,
+        };
+        if let Some(failure) = self.failure.take() {
+            info!("Camera {:?} recovered from: {}", self.name, failure);
+        }
+        if let Some(bound) = bound {
+            match bound.clamp_box_inside(&BoundingBox {
                 anchor: &self.position + &self.offset_position,
                 size: self.view_size.clone(),
             }) {
@@ -210,6 +344,9 @@ impl<T: EntityType, E: ExternalEvent> Entity<T, E> for Camera {
         _sprite_sheet: Vec<Option<&super::SpriteSheet>>,
     ) {
     }
+    fn is_render_dirty(&self) -> bool {
+        false
+    }
     fn sprite_sheets(&self) -> Vec<&super::SpriteSheetName> {
         vec![]
     }
@@ -249,16 +386,43 @@ impl<T: EntityType, E: ExternalEvent> Entity<T, E> for Camera {
         }
         vec![]
     }
+    fn handle_resize(
+        &mut self,
+        _window: &WindowId,
+        _name: Option<&WindowName>,
+        size: &Size<f32>,
+    ) -> Vec<E> {
+        if self.aspect == CameraAspect::Stretch {
+            return vec![];
+        }
+        self.view_size = self.aspect.view_size(&self.design_size, size);
+        vec![E::update_uniform_buffer(self.uniform_name.clone(), self.as_bytes())]
+    }
     fn name(&self) -> &EntityName {
         &self.name
     }
     fn bounding_box(&self) -> BoundingBox {
-        BoundingBox {
-            anchor: &self.position + &self.offset_position,
-            size: self.view_size.clone(),
-        }
+        self.view_rect()
     }
     fn entity_type(&self) -> T {
         T::default()
     }
+    fn camera(&self) -> Option<&Camera> {
+        Some(self)
+    }
+    fn camera_mut(&mut self) -> Option<&mut Camera> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing() {
+        assert_eq!(CameraEasing::Exponential.decelerate(8.0, 4.0, 8.0), 6.0);
+        assert_eq!(CameraEasing::Linear.decelerate(-3.0, 4.0, 8.0), -1.0);
+        assert_eq!(CameraEasing::Linear.decelerate(1.0, 4.0, 8.0), 0.0);
+    }
 }
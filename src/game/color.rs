@@ -98,6 +98,83 @@ impl Color {
         )
     }
 
+    ///Multiplies the color with its alpha, for render scenes using BlendMode::PremultipliedAlpha.
+    ///With srgb the color is multiplied in linear space and encoded as sRGB again, like sRGB
+    ///textures. Pass the srgb of the texture the color is blended with, or if the shader
+    ///converts the vertex color from sRGB
+    pub fn premultiply(&self, srgb: bool) -> Self {
+        fn to_linear(c: f32) -> f32 {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        fn to_srgb(c: f32) -> f32 {
+            if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        }
+        let [r, g, b, a] = self.to_rgba().to_slice();
+        let alpha = a as f32 / 255.0;
+        let premultiply = |c: u8| {
+            if srgb {
+                (to_srgb(to_linear(c as f32 / 255.0) * alpha) * 255.0).round() as u8
+            } else {
+                (c as f32 * alpha).round() as u8
+            }
+        };
+        Self::new_rgba(premultiply(r), premultiply(g), premultiply(b), a)
+    }
+
+    ///Stable, visually distinct color for an id, eg. to tell entities apart in debug overlays
+    ///Steps the hue by the golden ratio, such that consecutive ids are far apart
+    pub fn from_hash(value: u64) -> Self {
+        let hue = (value.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 56) as u8;
+        Self::new_hsva(hue, 200, 230, 255).to_rgba()
+    }
+
+    ///Component wise product in rgba, eg. to tint a vertex color. White keeps the color as it is
+    pub fn multiply(&self, other: &Self) -> Self {
+        let a = self.to_rgba().to_slice();
+        let b = other.to_rgba().to_slice();
+        let mut c = [0; 4];
+        for i in 0..4 {
+            c[i] = ((a[i] as u16 * b[i] as u16 + 127) / 255) as u8;
+        }
+        Self::rgba_from_slice(&c)
+    }
+
+    ///Linear interpolation in rgba. `t` is clamped to [0, 1], 0 being self
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let a = self.to_rgba().to_slice();
+        let b = other.to_rgba().to_slice();
+        let mut c = [0; 4];
+        for i in 0..4 {
+            c[i] = (a[i] as f32 + (b[i] as f32 - a[i] as f32) * t).round() as u8;
+        }
+        Self::rgba_from_slice(&c)
+    }
+
+    ///Packs the rgba color into a vertex attribute, red in the lowest byte as `unpack4x8unorm`
+    ///expects it in the shader. Unlike casting the bytes, this is the same on every endianness
+    pub fn to_packed_u32(&self) -> u32 {
+        u32::from_le_bytes(self.to_rgba().to_slice())
+    }
+
+    ///Inverse of to_packed_u32
+    pub fn from_packed_u32(packed: u32) -> Self {
+        Self::rgba_from_slice(&packed.to_le_bytes())
+    }
+
+    ///Rgba in [0, 1], eg. for a `vec4<f32>` vertex color
+    pub fn to_rgba_f32(&self) -> [f32; 4] {
+        self.to_rgba().to_slice().map(|c| c as f32 / 255.0)
+    }
+
     pub fn to_slice(&self) -> [u8; 4] {
         match self {
             Self::RGBA(r, g, b, a) => [*r, *g, *b, *a],
@@ -155,4 +232,51 @@ mod tests {
             Color::new_hsva(0, 255, 255, 255).to_rgba();
         }
     }
+
+    #[test]
+    fn lerp() {
+        let black = Color::new_rgba(0, 0, 0, 255);
+        let white = Color::new_rgba(255, 255, 255, 255);
+        assert_eq!(black.lerp(&white, 0.5).to_slice(), [128, 128, 128, 255]);
+        assert_eq!(black.lerp(&white, 2.0).to_slice(), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn from_hash() {
+        for value in [0, 1, 42, u64::MAX] {
+            assert_eq!(
+                Color::from_hash(value).to_slice(),
+                Color::from_hash(value).to_slice()
+            );
+        }
+        assert_ne!(Color::from_hash(1).to_slice(), Color::from_hash(2).to_slice());
+    }
+
+    #[test]
+    fn multiply() {
+        let color = Color::new_rgba(200, 100, 0, 255);
+        let white = Color::new_rgba(255, 255, 255, 255);
+        assert_eq!(color.multiply(&white).to_slice(), color.to_slice());
+        let half_red = Color::new_rgba(255, 128, 128, 128);
+        assert_eq!(color.multiply(&half_red).to_slice(), [200, 50, 0, 128]);
+    }
+
+    #[test]
+    fn packed_u32() {
+        let color = Color::new_rgba(1, 2, 3, 4);
+        assert_eq!(color.to_packed_u32(), 0x04030201);
+        assert_eq!(Color::from_packed_u32(0x04030201).to_slice(), [1, 2, 3, 4]);
+        assert_eq!(color.to_rgba_f32()[3], 4.0 / 255.0);
+    }
+
+    #[test]
+    fn premultiply() {
+        let color = Color::new_rgba(255, 100, 0, 128);
+        assert_eq!(color.premultiply(true).to_slice(), [188, 72, 0, 128]);
+        assert_eq!(color.premultiply(false).to_slice(), [128, 50, 0, 128]);
+        let opaque = Color::new_rgba(10, 100, 200, 255);
+        assert_eq!(opaque.premultiply(true).to_slice(), opaque.to_slice());
+        assert_eq!(opaque.premultiply(false).to_slice(), opaque.to_slice());
+        assert_eq!(Color::new_rgba(255, 255, 255, 0).premultiply(true).to_slice(), [0; 4]);
+    }
 }
@@ -0,0 +1,288 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use log::error;
+use twod::Vector;
+
+use crate::{
+    app::{fits_u16_indices, write_regular_ngon_u16, IndexBuffer, VertexBuffer},
+    game_engine::{example::SimpleVertex, BoundingBox, Color},
+    graphics::{RenderSceneDescriptor, Vertex},
+    Size,
+};
+
+use super::{ExternalEvent, Scene};
+
+pub mod exports {
+    pub use super::{draw_bounding_box, draw_line};
+}
+
+///Writes a quad of `thickness` along the line. With a `feather` a strip fading to transparent is
+///added along both sides, which smooths the edges in render scenes blending alpha. Zero length
+///lines are skipped, as they have no direction, and so are lines not fitting into u16 indices
+pub fn draw_line<V: Vertex>(
+    vertices: &mut VertexBuffer,
+    indices: &mut IndexBuffer,
+    from: &Vector<f32>,
+    to: &Vector<f32>,
+    thickness: f32,
+    feather: f32,
+    color: &Color,
+    create_vertex: fn(Vector<f32>, Color) -> V,
+) {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length <= f32::EPSILON {
+        return;
+    }
+    let quads = if feather > 0.0 { 3 } else { 1 };
+    if !fits_u16_indices(vertices, quads * 4) {
+        error!("Line does not fit into the u16 indices of its render scene");
+        return;
+    }
+    //Normal of the line, scaled to a distance from it
+    let side = |distance: f32| (-dy / length * distance, dx / length * distance);
+    let corner = |point: &Vector<f32>, (x, y): (f32, f32), color: &Color| {
+        create_vertex(Vector::new(point.x + x, point.y + y), color.clone())
+    };
+    let (x, y) = side(thickness / 2.0);
+    write_regular_ngon_u16(
+        vertices,
+        indices,
+        &[
+            corner(from, (x, y), color),
+            corner(to, (x, y), color),
+            corner(to, (-x, -y), color),
+            corner(from, (-x, -y), color),
+        ],
+    );
+    if feather <= 0.0 {
+        return;
+    }
+    let [r, g, b, _] = color.to_rgba().to_slice();
+    let transparent = Color::new_rgba(r, g, b, 0);
+    let (outer_x, outer_y) = side(thickness / 2.0 + feather);
+    for sign in [1.0, -1.0] {
+        let (x, y) = (x * sign, y * sign);
+        let (outer_x, outer_y) = (outer_x * sign, outer_y * sign);
+        write_regular_ngon_u16(
+            vertices,
+            indices,
+            &[
+                corner(from, (outer_x, outer_y), &transparent),
+                corner(to, (outer_x, outer_y), &transparent),
+                corner(to, (x, y), color),
+                corner(from, (x, y), color),
+            ],
+        );
+    }
+}
+
+///Outlines the bounding box with lines centered on its edges, which do not overlap in the corners
+pub fn draw_bounding_box<V: Vertex>(
+    vertices: &mut VertexBuffer,
+    indices: &mut IndexBuffer,
+    bounding_box: &BoundingBox,
+    thickness: f32,
+    color: &Color,
+    create_vertex: fn(Vector<f32>, Color) -> V,
+) {
+    outline(
+        vertices,
+        indices,
+        bounding_box,
+        (thickness, thickness),
+        color,
+        create_vertex,
+    );
+}
+
+///Like draw_bounding_box, with the thickness of the vertical and the horizontal edges
+fn outline<V: Vertex>(
+    vertices: &mut VertexBuffer,
+    indices: &mut IndexBuffer,
+    bounding_box: &BoundingBox,
+    (thickness_x, thickness_y): (f32, f32),
+    color: &Color,
+    create_vertex: fn(Vector<f32>, Color) -> V,
+) {
+    //All four edges or none
+    if !fits_u16_indices(vertices, 16) {
+        error!("Outline does not fit into the u16 indices of its render scene");
+        return;
+    }
+    let (min, max) = (bounding_box.min(), bounding_box.max());
+    let (half_x, half_y) = (thickness_x / 2.0, thickness_y / 2.0);
+    let mut line = |from: Vector<f32>, to: Vector<f32>, thickness: f32| {
+        draw_line(vertices, indices, &from, &to, thickness, 0.0, color, create_vertex)
+    };
+    line(
+        Vector::new(min.x - half_x, min.y),
+        Vector::new(max.x + half_x, min.y),
+        thickness_y,
+    );
+    line(
+        Vector::new(min.x - half_x, max.y),
+        Vector::new(max.x + half_x, max.y),
+        thickness_y,
+    );
+    line(
+        Vector::new(min.x, min.y + half_y),
+        Vector::new(min.x, max.y - half_y),
+        thickness_x,
+    );
+    line(
+        Vector::new(max.x, min.y + half_y),
+        Vector::new(max.x, max.y - half_y),
+        thickness_x,
+    );
+}
+
+///Maps the clip space of a viewport to the clip space of the whole window
+fn to_window_clip(
+    bounding_box: BoundingBox,
+    (x, y, width, height): (f32, f32, f32, f32),
+    window_size: &Size<u32>,
+) -> BoundingBox {
+    let window_width = window_size.width().max(1) as f32;
+    let window_height = window_size.height().max(1) as f32;
+    let (scale_x, scale_y) = (width / window_width, height / window_height);
+    //The viewport is placed from the top left, clip space points up
+    let center_x = (2.0 * x + width) / window_width - 1.0;
+    let center_y = 1.0 - (2.0 * y + height) / window_height;
+    BoundingBox {
+        anchor: Vector::new(
+            center_x + bounding_box.anchor.x * scale_x,
+            center_y + bounding_box.anchor.y * scale_y,
+        ),
+        size: Size::new(
+            bounding_box.size.width() * scale_x,
+            bounding_box.size.height() * scale_y,
+        ),
+    }
+}
+
+///Outlines of the bounding boxes of the visible entities and the view rects of the cameras in clip
+///space, two pixels wide. Each entity keeps its color between frames. Scenes without a camera
+///are expected to be in clip space already. The outlines are projected like the render scene of
+///their scene, with the camera and into the letterboxed viewport of its target_aspect
+pub(crate) fn build_debug_overlay<'a, E: ExternalEvent + 'a>(
+    scenes: impl Iterator<Item = (&'a Scene<E>, &'a RenderSceneDescriptor)>,
+    window_size: &Size<u32>,
+) -> (VertexBuffer, IndexBuffer) {
+    let mut vertices = VertexBuffer::new();
+    let mut indices = IndexBuffer::new();
+    let thickness = (
+        4.0 / window_size.width().max(1) as f32,
+        4.0 / window_size.height().max(1) as f32,
+    );
+    for (scene, render_scene) in scenes {
+        let viewport = render_scene.viewport(window_size.width(), window_size.height());
+        let view = scene
+            .entities
+            .iter()
+            .find_map(|entity| entity.camera())
+            .map(|camera| camera.view_rect());
+        let to_viewport_clip = |bounding_box: BoundingBox| match &view {
+            Some(view) => {
+                let scale_x = 2.0 / view.size.width();
+                let scale_y = 2.0 / view.size.height();
+                BoundingBox {
+                    anchor: Vector::new(
+                        (bounding_box.anchor.x - view.anchor.x) * scale_x,
+                        (bounding_box.anchor.y - view.anchor.y) * scale_y,
+                    ),
+                    size: Size::new(
+                        bounding_box.size.width() * scale_x,
+                        bounding_box.size.height() * scale_y,
+                    ),
+                }
+            }
+            None => bounding_box,
+        };
+        let to_clip =
+            |bounding_box| to_window_clip(to_viewport_clip(bounding_box), viewport, window_size);
+        for entity in scene.entities.iter().filter(|entity| entity.is_visible()) {
+            let (bounding_box, color) = match entity.camera() {
+                Some(camera) => (camera.view_rect(), Color::new_rgba(255, 255, 255, 255)),
+                None => {
+                    let mut hasher = DefaultHasher::new();
+                    entity.name().hash(&mut hasher);
+                    (entity.bounding_box(), Color::from_hash(hasher.finish()))
+                }
+            };
+            outline(
+                &mut vertices,
+                &mut indices,
+                &to_clip(bounding_box),
+                thickness,
+                &color,
+                SimpleVertex::new,
+            );
+        }
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::MAX_U16_VERTICES;
+
+    #[test]
+    fn line_quads() {
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        let color = Color::new_rgba(255, 0, 0, 255);
+        let (from, to) = (Vector::new(0.0, 0.0), Vector::new(1.0, 1.0));
+        draw_line(&mut vertices, &mut indices, &from, &to, 0.1, 0.0, &color, SimpleVertex::new);
+        assert_eq!((vertices.len(), indices.len()), (4, 6));
+        draw_line(&mut vertices, &mut indices, &from, &to, 0.1, 0.05, &color, SimpleVertex::new);
+        assert_eq!((vertices.len(), indices.len()), (16, 24));
+    }
+
+    #[test]
+    fn zero_length_line() {
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        let point = Vector::new(2.0, 3.0);
+        let color = Color::new_rgba(255, 0, 0, 255);
+        draw_line(&mut vertices, &mut indices, &point, &point, 1.0, 1.0, &color, SimpleVertex::new);
+        assert_eq!((vertices.len(), indices.len()), (0, 0));
+    }
+
+    #[test]
+    fn u16_limit() {
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        let color = Color::new_rgba(255, 0, 0, 255);
+        let filler = SimpleVertex::new(Vector::new(0.0, 0.0), color.clone());
+        vertices.extend_from_slice(&vec![filler; MAX_U16_VERTICES - 8]);
+        let (from, to) = (Vector::new(0.0, 0.0), Vector::new(1.0, 1.0));
+        //The feathered line needs 12 vertices, the plain one 4
+        draw_line(&mut vertices, &mut indices, &from, &to, 0.1, 0.05, &color, SimpleVertex::new);
+        assert_eq!(vertices.len() as usize, MAX_U16_VERTICES - 8);
+        draw_line(&mut vertices, &mut indices, &from, &to, 0.1, 0.0, &color, SimpleVertex::new);
+        assert_eq!((vertices.len() as usize, indices.len()), (MAX_U16_VERTICES - 4, 6));
+    }
+
+    #[test]
+    fn letterboxed_clip_space() {
+        let full = BoundingBox {
+            anchor: Vector::new(0.0, 0.0),
+            size: Size::new(2.0, 2.0),
+        };
+        //A square viewport centered in a window twice as wide
+        let window_size = Size::new(200, 100);
+        let clip = to_window_clip(full.clone(), (50.0, 0.0, 100.0, 100.0), &window_size);
+        assert_eq!((clip.anchor.x, clip.anchor.y), (0.0, 0.0));
+        assert_eq!((clip.size.width(), clip.size.height()), (1.0, 2.0));
+        //The top half of a window twice as high
+        let window_size = Size::new(100, 200);
+        let clip = to_window_clip(full, (0.0, 0.0, 100.0, 100.0), &window_size);
+        assert_eq!((clip.anchor.x, clip.anchor.y), (0.0, 0.5));
+        assert_eq!((clip.size.width(), clip.size.height()), (2.0, 1.0));
+    }
+}
@@ -1,15 +1,20 @@
 use crate::{
-    app::{IndexBuffer, MouseEvent, VertexBuffer},
+    app::{IndexBuffer, MouseEvent, TextInputEvent, TouchEvent, VertexBuffer},
     game_engine::BoundingBox,
-    create_name_struct,
+    graphics::RenderSceneName,
+    create_name_struct, Size,
 };
 use std::{fmt::Debug, time::Duration};
 use twod::Vector;
-use winit::event::KeyEvent;
+use winit::{event::KeyEvent, keyboard::ModifiersState, window::WindowId};
 
 use super::{
-    ressource_descriptor::SpriteSheetName, sprite_sheet::SpriteSheet, ExternalEvent,
-    SceneName,
+    action_map::ActionEvent,
+    camera::Camera,
+    ressource_descriptor::{SpriteSheetName, WindowName},
+    sprite_sheet::SpriteSheet,
+    world_view::WorldView,
+    ExternalEvent, SceneName,
 };
 
 pub mod exports {
@@ -21,27 +26,80 @@ create_name_struct!(EntityName);
 pub trait EntityType: PartialEq + Debug + Default {}
 
 pub trait Entity<T: EntityType, E: ExternalEvent>: Debug + Send {
+    ///`world` holds all other entities of the scene
     fn update(
         &mut self,
-        _entities: &Vec<&Box<dyn Entity<T, E>>>,
+        _world: &mut WorldView<T, E>,
         _delta_t: &Duration,
         _scene: &SceneName,
     ) -> Vec<E> {
         vec![]
     }
+    ///`sprite_sheet` holds the sheets of `sprite_sheets` in order. None means the sheet is not
+    ///loaded (yet). Instead of unwrapping, pass it through SpriteSheet::or_placeholder to draw a
+    ///magenta placeholder. The scene is rendered again once the sheet arrives
     fn render(
         &mut self,
         vertices: &mut VertexBuffer,
         indices: &mut IndexBuffer,
         sprite_sheet: Vec<Option<&SpriteSheet>>,
     );
+    ///Return false if render would produce the same geometry as last time. If no visible entity
+    ///of a scene changed, the scene skips rebuilding and uploading its buffers. Entities that
+    ///change their visibility have to report it here as well
+    fn is_render_dirty(&self) -> bool {
+        true
+    }
+    ///The render scene `render` draws into. None is the main render scene of the scene, otherwise
+    ///one of its extra render scenes, eg. for a HUD entity of a world scene. Entities naming a
+    ///render scene their scene does not have are not drawn
+    fn render_scene(&self) -> Option<&RenderSceneName> {
+        None
+    }
+    ///Called once for every extra render scene of the scene, after render
+    fn render_extra(
+        &mut self,
+        _render_scene: &RenderSceneName,
+        _vertices: &mut VertexBuffer,
+        _indices: &mut IndexBuffer,
+        _sprite_sheet: Vec<Option<&SpriteSheet>>,
+    ) {
+    }
     fn sprite_sheets(&self) -> Vec<&SpriteSheetName>;
     fn handle_key_input(&mut self, _input: &KeyEvent) -> Vec<E> { 
         vec![]
     }
+    ///Like handle_key_input, but with the modifier keys held at the time, eg. for shortcuts
+    fn handle_key_input_with_modifiers(
+        &mut self,
+        input: &KeyEvent,
+        _modifiers: &ModifiersState,
+    ) -> Vec<E> {
+        self.handle_key_input(input)
+    }
     fn handle_mouse_input(&mut self, _input: &MouseEvent) -> Vec<E> {
         vec![]
     }
+    ///Actions of the ActionMap of the Game, triggered by their bound keys or mouse buttons
+    fn handle_action(&mut self, _input: &ActionEvent) -> Vec<E> {
+        vec![]
+    }
+    fn handle_touch_input(&mut self, _input: &TouchEvent) -> Vec<E> {
+        vec![]
+    }
+    fn handle_text_input(&mut self, _input: &TextInputEvent) -> Vec<E> {
+        vec![]
+    }
+    ///Called when the window of the scene is resized. `size` is the new logical size, `name` the
+    ///name of the window in the RessourceDescriptor, if it has one
+    fn handle_resize(
+        &mut self,
+        _window: &WindowId,
+        _name: Option<&WindowName>,
+        _size: &Size<f32>,
+    ) -> Vec<E> {
+        vec![]
+    }
     fn name(&self) -> &EntityName;
     fn bounding_box(&self) -> BoundingBox;
     fn entity_type(&self) -> T;
@@ -52,7 +110,25 @@ pub trait Entity<T: EntityType, E: ExternalEvent>: Debug + Send {
     fn position(&self) -> Vector<f32> {
         self.bounding_box().anchor
     }
+    ///Disabled entities are not updated, but stay in the scene with their state, eg. a pickup
+    ///waiting to respawn
+    fn is_enabled(&self) -> bool {
+        true
+    }
+    ///Hidden entities are not rendered
+    fn is_visible(&self) -> bool {
+        true
+    }
     fn delete_child_entity(&mut self, _name: &EntityName) {}
+    ///Cameras return themselves, so mouse positions can be transformed into the world
+    fn camera(&self) -> Option<&Camera> {
+        None
+    }
+    ///Cameras return themselves, so they can be steered inside a scene, eg. with
+    ///`Camera::set_target_position`
+    fn camera_mut(&mut self) -> Option<&mut Camera> {
+        None
+    }
     fn handle_event(&mut self, _event: E::EntityEvent) -> Vec<E> {
         vec![]
     }
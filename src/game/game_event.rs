@@ -5,20 +5,32 @@ use std::{
 };
 
 use crate::{
-    app::{ApplicationEvent, IndexBuffer, VertexBuffer, WindowDescriptor},
+    app::{ApplicationEvent, CoalesceKey, IndexBuffer, VertexBuffer, WindowDescriptor},
     graphics::{
-        RenderSceneDescriptor, RenderSceneName, ShaderDescriptor, UniformBufferName, Visibility,
+        RenderSceneDescriptor, RenderSceneName, RenderStats, ScissorRect, ShaderDescriptor,
+        UniformBufferName, Visibility,
     },
     game_engine::{EntityName, EntityType},
+    Size,
 };
-use winit::window::WindowId;
+use winit::window::{CursorGrabMode, CursorIcon, WindowId, WindowLevel};
 
-use super::{Entity, Scene, SceneName};
+use super::action_map::{ActionName, Binding};
+use super::{Entity, Scene, SceneName, SceneStatus, UniformTween};
 
+#[cfg(feature = "audio")]
+use super::audio::SoundName;
 use super::ressource_descriptor::{SpriteSheetName, WindowName};
 
 pub mod exports {
-    pub use super::ExternalEvent;
+    pub use super::{CursorRequest, ExternalEvent};
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CursorRequest {
+    Visible(bool),
+    Grab(CursorGrabMode),
+    Icon(CursorIcon),
 }
 
 #[derive(Debug)]
@@ -28,8 +40,14 @@ pub enum GameEvent<E: ExternalEvent> {
     NewWindow(WindowId, WindowName),
     RequestNewWindow(WindowDescriptor, WindowName),
     RenderUpdate(RenderSceneName, VertexBuffer, IndexBuffer),
+    ///The updates of several render scenes, written to the GPU at once
+    RenderBatch(Vec<(RenderSceneName, VertexBuffer, IndexBuffer)>),
+    ///The window was resized to the given logical size, named or not
+    WindowResized(WindowId, Size<f32>),
     NewSpriteSheet(SpriteSheetName, Option<u32>),
     RequestNewSpriteSheet(SpriteSheetName, PathBuf),
+    ///Frees the texture of the sprite sheet
+    RequestRemoveSpriteSheet(SpriteSheetName),
     NewRenderScene(RenderSceneName),
     RequestNewRenderScene(
         WindowId,
@@ -40,6 +58,17 @@ pub enum GameEvent<E: ExternalEvent> {
         Vec<(UniformBufferName, Vec<u8>, wgpu::ShaderStages)>,
     ),
     RequestSetVisibilityRenderScene(RenderSceneName, Visibility),
+    RequestCursor(WindowName, CursorRequest),
+    RequestImeAllowed(WindowName, bool),
+    RequestWindowLevel(WindowName, WindowLevel),
+    ///Cap the redraws of each window at the fps, None removes the limit
+    RequestFrameLimit(Option<f32>),
+    DeviceLost,
+    ///The device of `GraphicsDescriptor::async_init` is ready
+    GraphicsReady,
+    ///Play the sound once at the given volume
+    #[cfg(feature = "audio")]
+    PlaySound(SoundName, f32),
     External(E),
     EndGame,
 }
@@ -71,6 +100,18 @@ impl<E: ExternalEvent> ApplicationEvent for GameEvent<E> {
         }
     }
 
+    fn is_render_batch(&self) -> bool {
+        matches!(self, Self::RenderBatch(_))
+    }
+
+    fn consume_render_batch(self) -> Vec<(RenderSceneName, VertexBuffer, IndexBuffer)> {
+        if let Self::RenderBatch(updates) = self {
+            updates
+        } else {
+            panic!("Test if it is a render batch, before trying to consume the event as one")
+        }
+    }
+
     fn is_request_new_texture<'a>(&'a self) -> Option<(&'a Path, &'a str)> {
         if let Self::RequestNewSpriteSheet(label, path) = self {
             Some((path, label.as_str()))
@@ -79,6 +120,14 @@ impl<E: ExternalEvent> ApplicationEvent for GameEvent<E> {
         }
     }
 
+    fn is_request_remove_texture<'a>(&'a self) -> Option<&'a str> {
+        if let Self::RequestRemoveSpriteSheet(label) = self {
+            Some(label.as_str())
+        } else {
+            None
+        }
+    }
+
     fn is_request_set_visibility_render_scene<'a>(
         &'a self,
     ) -> Option<(&'a RenderSceneName, &'a Visibility)> {
@@ -133,18 +182,72 @@ impl<E: ExternalEvent> ApplicationEvent for GameEvent<E> {
     fn is_quit(&self) -> bool {
         matches!(self, Self::EndGame)
     }
+
+    fn is_request_frame_limit(&self) -> Option<Option<f32>> {
+        match self {
+            Self::RequestFrameLimit(max_fps) => Some(*max_fps),
+            _ => None,
+        }
+    }
+
+    fn device_lost() -> Option<Self> {
+        Some(Self::DeviceLost)
+    }
+
+    ///Batches only hold the render scenes that changed, so the render scenes missing in the later
+    ///batch keep the buffers of the earlier one
+    fn coalesce(&mut self, earlier: Self) {
+        if let (Self::RenderBatch(updates), Self::RenderBatch(earlier)) = (self, earlier) {
+            let mut merged = earlier
+                .into_iter()
+                .filter(|(render_scene, _, _)| updates.iter().all(|(r, _, _)| r != render_scene))
+                .collect::<Vec<_>>();
+            merged.append(updates);
+            *updates = merged;
+        }
+    }
+
+    fn graphics_ready() -> Option<Self> {
+        Some(Self::GraphicsReady)
+    }
+
+    fn coalesce_key(&self) -> Option<CoalesceKey> {
+        match self {
+            Self::RenderUpdate(render_scene, _, _) => {
+                Some(CoalesceKey::RenderScene(render_scene.clone()))
+            }
+            Self::RenderBatch(_) => Some(CoalesceKey::RenderBatch),
+            Self::External(event) => event
+                .is_update_uniform_buffer()
+                .map(|(name, _)| CoalesceKey::UniformBuffer(name.clone())),
+            _ => None,
+        }
+    }
+
+    fn needs_device(&self) -> bool {
+        match self {
+            Self::External(event) => {
+                event.is_update_uniform_buffer().is_some()
+                    || event.is_request_set_scene_z_index().is_some()
+                    || event.is_request_set_scissor_scene().is_some()
+            }
+            _ => false,
+        }
+    }
 }
 
 pub trait ExternalEvent: Debug + Send + Default {
     type EntityType: EntityType;
     type EntityEvent: Debug;
     fn is_request_render_scene<'a>(&'a self) -> Option<&'a SceneName>;
+    ///Entity events are delivered to the entities of active scenes and of suspended scenes with
+    ///`Scene::receive_while_suspended`, otherwise they are dropped with a warning
     fn is_entity_event<'a>(&'a self) -> bool;
     /// Should only be called if is_entity_event returns true
     fn consume_entity_event(self) -> Option<(EntityName, Self::EntityEvent)>;
     fn is_request_set_visibility_scene<'a>(&'a self) -> Option<(&'a SceneName, &'a Visibility)>;
-    ///Suspended scenes will now longer update their buffers, but will still be rendered in their
-    ///current state
+    ///Suspended scenes will no longer update their entities nor their buffers, but will still be
+    ///rendered in their current state
     fn is_request_suspend_scene<'a>(&'a self) -> Option<&'a SceneName>;
     fn is_request_activate_suspended_scene<'a>(&'a self) -> Option<&'a SceneName>;
     ///Deleting a scene will remove it entirely from the game, such that it cannot be rendere again
@@ -170,6 +273,129 @@ pub trait ExternalEvent: Debug + Send + Default {
     where
         Self: Sized;
     fn is_end_game(&self) -> bool;
+    ///Change the cursor of a window, eg. hide and lock it for mouse look
+    fn is_request_cursor<'a>(&'a self) -> Option<(&'a WindowName, &'a CursorRequest)> {
+        None
+    }
+    ///Change the frame limit of `ManagerApplication::with_frame_limit` at runtime, eg. from the
+    ///settings menu. None removes it
+    fn is_request_frame_limit(&self) -> Option<Option<f32>> {
+        None
+    }
+    ///Change the level of a window at runtime, eg. to toggle always on top
+    fn is_request_window_level<'a>(&'a self) -> Option<(&'a WindowName, WindowLevel)> {
+        None
+    }
+    ///Animate a uniform buffer, overriding a running tween of the same uniform
+    fn is_request_tween_uniform<'a>(&'a self) -> Option<&'a UniformTween> {
+        None
+    }
+    ///Sent once a tween reached its end value
+    fn uniform_tween_finished(_uniform: &UniformBufferName) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Sent after new_scene, once all sprite sheets of the entities of the scene were loaded, eg. to
+    ///start a fade in. Sprite sheets failing to load count as loaded, as the placeholder is drawn
+    fn scene_ready(_scene: &SceneName) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Sent once the preloaded sprite sheets are loaded, if the Game was created with_preloading.
+    ///The scenes held back are activated next
+    fn preload_finished() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Show or hide the debug overlay, if the Game was created with_debug_overlay
+    fn is_request_toggle_debug_overlay(&self) -> bool {
+        false
+    }
+    ///Allow input methods on a window, eg. while a text field is focused
+    fn is_request_ime_allowed<'a>(&'a self) -> Option<(&'a WindowName, bool)> {
+        None
+    }
+    ///Restack a scene, eg. bring a dialog to the front. Higher z_indices are drawn on top
+    fn is_request_set_scene_z_index<'a>(&'a self) -> Option<(&'a SceneName, i32)> {
+        None
+    }
+    ///Clip the render scene of a scene to a pixel rectangle, eg. a scrollable list to its panel.
+    ///None removes the clipping
+    fn is_request_set_scissor_scene<'a>(&'a self) -> Option<(&'a SceneName, Option<ScissorRect>)> {
+        None
+    }
+    ///Ask whether a scene is pending, active or suspended. Answered with scene_status
+    fn is_request_scene_status<'a>(&'a self) -> Option<&'a SceneName> {
+        None
+    }
+    ///Answer to is_request_scene_status, None if no scene with the name is known
+    fn scene_status(_scene: &SceneName, _status: Option<SceneStatus>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Replace all bindings of an action of the ActionMap, eg. after the player picked a new key
+    fn is_request_rebind_action<'a>(&'a self) -> Option<(&'a ActionName, &'a [Binding])> {
+        None
+    }
+    ///Ask whether a scene was last requested visible or hidden. Answered with scene_visibility
+    fn is_request_scene_visibility<'a>(&'a self) -> Option<&'a SceneName> {
+        None
+    }
+    ///Answer to is_request_scene_visibility, None if no scene with the name is known
+    fn scene_visibility(_scene: &SceneName, _visibility: Option<Visibility>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Ask for the counts of the last frame rendered to a window. Answered with render_stats
+    fn is_request_render_stats<'a>(&'a self) -> Option<&'a WindowName> {
+        None
+    }
+    ///Answer to is_request_render_stats, None if nothing was rendered to the window yet
+    fn render_stats(_window: &WindowName, _stats: Option<RenderStats>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Sent once the device of `GraphicsDescriptor::async_init` is ready and the requests held back
+    ///meanwhile were handled
+    fn graphics_ready() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Sent once a window of the RessourceDescriptor is created, eg. to tell which window the
+    ///WindowId of a raw winit event belongs to
+    fn window_created(_window: &WindowName, _id: &WindowId) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Sent when a window is created and whenever its scale factor changes, eg. when it is moved
+    ///to a HiDPI display
+    fn scale_factor_changed(_window: &WindowName, _scale_factor: f64) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Play a sound from the RessourceDescriptor once, eg. for footsteps or hits
+    #[cfg(feature = "audio")]
+    fn is_play_sound<'a>(&'a self) -> Option<(&'a SoundName, f32)> {
+        None
+    }
 }
 
 pub mod example {
@@ -271,3 +497,99 @@ pub mod example {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::example::EmptyExternalEvent;
+    use super::*;
+
+    #[test]
+    fn coalesce_render_batches() {
+        let update = |name: &str| (name.into(), VertexBuffer::new(), IndexBuffer::new());
+        let earlier = GameEvent::<EmptyExternalEvent>::RenderBatch(vec![update("a"), update("b")]);
+        let mut later = GameEvent::RenderBatch(vec![update("b"), update("c")]);
+        assert_eq!(later.coalesce_key(), Some(CoalesceKey::RenderBatch));
+        later.coalesce(earlier);
+        let render_scenes = later
+            .consume_render_batch()
+            .into_iter()
+            .map(|(render_scene, _, _)| render_scene)
+            .collect::<Vec<RenderSceneName>>();
+        assert_eq!(render_scenes, vec!["a".into(), "b".into(), "c".into()]);
+    }
+
+    #[derive(Debug, Default)]
+    enum UniformEvent {
+        #[default]
+        Other,
+        Update(UniformBufferName, Vec<u8>),
+    }
+    impl ExternalEvent for UniformEvent {
+        type EntityType = example::EmptyEntityType;
+        type EntityEvent = example::EmptyEntityEvent;
+        fn is_end_game(&self) -> bool {
+            false
+        }
+        fn is_request_new_scenes<'a>(&'a self) -> bool {
+            false
+        }
+        fn is_request_render_scene<'a>(&'a self) -> Option<&'a SceneName> {
+            None
+        }
+        fn is_entity_event<'a>(&'a self) -> bool {
+            false
+        }
+        fn consume_entity_event(self) -> Option<(EntityName, Self::EntityEvent)> {
+            None
+        }
+        fn is_request_delete_scene<'a>(&'a self) -> Option<&'a SceneName> {
+            None
+        }
+        fn is_request_suspend_scene<'a>(&'a self) -> Option<&'a SceneName> {
+            None
+        }
+        fn is_add_entities<'a>(&'a self) -> bool {
+            false
+        }
+        fn is_request_set_visibility_scene<'a>(
+            &'a self,
+        ) -> Option<(&'a SceneName, &'a Visibility)> {
+            None
+        }
+        fn is_request_activate_suspended_scene<'a>(&'a self) -> Option<&'a SceneName> {
+            None
+        }
+        fn consume_scenes_request(self) -> Option<Vec<Scene<Self>>> {
+            None
+        }
+        fn new_scene(_scene: &Scene<Self>) -> Self {
+            Self::Other
+        }
+        fn consume_add_entities_request(
+            self,
+        ) -> Option<(Vec<Box<dyn Entity<Self::EntityType, Self>>>, SceneName)> {
+            None
+        }
+        fn is_delete_entity<'a>(&'a self) -> Option<(&'a EntityName, &'a SceneName)> {
+            None
+        }
+        fn is_update_uniform_buffer<'a>(&'a self) -> Option<(&'a UniformBufferName, &'a [u8])> {
+            match self {
+                Self::Update(name, data) => Some((name, data)),
+                Self::Other => None,
+            }
+        }
+        fn update_uniform_buffer(name: UniformBufferName, data: Vec<u8>) -> Self {
+            Self::Update(name, data)
+        }
+    }
+
+    #[test]
+    fn uniform_updates_wait_for_the_device() {
+        let update = UniformEvent::update_uniform_buffer("tint".into(), vec![0; 16]);
+        assert!(GameEvent::External(update).needs_device());
+        assert!(!GameEvent::External(UniformEvent::Other).needs_device());
+        //Handled by the ManagerApplication itself
+        assert!(!GameEvent::<UniformEvent>::RenderBatch(vec![]).needs_device());
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use winit::{
+    event::{ElementState, KeyEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+};
+
+pub mod exports {
+    pub use super::InputState;
+}
+
+///Keys and modifiers currently held, so entities can poll them in `update` instead of tracking
+///presses and releases themselves
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    pressed: HashSet<KeyCode>,
+    modifiers: ModifiersState,
+}
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_pressed(&self, key: KeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    pub fn pressed(&self) -> impl Iterator<Item = &KeyCode> {
+        self.pressed.iter()
+    }
+
+    pub fn modifiers(&self) -> &ModifiersState {
+        &self.modifiers
+    }
+
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    pub fn handle_key_event(&mut self, event: &KeyEvent) {
+        if let PhysicalKey::Code(key) = event.physical_key {
+            self.set_key(key, event.state == ElementState::Pressed);
+        }
+    }
+
+    pub fn set_key(&mut self, key: KeyCode, pressed: bool) {
+        if pressed {
+            self.pressed.insert(key);
+        } else {
+            self.pressed.remove(&key);
+        }
+    }
+
+    ///Release everything, eg. when the window loses focus and the releases are not received
+    pub fn clear(&mut self) {
+        self.pressed.clear();
+        self.modifiers = ModifiersState::empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn press_and_release() {
+        let mut input = InputState::new();
+        input.set_key(KeyCode::KeyW, true);
+        input.set_key(KeyCode::KeyA, true);
+        input.set_key(KeyCode::KeyW, false);
+        assert!(!input.is_pressed(KeyCode::KeyW));
+        assert!(input.is_pressed(KeyCode::KeyA));
+        input.clear();
+        assert_eq!(input.pressed().count(), 0);
+    }
+}
@@ -1,40 +1,56 @@
 use std::{
-    thread,
+    collections::HashMap,
+    iter, thread,
     time::{Duration, Instant},
 };
 
 use crate::{
-    app::{EventManager, IndexBuffer, MouseEvent, VertexBuffer, WindowManager},
-    graphics::{GraphicsProvider, RenderSceneName, ShaderDescriptor, UniformBufferName},
+    app::{
+        typed_text, ActiveLoop, EventManager, IndexBuffer, MouseEvent, TextInputEvent, TouchEvent,
+        VertexBuffer, WindowManager,
+    },
+    graphics::{
+        BlendMode, GraphicsProvider, RenderSceneDescriptor, RenderSceneName, RenderStats,
+        SceneLoadOp, ShaderDescriptor, TimeUniform, Uniform, UniformBufferName, Vertex, Visibility,
+    },
     Position, Size,
 };
 
-use log::{info, warn};
+use log::{debug, error, info, warn};
 use winit::{
-    event::{DeviceId, WindowEvent},
+    dpi::PhysicalPosition,
+    event::{DeviceId, ElementState, Ime, Touch, TouchPhase, WindowEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::WindowId,
 };
 
 use self::{
+    action_map::{ActionEvent, ActionMap, Binding},
+    debug_draw::build_debug_overlay,
     entity::Entity,
-    game_event::{ExternalEvent, GameEvent},
+    example::SimpleVertex,
+    game_event::{CursorRequest, ExternalEvent, GameEvent},
     ressource_descriptor::{
         RessourceDescriptor, SpriteSheetName, WindowName,
     },
-    scene::{Scene, SceneName},
-    sprite_sheet::SpriteSheet,
+    scene::{Scene, SceneName, SceneStatus, WindowTarget},
+    input_state::InputState,
+    sprite_sheet::{SpriteSheet, SpriteSheetScope},
+    time_source::{FramePoll, RealClock, TimeSource},
+    tween::UniformTween,
 };
 
 pub mod example {
     pub use super::color::Color;
     pub use super::game_event::example::*;
     pub use game_state::SimpleGameState;
-    pub use vertex::SimpleVertex;
+    pub use sprite::{SpriteEntity, TEXTURED_SHADER, TEXTURED_SINGLE_SHADER};
+    pub use vertex::{FloatColorVertex, SimpleVertex, TexturedVertex, Vertex3};
 
     mod vertex {
         use crate::{
             graphics::Vertex,
-            game_engine::Color,
+            game_engine::{Color, TextureCoordinates},
         };
         use repr_trait::C;
         use twod::Vector;
@@ -49,7 +65,7 @@ pub mod example {
             pub fn new(position: Vector<f32>, color: Color) -> Self {
                 Self {
                     position: [position.x, position.y],
-                    color: bytemuck::cast_slice(&color.to_slice())[0],
+                    color: color.to_packed_u32(),
                 }
             }
         }
@@ -60,6 +76,204 @@ pub mod example {
                 &UI_VERTEX_ATTRIBUTES
             }
         }
+
+        ///Like SimpleVertex, but with a `vec4<f32>` color, eg. for HDR targets or colors above 1
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, repr_trait::C)]
+        pub struct FloatColorVertex {
+            position: [f32; 2],
+            color: [f32; 4],
+        }
+        impl FloatColorVertex {
+            pub fn new(position: Vector<f32>, color: [f32; 4]) -> Self {
+                Self {
+                    position: [position.x, position.y],
+                    color,
+                }
+            }
+
+            pub fn from_color(position: Vector<f32>, color: &Color) -> Self {
+                Self::new(position, color.to_rgba_f32())
+            }
+        }
+        const FLOAT_COLOR_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+        impl Vertex for FloatColorVertex {
+            fn attributes() -> &'static [wgpu::VertexAttribute] {
+                &FLOAT_COLOR_VERTEX_ATTRIBUTES
+            }
+        }
+
+        ///Vertex carrying the entity z, for render scenes with use_depth. The shader has to pass
+        ///the z through to the clip position, mapped to [0, 1]
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, repr_trait::C)]
+        pub struct Vertex3 {
+            position: [f32; 3],
+            color: u32,
+        }
+        impl Vertex3 {
+            pub fn new(position: Vector<f32>, z: f32, color: Color) -> Self {
+                Self {
+                    position: [position.x, position.y, z],
+                    color: color.to_packed_u32(),
+                }
+            }
+        }
+        const DEPTH_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Uint32];
+        impl Vertex for Vertex3 {
+            fn attributes() -> &'static [wgpu::VertexAttribute] {
+                &DEPTH_VERTEX_ATTRIBUTES
+            }
+        }
+
+        ///Vertex of a sprite, for render scenes with use_textures. Fits `Tilemap::new` as well
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, repr_trait::C)]
+        pub struct TexturedVertex {
+            position: [f32; 2],
+            uv: [f32; 2],
+            texture: u32,
+        }
+        impl TexturedVertex {
+            pub fn new(position: Vector<f32>, uv: &TextureCoordinates, texture: u32) -> Self {
+                Self {
+                    position: [position.x, position.y],
+                    uv: [uv.u, uv.v],
+                    texture,
+                }
+            }
+        }
+        const TEXTURED_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+            wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Uint32];
+        impl Vertex for TexturedVertex {
+            fn attributes() -> &'static [wgpu::VertexAttribute] {
+                &TEXTURED_VERTEX_ATTRIBUTES
+            }
+        }
+    }
+
+    mod sprite {
+        use twod::Vector;
+
+        use crate::{
+            app::{fits_u16_indices, IndexBuffer, VertexBuffer},
+            game_engine::{
+                BoundingBox, Entity, EntityName, EntityType, ExternalEvent, SpritePosition,
+                SpriteSheet, SpriteSheetName,
+            },
+            graphics::ShaderDescriptor,
+            Size,
+        };
+        use log::error;
+
+        use super::TexturedVertex;
+
+        ///Shader of TexturedVertex, indexing all textures at once. Render scenes using it need
+        ///use_textures and an adapter supporting texture arrays
+        pub const TEXTURED_SHADER: ShaderDescriptor = ShaderDescriptor {
+            file: concat!(env!("CARGO_MANIFEST_DIR"), "/src/game/textured.wgsl"),
+            vertex_shader: "vs_main",
+            fragment_shader: "fs_main",
+            uniforms: &[],
+        };
+
+        ///TEXTURED_SHADER for adapters without texture arrays, see
+        ///`GraphicsProvider::supports_texture_arrays`. It draws the one texture bound to the
+        ///render scene, whatever the texture index of the vertices
+        pub const TEXTURED_SINGLE_SHADER: ShaderDescriptor = ShaderDescriptor {
+            file: concat!(env!("CARGO_MANIFEST_DIR"), "/src/game/textured_single.wgsl"),
+            vertex_shader: "vs_main",
+            fragment_shader: "fs_main",
+            uniforms: &[],
+        };
+
+        ///Draws one sprite of a sprite sheet in clip space. The sheet is requested when the scene
+        ///becomes active, and the placeholder is drawn until it arrives
+        #[derive(Debug)]
+        pub struct SpriteEntity {
+            name: EntityName,
+            sprite_sheet: SpriteSheetName,
+            sprite: SpritePosition,
+            anchor: Vector<f32>,
+            size: Size<f32>,
+        }
+        impl SpriteEntity {
+            pub fn new(
+                name: EntityName,
+                sprite_sheet: SpriteSheetName,
+                sprite: SpritePosition,
+                anchor: Vector<f32>,
+                size: Size<f32>,
+            ) -> Self {
+                Self {
+                    name,
+                    sprite_sheet,
+                    sprite,
+                    anchor,
+                    size,
+                }
+            }
+
+            ///Corners in the order of `SpriteSheet::get_sprite_coordinates`
+            pub fn vertices(&self, sprite_sheet: &SpriteSheet) -> [TexturedVertex; 4] {
+                let (half_width, half_height) = (self.size.width() / 2.0, self.size.height() / 2.0);
+                let corners = [
+                    Vector::new(self.anchor.x - half_width, self.anchor.y + half_height),
+                    Vector::new(self.anchor.x + half_width, self.anchor.y + half_height),
+                    Vector::new(self.anchor.x + half_width, self.anchor.y - half_height),
+                    Vector::new(self.anchor.x - half_width, self.anchor.y - half_height),
+                ];
+                let uvs = sprite_sheet.get_sprite_coordinates(&self.sprite);
+                [0, 1, 2, 3].map(|i| {
+                    TexturedVertex::new(corners[i].clone(), &uvs[i], sprite_sheet.texture())
+                })
+            }
+        }
+        impl<T: EntityType, E: ExternalEvent> Entity<T, E> for SpriteEntity {
+            fn render(
+                &mut self,
+                vertices: &mut VertexBuffer,
+                indices: &mut IndexBuffer,
+                sprite_sheet: Vec<Option<&SpriteSheet>>,
+            ) {
+                let sprite_sheet =
+                    SpriteSheet::or_placeholder(sprite_sheet.first().copied().flatten());
+                if !fits_u16_indices(vertices, 4) {
+                    error!(
+                        "Sprite {:?} does not fit into the u16 indices of its render scene",
+                        self.name
+                    );
+                    return;
+                }
+                let start = vertices.len() as u16;
+                vertices.extend_from_slice(&self.vertices(sprite_sheet));
+                indices.extend_from_slice(&[
+                    start,
+                    start + 1,
+                    start + 2,
+                    start,
+                    start + 2,
+                    start + 3,
+                ]);
+            }
+            fn sprite_sheets(&self) -> Vec<&SpriteSheetName> {
+                vec![&self.sprite_sheet]
+            }
+            fn name(&self) -> &EntityName {
+                &self.name
+            }
+            fn bounding_box(&self) -> BoundingBox {
+                BoundingBox {
+                    anchor: self.anchor.clone(),
+                    size: self.size.clone(),
+                }
+            }
+            fn entity_type(&self) -> T {
+                T::default()
+            }
+        }
     }
 
     mod game_state {
@@ -92,43 +306,170 @@ pub mod example {
     }
 }
 
+mod action_map;
+#[cfg(feature = "audio")]
+mod audio;
 mod bounding_box;
 mod camera;
 mod color;
+mod debug_draw;
 mod entity;
 mod game_event;
+mod input_state;
+mod nine_slice;
+mod particle_emitter;
 mod ressource_descriptor;
 mod scene;
+mod spatial_grid;
 mod sprite_sheet;
+mod tilemap;
+mod time_source;
+mod tint;
+mod tween;
 mod velocity_controller;
+mod world_view;
 
 pub mod exports {
+    pub use super::action_map::exports::*;
+    #[cfg(feature = "audio")]
+    pub use super::audio::exports::*;
     pub use super::bounding_box::exports::*;
     pub use super::camera::exports::*;
     pub use super::color::exports::*;
+    pub use super::debug_draw::exports::*;
     pub use super::entity::exports::*;
     pub use super::game_event::exports::*;
+    pub use super::input_state::exports::*;
+    pub use super::nine_slice::exports::*;
+    pub use super::particle_emitter::exports::*;
     pub use super::ressource_descriptor::exports::*;
     pub use super::scene::exports::*;
+    pub use super::spatial_grid::exports::*;
     pub use super::sprite_sheet::exports::*;
+    pub use super::tilemap::exports::*;
+    pub use super::time_source::exports::*;
+    pub use super::tint::exports::*;
+    pub use super::tween::exports::*;
     pub use super::velocity_controller::exports::*;
+    pub use super::world_view::exports::*;
+}
+
+///Longest frame delta handed to the entities, if not configured otherwise
+pub const DEFAULT_MAX_DELTA_T: Duration = Duration::from_millis(100);
+
+///Listing this in `ShaderDescriptor::uniforms` binds a TimeUniform with the seconds elapsed in the
+///game, updated every frame. It does not have to be specified in the RessourceDescriptor
+pub const TIME_UNIFORM: &str = "Time";
+
+///Render scene of the debug overlay of a window
+fn debug_overlay_name(window: &WindowId) -> RenderSceneName {
+    format!("{:?} Debug Overlay", window).into()
+}
+
+///Texture label of a sprite sheet scoped to a scene, so scenes can use the same name for
+///different images
+fn scene_sprite_sheet_label(scene: &SceneName, name: &SpriteSheetName) -> SpriteSheetName {
+    format!("{} of Scene {}", name.as_str(), scene.as_str()).into()
+}
+
+///Name of the TIME_UNIFORM of a render scene
+pub fn time_uniform_name(render_scene: &RenderSceneName) -> UniformBufferName {
+    format!("{} {}", render_scene.as_str(), TIME_UNIFORM).into()
 }
 
 pub trait State<E: ExternalEvent> {
     fn handle_event(&mut self, event: E) -> Vec<E>;
+    ///Like handle_event, but with access to the running event loop, eg. to query monitors or
+    ///create cursors. The ActiveLoop is only valid during this call
+    fn handle_event_with_loop(&mut self, event: E, _event_loop: &ActiveLoop) -> Vec<E> {
+        self.handle_event(event)
+    }
+    ///The scenes become active in the order of the Vec, even if their windows are created in a
+    ///different order. Scenes requested later become active after these
     fn start_scenes(self) -> (Vec<Scene<E>>, Self);
+    ///Called every Timer tick before the entities are updated, for global game logic like wave
+    ///spawners or score timers. `delta_t` is clamped to the max_delta_t
+    fn update(&mut self, _delta_t: &Duration) -> Vec<E> {
+        vec![]
+    }
+    ///Called when a window is resized. `size` is the new logical size. `name` is the name of the
+    ///window in the RessourceDescriptor, None for windows created without one
+    fn handle_window_resized(
+        &mut self,
+        _window: &WindowId,
+        _name: Option<&WindowName>,
+        _size: &Size<f32>,
+    ) -> Vec<E> {
+        vec![]
+    }
 }
 
 pub struct Game<E: ExternalEvent, S: State<E>> {
     ressources: RessourceDescriptor,
     active_scenes: Vec<Scene<E>>,
     pending_scenes: Vec<Scene<E>>,
+    ///Render scenes of pending scenes, that wait for the scenes started before them
+    created_render_scenes: Vec<RenderSceneName>,
     suspended_scenes: Vec<Scene<E>>,
     window_ids: Vec<(WindowName, WindowId)>,
     window_sizes: Vec<(WindowId, Size<u32>)>,
-    sprite_sheets: Vec<(SpriteSheetName, SpriteSheet)>,
+    scale_factors: Vec<(WindowId, f64)>,
+    ///Copied from the GraphicsProvider every frame
+    render_stats: Vec<(WindowId, RenderStats)>,
+    ///Global sprite sheets by name, scoped ones by their texture label
+    sprite_sheets: HashMap<SpriteSheetName, SpriteSheet>,
+    ///The names scoped to an activated scene with the labels of their textures
+    scene_sprite_sheets: Vec<(SceneName, Vec<(SpriteSheetName, SpriteSheetName)>)>,
+    ///Scoped sprite sheets freed while they were loading, their replies are dropped
+    dropped_sprite_sheets: Vec<SpriteSheetName>,
+    ///Active scenes with the sprite sheets they still wait for, before they are ready
+    loading_scenes: Vec<(SceneName, Vec<SpriteSheetName>)>,
     cursors: Vec<(DeviceId, WindowId, Position<i32>)>,
-    target_fps: u8,
+    ///Fingers currently touching a window
+    touches: Vec<(DeviceId, u64, WindowId, Position<i32>)>,
+    ///Taken by the timer thread or the event loop once the game is resumed
+    time_source: Option<Box<dyn TimeSource>>,
+    use_event_loop_timer: bool,
+    ///Scenes held back until the preloaded sprite sheets are loaded, with_preloading
+    preload_scenes: Option<Vec<Scene<E>>>,
+    preloading: Vec<SpriteSheetName>,
+    ///The preloaded sprite sheets were requested from an existing device
+    preload_started: bool,
+    ///Shown while preloading, deleted once the held back scenes are active
+    loading_scene: Option<SceneName>,
+    ///Polled in about_to_wait once the game is resumed, with_event_loop_timer
+    event_loop_time_source: Option<Box<dyn TimeSource>>,
+    spatial_grid_cell_size: Option<f32>,
+    max_delta_t: Duration,
+    ///Sum of the clamped frame deltas, for the TIME_UNIFORM
+    elapsed: Duration,
+    ime_preedit: bool,
+    ///Their text arrives as Ime::Commit, not with the key presses
+    ime_windows: Vec<WindowId>,
+    input: InputState,
+    action_map: Option<ActionMap>,
+    key_repeat: bool,
+    ///Scenes whose entities were added or removed, or that were just activated, so they have to
+    ///be rendered even if no entity is render dirty
+    dirty_scenes: Vec<SceneName>,
+    ///Reused for the events of the entities in response to input
+    input_events: Vec<E>,
+    ///Running tweens with the time elapsed since they started
+    tweens: Vec<(UniformTween, Duration)>,
+    ///Scenes last requested to be hidden
+    hidden_scenes: Vec<SceneName>,
+    ///Scenes suspended when the device was lost, they are suspended again once recreated
+    recreated_suspended_scenes: Vec<SceneName>,
+    ///Shader of the overlay render scene requested for every window
+    debug_overlay: Option<ShaderDescriptor>,
+    debug_overlay_key: Option<KeyCode>,
+    show_debug_overlay: bool,
+    ///Windows the overlay render scene was requested for, named or not
+    debug_overlay_windows: Vec<WindowId>,
+    ///Opened when the first sound is played. Without an audio output it is not tried again and
+    ///the sounds are skipped
+    #[cfg(feature = "audio")]
+    audio: Option<Option<audio::AudioPlayer>>,
     state: S,
 }
 impl<E: ExternalEvent, S: State<E>> Game<E, S> {
@@ -137,17 +478,228 @@ impl<E: ExternalEvent, S: State<E>> Game<E, S> {
         Self {
             ressources,
             pending_scenes: initial_scenes,
+            created_render_scenes: Vec::new(),
             active_scenes: Vec::new(),
             suspended_scenes: Vec::new(),
             window_ids: Vec::new(),
             window_sizes: Vec::new(),
-            sprite_sheets: Vec::new(),
+            scale_factors: Vec::new(),
+            render_stats: Vec::new(),
+            sprite_sheets: HashMap::new(),
+            scene_sprite_sheets: Vec::new(),
+            dropped_sprite_sheets: Vec::new(),
+            loading_scenes: Vec::new(),
             cursors: Vec::new(),
-            target_fps,
+            touches: Vec::new(),
+            time_source: Some(Box::new(RealClock::new(target_fps))),
+            use_event_loop_timer: false,
+            event_loop_time_source: None,
+            preload_scenes: None,
+            preloading: Vec::new(),
+            preload_started: false,
+            loading_scene: None,
+            spatial_grid_cell_size: None,
+            max_delta_t: DEFAULT_MAX_DELTA_T,
+            elapsed: Duration::ZERO,
+            ime_preedit: false,
+            ime_windows: Vec::new(),
+            input: InputState::new(),
+            action_map: None,
+            key_repeat: true,
+            input_events: Vec::new(),
+            tweens: Vec::new(),
+            hidden_scenes: Vec::new(),
+            recreated_suspended_scenes: Vec::new(),
+            debug_overlay: None,
+            debug_overlay_key: None,
+            show_debug_overlay: false,
+            debug_overlay_windows: Vec::new(),
+            dirty_scenes: Vec::new(),
+            #[cfg(feature = "audio")]
+            audio: None,
             state,
         }
     }
 
+    ///Index the entities of each scene in a SpatialGrid every frame, so `WorldView::nearby` only
+    ///has to check entities in the overlapped cells. Panics if the cell size is not positive and
+    ///finite
+    pub fn with_spatial_grid(mut self, cell_size: f32) -> Self {
+        assert!(
+            cell_size.is_finite() && cell_size > 0.0,
+            "The cell size of the spatial grid has to be positive and finite, not {}",
+            cell_size
+        );
+        self.spatial_grid_cell_size = Some(cell_size);
+        self
+    }
+
+    ///Replace the wall clock driving the entity updates, eg. with a SteppedClock for replays
+    pub fn with_time_source(mut self, time_source: impl TimeSource + 'static) -> Self {
+        self.time_source = Some(Box::new(time_source));
+        self
+    }
+
+    ///Send the Timer events from the event loop instead of a thread, eg. for single threaded
+    ///targets. Run the application with `ControlFlow::Wait`, so the loop sleeps until the next
+    ///frame. The time source is polled with `TimeSource::poll_frame` then
+    pub fn with_event_loop_timer(mut self) -> Self {
+        self.use_event_loop_timer = true;
+        self
+    }
+
+    ///Hold back the scenes of `State::start_scenes` until the sprite sheets of
+    ///`RessourceDescriptor::preload_sprite_sheets` are loaded, so they start without missing
+    ///textures. The loading scene is shown meanwhile and deleted once they are active, its own
+    ///sprite sheets are loaded on demand
+    pub fn with_preloading(mut self, loading_scene: Option<Scene<E>>) -> Self {
+        let scenes = std::mem::take(&mut self.pending_scenes);
+        self.preload_scenes = Some(scenes);
+        if let Some(scene) = loading_scene {
+            self.loading_scene = Some(scene.name.clone());
+            self.pending_scenes.push(scene);
+        }
+        self
+    }
+
+    ///Translate bound keys and mouse buttons into ActionEvents for `Entity::handle_action`, in
+    ///addition to the raw input. Key repeats do not trigger actions
+    pub fn with_action_map(mut self, action_map: ActionMap) -> Self {
+        self.action_map = Some(action_map);
+        self
+    }
+
+    ///None without with_action_map
+    pub fn action_map(&self) -> Option<&ActionMap> {
+        self.action_map.as_ref()
+    }
+
+    ///Rebind controls, eg. from tools. While the game runs the state rebinds them with
+    ///`ExternalEvent::is_request_rebind_action`
+    pub fn action_map_mut(&mut self) -> Option<&mut ActionMap> {
+        self.action_map.as_mut()
+    }
+
+    ///Also deliver the text being composed by an input method, not only the committed text
+    pub fn with_ime_preedit(mut self) -> Self {
+        self.ime_preedit = true;
+        self
+    }
+
+    ///With false, the repeated KeyboardInput of a held key is not delivered to the entities
+    pub fn with_key_repeat(mut self, key_repeat: bool) -> Self {
+        self.key_repeat = key_repeat;
+        self
+    }
+
+    ///Draw the bounding boxes of the entities of the active scenes and the view rects of their
+    ///cameras above every window, while toggled on by the key or by
+    ///`ExternalEvent::is_request_toggle_debug_overlay`. The shader gets SimpleVertex positions in
+    ///clip space and no uniforms
+    pub fn with_debug_overlay(
+        mut self,
+        shader_descriptor: ShaderDescriptor,
+        toggle_key: Option<KeyCode>,
+    ) -> Self {
+        self.debug_overlay = Some(shader_descriptor);
+        self.debug_overlay_key = toggle_key;
+        self
+    }
+
+    ///Modifier keys currently held
+    pub fn modifiers(&self) -> &ModifiersState {
+        self.input.modifiers()
+    }
+
+    ///The winit id of a window, once it is created. The state learns it from
+    ///`ExternalEvent::window_created`
+    pub fn window_id_for(&self, window: &WindowName) -> Option<&WindowId> {
+        self.window_ids
+            .iter()
+            .find(|(name, _)| name == window)
+            .map(|(_, id)| id)
+    }
+
+    ///Physical pixels per logical pixel of the window, eg. 2.0 on most HiDPI displays.
+    ///The state learns it from `ExternalEvent::scale_factor_changed`
+    pub fn scale_factor(&self, window: &WindowName) -> Option<f64> {
+        let id = self.window_id_for(window)?;
+        self.scale_factors
+            .iter()
+            .find(|(i, _)| i == id)
+            .map(|(_, scale_factor)| *scale_factor)
+    }
+
+    ///Counts of the last frame rendered to the window. The state can ask for them with
+    ///`ExternalEvent::is_request_render_stats`
+    pub fn render_stats(&self, window: &WindowName) -> Option<&RenderStats> {
+        let id = self.window_id_for(window)?;
+        self.render_stats
+            .iter()
+            .find(|(i, _)| i == id)
+            .map(|(_, stats)| stats)
+    }
+
+    ///None if no scene with the name is known, eg. after it was deleted. The state asks for it
+    ///with `ExternalEvent::is_request_scene_status`
+    pub fn scene_status(&self, scene: &SceneName) -> Option<SceneStatus> {
+        let has_scene = |scenes: &[Scene<E>]| scenes.iter().any(|s| s.name == *scene);
+        if has_scene(&self.active_scenes) {
+            Some(SceneStatus::Active)
+        } else if has_scene(&self.suspended_scenes) {
+            Some(SceneStatus::Suspended)
+        } else if has_scene(&self.pending_scenes) {
+            Some(SceneStatus::Pending)
+        } else {
+            None
+        }
+    }
+
+    ///The visibility last requested for the scene, None if no scene with the name is known.
+    ///The state can ask for it with `ExternalEvent::is_request_scene_visibility`
+    pub fn scene_visibility(&self, scene: &SceneName) -> Option<Visibility> {
+        self.scene_status(scene)?;
+        if self.hidden_scenes.contains(scene) {
+            Some(Visibility::Hidden)
+        } else {
+            Some(Visibility::Visible)
+        }
+    }
+
+    ///Keys and modifiers currently held
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
+
+    ///The state returned by `State::start_scenes`, eg. to inspect the score in tests or tools.
+    ///Once the Game is handed to a ManagerApplication it is reached through
+    ///`ManagerApplication::event_manager`
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    ///Changes take effect with the next event handled by the state
+    pub fn state_mut(&mut self) -> &mut S {
+        &mut self.state
+    }
+
+    ///Frame deltas are clamped to this, so entities do not jump after a stall of the main thread
+    pub fn with_max_delta_t(mut self, max_delta_t: Duration) -> Self {
+        self.max_delta_t = max_delta_t;
+        self
+    }
+
+    ///The active scenes and the suspended scenes with `Scene::receive_while_suspended`. Only
+    ///these receive input, entity events and added or deleted entities
+    fn receiving_scenes<'a>(
+        active_scenes: &'a mut [Scene<E>],
+        suspended_scenes: &'a mut [Scene<E>],
+    ) -> impl Iterator<Item = &'a mut Scene<E>> {
+        active_scenes
+            .iter_mut()
+            .chain(suspended_scenes.iter_mut().filter(|s| s.receive_while_suspended))
+    }
+
     fn activate_scenes(&mut self, window_manager: &mut WindowManager<GameEvent<E>>) {
         let mut needed_windows = Vec::new();
         let mut scenes_to_discard = Vec::new();
@@ -163,23 +715,49 @@ impl<E: ExternalEvent, S: State<E>> Game<E, S> {
                 scenes_to_discard.push(scene.name.clone());
                 continue;
             }
-            if let Some((_, id)) = self
-                .window_ids
-                .iter()
-                .find(|(existing_window, _)| scene.target_window == *existing_window)
-            {
+            //Only waits for the scenes before it
+            if self.created_render_scenes.contains(&scene.render_scene) {
+                continue;
+            }
+            let id = match &scene.target_window {
+                WindowTarget::Name(name) => self
+                    .window_ids
+                    .iter()
+                    .find(|(existing_window, _)| name == existing_window)
+                    .map(|(_, id)| id),
+                WindowTarget::Id(id) => {
+                    if window_manager.get_window(id).is_none() {
+                        warn!(
+                            "Window {:?} of Scene {:?} does not exist. Discarding it",
+                            id, scene.name
+                        );
+                        scenes_to_discard.push(scene.name.clone());
+                        continue;
+                    }
+                    Some(id)
+                }
+            };
+            if let Some(id) = id {
                 scenes_to_request.push((
                     id.clone(),
                     scene.render_scene.clone(),
                     scene.shader_descriptor.clone(),
                 ));
-            } else {
-                if !needed_windows.contains(&scene.target_window) {
-                    needed_windows.push(scene.target_window.clone());
+                for (render_scene, shader_descriptor) in scene.extra_render_scenes.iter() {
+                    scenes_to_request.push((
+                        id.clone(),
+                        render_scene.clone(),
+                        shader_descriptor.clone(),
+                    ));
+                }
+            } else if let WindowTarget::Name(name) = &scene.target_window {
+                if !needed_windows.contains(name) {
+                    needed_windows.push(name.clone());
                 }
             }
         }
         for (window_id, render_scene, shader_descriptor) in scenes_to_request {
+            self.request_debug_overlay(&window_id, window_manager);
             self.request_render_scene(
                 &window_id,
                 window_manager,
@@ -188,19 +766,354 @@ impl<E: ExternalEvent, S: State<E>> Game<E, S> {
             );
         }
         for window_name in needed_windows.iter() {
-            let window_descriptor = &self
-                .ressources
-                .get_window(&window_name)
-                .expect(&format!("No ressources provided for {:?}", window_name));
-            window_manager.send_event(GameEvent::RequestNewWindow(
-                window_descriptor.clone(),
-                window_name.clone(),
-            ));
+            self.request_window(window_name, window_manager);
         }
         self.pending_scenes
             .retain_mut(|s| !scenes_to_discard.contains(&s.name));
     }
 
+    fn request_window(
+        &self,
+        window_name: &WindowName,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+    ) {
+        let window_descriptor = self
+            .ressources
+            .get_window(window_name)
+            .expect(&format!("No ressources provided for {:?}", window_name));
+        window_manager.send_event(GameEvent::RequestNewWindow(
+            window_descriptor,
+            window_name.clone(),
+        ));
+    }
+
+    ///Activates the pending scenes in order, as long as their render scenes are created
+    fn activate_created_scenes(
+        &mut self,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+        graphics_provider: &mut GraphicsProvider,
+    ) {
+        while let Some(scene) = self.pending_scenes.first() {
+            if self.active_scenes.iter().any(|s| s.name == scene.name) {
+                warn!("Scene {:?} already exists. Discarding it", scene.name);
+                self.pending_scenes.remove(0);
+                continue;
+            }
+            let created = match self
+                .created_render_scenes
+                .iter()
+                .position(|r| *r == scene.render_scene)
+            {
+                Some(created) => created,
+                None => break,
+            };
+            self.created_render_scenes.swap_remove(created);
+            let scene = self.pending_scenes.remove(0);
+            let scoped = self
+                .ressources
+                .scene_sprite_sheet_names(&scene.name)
+                .into_iter()
+                .map(|name| (name.clone(), scene_sprite_sheet_label(&scene.name, name)))
+                .collect::<Vec<_>>();
+            if !scoped.is_empty() {
+                self.scene_sprite_sheets.retain(|(s, _)| *s != scene.name);
+                self.scene_sprite_sheets.push((scene.name.clone(), scoped));
+            }
+            let mut loading = Vec::new();
+            for sprite_sheet in scene.entities.iter().flat_map(|e| e.sprite_sheets()) {
+                let label = self.request_sprite_sheet(&scene.name, sprite_sheet, window_manager);
+                if !self.sprite_sheets.contains_key(&label) && !loading.contains(&label) {
+                    loading.push(label);
+                }
+            }
+            graphics_provider.set_z_index_render_scene(&scene.render_scene, scene.z_index);
+            let sprite_sheets = Self::sprite_sheet_scope(
+                &self.sprite_sheets,
+                &self.scene_sprite_sheets,
+                &scene.name,
+            );
+            Self::bind_scene_texture(&scene, sprite_sheets, graphics_provider);
+            //Recreated after the device was lost
+            if self.hidden_scenes.contains(&scene.name) {
+                graphics_provider
+                    .set_visibility_render_scene(&scene.render_scene, &Visibility::Hidden);
+            }
+            window_manager.send_event(GameEvent::External(E::new_scene(&scene)));
+            if loading.is_empty() {
+                if let Some(event) = E::scene_ready(&scene.name) {
+                    window_manager.send_event(GameEvent::External(event));
+                }
+            } else {
+                self.loading_scenes.push((scene.name.clone(), loading));
+            }
+            Self::mark_scene_dirty(&mut self.dirty_scenes, &scene.name);
+            if let Some(index) = self
+                .recreated_suspended_scenes
+                .iter()
+                .position(|s| *s == scene.name)
+            {
+                self.recreated_suspended_scenes.swap_remove(index);
+                self.suspended_scenes.push(scene);
+                continue;
+            }
+            self.active_scenes.push(scene);
+            //Stable, so scenes with the same z_index keep the order they were started in
+            self.active_scenes.sort_by_key(|s| s.z_index);
+        }
+        if self.preload_scenes.is_none() && self.pending_scenes.is_empty() {
+            if let Some(loading_scene) = self.loading_scene.take() {
+                self.delete_scene(&loading_scene, window_manager, graphics_provider);
+            }
+        }
+    }
+
+    ///Without texture arrays a render scene draws a single texture, the one of the first sprite
+    ///sheet of the entities drawing into it. Entities with other sprite sheets are drawn with it
+    ///as well, so scenes have to keep to one sprite sheet per render scene on such adapters, and
+    ///use shaders like example::TEXTURED_SINGLE_SHADER
+    fn bind_scene_texture(
+        scene: &Scene<E>,
+        sprite_sheets: SpriteSheetScope<'_>,
+        graphics_provider: &mut GraphicsProvider,
+    ) {
+        if graphics_provider.supports_texture_arrays() {
+            return;
+        }
+        for render_scene in scene.render_scenes() {
+            let mut names = scene
+                .entities
+                .iter()
+                .filter(|e| e.render_scene().unwrap_or(&scene.render_scene) == render_scene)
+                .flat_map(|e| e.sprite_sheets())
+                .collect::<Vec<_>>();
+            //Entities draw into the extra render scenes with render_extra as well
+            if names.is_empty() {
+                names = scene.entities.iter().flat_map(|e| e.sprite_sheets()).collect();
+            }
+            let mut textures = names
+                .iter()
+                .filter_map(|name| sprite_sheets.get(name))
+                .map(|sprite_sheet| sprite_sheet.texture())
+                .collect::<Vec<_>>();
+            textures.sort();
+            textures.dedup();
+            if textures.len() > 1 {
+                error!(
+                    "RenderScene {:?} of Scene {:?} draws {} sprite sheets, but without texture arrays only the first is bound",
+                    render_scene,
+                    scene.name,
+                    textures.len()
+                );
+            }
+            if let Some(first) = names.first() {
+                let texture = SpriteSheet::or_placeholder(sprite_sheets.get(first)).texture();
+                graphics_provider.bind_texture_render_scene(render_scene, texture);
+            }
+        }
+    }
+
+    fn sprite_sheet_scope<'a>(
+        sprite_sheets: &'a HashMap<SpriteSheetName, SpriteSheet>,
+        scene_sprite_sheets: &'a [(SceneName, Vec<(SpriteSheetName, SpriteSheetName)>)],
+        scene: &SceneName,
+    ) -> SpriteSheetScope<'a> {
+        match scene_sprite_sheets.iter().find(|(s, _)| s == scene) {
+            Some((_, scoped)) => SpriteSheetScope::new(sprite_sheets, scoped),
+            None => SpriteSheetScope::global(sprite_sheets),
+        }
+    }
+
+    fn delete_scene(
+        &mut self,
+        deletable_scene: &SceneName,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+        graphics_provider: &mut GraphicsProvider,
+    ) {
+        info!("Deleting Scene {:?}", deletable_scene);
+        self.hidden_scenes.retain(|s| s != deletable_scene);
+        self.recreated_suspended_scenes.retain(|s| s != deletable_scene);
+        let loading = match self
+            .loading_scenes
+            .iter()
+            .position(|(s, _)| s == deletable_scene)
+        {
+            Some(index) => self.loading_scenes.remove(index).1,
+            None => Vec::new(),
+        };
+        if let Some(active_index) = self
+            .active_scenes
+            .iter()
+            .position(|s| s.name == *deletable_scene)
+        {
+            let scene = self.active_scenes.remove(active_index);
+            for render_scene in scene.render_scenes() {
+                graphics_provider.remove_render_scene(render_scene);
+            }
+        } else if let Some(suspended_index) = self
+            .suspended_scenes
+            .iter()
+            .position(|s| s.name == *deletable_scene)
+        {
+            let scene = self.suspended_scenes.remove(suspended_index);
+            for render_scene in scene.render_scenes() {
+                graphics_provider.remove_render_scene(render_scene);
+            }
+        } else {
+            warn!(
+                "Tried to delete Scene {:?}, but its neither active nor suspended",
+                deletable_scene
+            );
+        }
+        self.remove_scene_sprite_sheets(deletable_scene, &loading, window_manager);
+    }
+
+    ///Requests the sprite sheets of `RessourceDescriptor::preload_sprite_sheets`, with_preloading.
+    ///Textures can only be created once the first window has its device. Until then the window of
+    ///the held back scenes is requested, unless a pending scene requests one anyway
+    fn request_preload(
+        &mut self,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+        graphics_provider: &GraphicsProvider,
+    ) {
+        let held_scenes = match &self.preload_scenes {
+            Some(held_scenes) if !self.preload_started => held_scenes,
+            _ => return,
+        };
+        if !graphics_provider.has_device() && !graphics_provider.is_initializing() {
+            if self.pending_scenes.is_empty() && self.window_ids.is_empty() {
+                let window_name = held_scenes.iter().find_map(|scene| match &scene.target_window {
+                    WindowTarget::Name(name) => Some(name.clone()),
+                    WindowTarget::Id(_) => None,
+                });
+                if let Some(window_name) = window_name {
+                    self.request_window(&window_name, window_manager);
+                }
+            }
+            return;
+        }
+        self.preload_started = true;
+        self.preloading.clear();
+        for name in self.ressources.preload_sprite_sheets.iter() {
+            let path = self.ressources.get_sprite_sheet(name).0;
+            window_manager.send_event(GameEvent::RequestNewSpriteSheet(name.clone(), path));
+            if !self.preloading.contains(name) {
+                self.preloading.push(name.clone());
+            }
+        }
+        if self.release_preloaded_scenes(window_manager) {
+            self.activate_scenes(window_manager);
+        }
+    }
+
+    ///Moves the held back scenes to the pending ones once all preloaded sprite sheets are loaded.
+    ///They still have to be activated
+    fn release_preloaded_scenes(
+        &mut self,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+    ) -> bool {
+        if !self.preloading.is_empty() {
+            return false;
+        }
+        match self.preload_scenes.take() {
+            Some(mut scenes) => {
+                info!("Preloading finished");
+                self.pending_scenes.append(&mut scenes);
+                if let Some(event) = E::preload_finished() {
+                    window_manager.send_event(GameEvent::External(event));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///Frees the textures of the sprite sheets scoped to the scene. Those still `loading` are freed
+    ///as well, as the removal is handled after their requests, but their replies are dropped
+    fn remove_scene_sprite_sheets(
+        &mut self,
+        scene: &SceneName,
+        loading: &[SpriteSheetName],
+        window_manager: &mut WindowManager<GameEvent<E>>,
+    ) {
+        let index = match self.scene_sprite_sheets.iter().position(|(s, _)| s == scene) {
+            Some(index) => index,
+            None => return,
+        };
+        let (_, scoped) = self.scene_sprite_sheets.remove(index);
+        for (_, label) in scoped {
+            self.sprite_sheets.remove(&label);
+            if loading.contains(&label) {
+                self.dropped_sprite_sheets.push(label.clone());
+            }
+            window_manager.send_event(GameEvent::RequestRemoveSpriteSheet(label));
+        }
+    }
+
+    ///If the reply is for a sprite sheet freed while it was loading. Its texture is freed again,
+    ///unless the scene was activated again and requested it anew
+    fn drop_sprite_sheet_reply(
+        &mut self,
+        label: &SpriteSheetName,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+    ) -> bool {
+        let index = match self.dropped_sprite_sheets.iter().position(|l| l == label) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.dropped_sprite_sheets.swap_remove(index);
+        debug!("Dropping SpriteSheet {:?} of a deleted scene", label);
+        let requested = self
+            .scene_sprite_sheets
+            .iter()
+            .any(|(_, scoped)| scoped.iter().any(|(_, l)| l == label));
+        if !requested {
+            window_manager.send_event(GameEvent::RequestRemoveSpriteSheet(label.clone()));
+        }
+        true
+    }
+
+    ///Sends scene_ready for the scenes waiting only for this sprite sheet
+    fn sprite_sheet_loaded(
+        &mut self,
+        sprite_sheet: &SpriteSheetName,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+    ) {
+        self.preloading.retain(|s| s != sprite_sheet);
+        if self.release_preloaded_scenes(window_manager) {
+            self.activate_scenes(window_manager);
+        }
+        for (_, loading) in self.loading_scenes.iter_mut() {
+            loading.retain(|s| s != sprite_sheet);
+        }
+        let (ready, loading) = std::mem::take(&mut self.loading_scenes)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, loading)| loading.is_empty());
+        self.loading_scenes = loading;
+        for (scene, _) in ready {
+            if let Some(event) = E::scene_ready(&scene) {
+                window_manager.send_event(GameEvent::External(event));
+            }
+        }
+    }
+
+    ///Entities substitute the placeholder for a sprite sheet that could not be loaded, preloading
+    ///goes on without it. Preloaded ones are requested again with the next device, if there was
+    ///none to create their textures
+    fn sprite_sheet_failed(
+        &mut self,
+        sprite_sheet: &SpriteSheetName,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+        graphics_provider: &GraphicsProvider,
+    ) {
+        if self.preloading.contains(sprite_sheet) && !graphics_provider.has_device() {
+            warn!("Could not preload SpriteSheet '{:?}' without a device", sprite_sheet);
+            self.preload_started = false;
+            return;
+        }
+        error!("Could not load SpriteSheet '{:?}'", sprite_sheet);
+        self.sprite_sheet_loaded(sprite_sheet, window_manager);
+    }
+
     fn request_render_scene(
         &mut self,
         target_window: &WindowId,
@@ -214,6 +1127,10 @@ impl<E: ExternalEvent, S: State<E>> Game<E, S> {
                 .uniforms
                 .iter()
                 .map(|name| {
+                    if *name == TIME_UNIFORM {
+                        return TimeUniform::new(self.elapsed.as_secs_f32())
+                            .describe(time_uniform_name(&render_scene));
+                    }
                     self.ressources
                         .get_uniform(&(*name).into())
                         .expect(&format!(
@@ -231,16 +1148,140 @@ impl<E: ExternalEvent, S: State<E>> Game<E, S> {
         ));
     }
 
-    fn request_sprite_sheet(
-        &self,
-        name: &SpriteSheetName,
+    ///Once per window, the first scene of a window without a name requests it
+    fn request_debug_overlay(
+        &mut self,
+        target_window: &WindowId,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+    ) {
+        let shader_descriptor = match &self.debug_overlay {
+            Some(shader_descriptor) => shader_descriptor.clone(),
+            None => return,
+        };
+        if self.debug_overlay_windows.contains(target_window) {
+            return;
+        }
+        self.debug_overlay_windows.push(target_window.clone());
+        window_manager.send_event(GameEvent::RequestNewRenderScene(
+            target_window.clone(),
+            debug_overlay_name(target_window),
+            shader_descriptor,
+            RenderSceneDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffer_layout: SimpleVertex::describe_buffer_layout(),
+                use_textures: false,
+                target_aspect: None,
+                use_depth: false,
+                blend_mode: BlendMode::Alpha,
+                use_global_uniforms: false,
+                load_op: SceneLoadOp::Clear,
+            },
+            Vec::new(),
+        ));
+    }
+
+    ///Hiding the overlay clears its render scenes, as they are no longer updated
+    fn toggle_debug_overlay(&mut self, window_manager: &mut WindowManager<GameEvent<E>>) {
+        if self.debug_overlay.is_none() {
+            return;
+        }
+        self.show_debug_overlay = !self.show_debug_overlay;
+        if !self.show_debug_overlay {
+            for window in self.debug_overlay_windows.iter() {
+                window_manager.send_event(GameEvent::RenderUpdate(
+                    debug_overlay_name(window),
+                    VertexBuffer::new(),
+                    IndexBuffer::new(),
+                ));
+            }
+        }
+    }
+
+    ///Sprite sheets scoped to the scene are requested under their own label, which is returned
+    fn request_sprite_sheet(
+        &self,
+        scene: &SceneName,
+        name: &SpriteSheetName,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+    ) -> SpriteSheetName {
+        let (label, path) = match self.ressources.get_scene_sprite_sheet(scene, name) {
+            Some((path, _)) => (scene_sprite_sheet_label(scene, name), path),
+            None => (name.clone(), self.ressources.get_sprite_sheet(name).0),
+        };
+        window_manager.send_event(GameEvent::RequestNewSpriteSheet(label.clone(), path));
+        label
+    }
+
+    ///Window-centered pixel position, growing to the right and downwards
+    fn center_position(
+        &self,
+        id: &WindowId,
+        position: &PhysicalPosition<f64>,
+    ) -> Option<Position<i32>> {
+        self.window_sizes
+            .iter()
+            .find(|(i, _)| i == id)
+            .map(|(_, size)| {
+                Position::new(
+                    (position.x - size.width() as f64 / 2.0) as i32,
+                    (position.y - size.height() as f64 / 2.0) as i32,
+                )
+            })
+    }
+
+    fn mark_scene_dirty(dirty_scenes: &mut Vec<SceneName>, scene: &SceneName) {
+        if !dirty_scenes.contains(scene) {
+            dirty_scenes.push(scene.clone());
+        }
+    }
+
+    ///Delivers the actions of the binding to the receiving scenes of the window
+    fn handle_binding(
+        &mut self,
+        id: &WindowId,
+        binding: Binding,
+        state: ElementState,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+    ) {
+        let actions = match &self.action_map {
+            Some(action_map) => action_map.actions(&binding).cloned().collect::<Vec<_>>(),
+            None => return,
+        };
+        for action in actions {
+            let event = ActionEvent { action, state };
+            for scene in
+                Self::receiving_scenes(&mut self.active_scenes, &mut self.suspended_scenes)
+                    .filter(|scene| scene.target_window.matches(id, &self.window_ids))
+            {
+                scene.handle_action(&event, &mut self.input_events);
+            }
+        }
+        for event in self.input_events.drain(..) {
+            window_manager.send_event(GameEvent::External(event));
+        }
+    }
+
+    fn set_scale_factor(
+        &mut self,
         window_manager: &mut WindowManager<GameEvent<E>>,
+        id: &WindowId,
+        scale_factor: f64,
     ) {
-        let path = &self.ressources.get_sprite_sheet(&name).0;
-        window_manager.send_event(GameEvent::RequestNewSpriteSheet(name.clone(), path.clone()));
+        match self.scale_factors.iter_mut().find(|(i, _)| i == id) {
+            Some((_, s)) => *s = scale_factor,
+            None => self.scale_factors.push((id.clone(), scale_factor)),
+        }
+        let event = self
+            .window_name_for(id)
+            .and_then(|name| E::scale_factor_changed(name, scale_factor));
+        if let Some(event) = event {
+            window_manager.send_event(GameEvent::External(event));
+        }
     }
 
-    fn get_window_name(&self, id: &WindowId) -> Option<&WindowName> {
+    ///The name of a window created by the game, eg. to tell which window a raw winit event is for.
+    ///The state learns it from `ExternalEvent::window_created`
+    pub fn window_name_for(&self, id: &WindowId) -> Option<&WindowName> {
         self.window_ids
             .iter()
             .find(|(_, i)| i == id)
@@ -266,6 +1307,21 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
                 } else {
                     self.window_sizes.push((id.clone(), (*size).into()));
                 }
+                let scale_factor = self
+                    .scale_factors
+                    .iter()
+                    .find(|(i, _)| i == id)
+                    .map(|(_, scale_factor)| *scale_factor)
+                    .or_else(|| window_manager.get_window(id).map(|w| w.scale_factor()))
+                    .unwrap_or(1.0);
+                let logical_size = size.to_logical::<f32>(scale_factor);
+                window_manager.send_event(GameEvent::WindowResized(
+                    id.clone(),
+                    Size::new(logical_size.width, logical_size.height),
+                ));
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.set_scale_factor(window_manager, id, *scale_factor);
             }
             WindowEvent::CursorEntered { device_id } => {
                 self.cursors
@@ -278,82 +1334,199 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
                 device_id,
                 position,
             } => {
-                if let Some((_, _, cursor_position)) = self
-                    .cursors
-                    .iter_mut()
-                    .find(|(device, window, _)| device_id == device && window == id)
-                {
-                    if let Some((_, size)) = self.window_sizes.iter().find(|(i, _)| i == id) {
-                        let position = Position::new(
-                            (position.x - size.width() as f64 / 2.0) as i32,
-                            (position.y - size.height() as f64 / 2.0) as i32,
-                        );
+                if let Some(position) = self.center_position(id, position) {
+                    if let Some((_, _, cursor_position)) = self
+                        .cursors
+                        .iter_mut()
+                        .find(|(device, window, _)| device_id == device && window == id)
+                    {
                         *cursor_position = position;
                     }
                 }
             }
+            WindowEvent::Touch(Touch {
+                device_id,
+                phase,
+                location,
+                id: finger,
+                ..
+            }) => {
+                let position = match self.center_position(id, location) {
+                    Some(position) => position,
+                    None => return true,
+                };
+                let touch = self.touches.iter().position(|(device, f, window, _)| {
+                    device == device_id && f == finger && window == id
+                });
+                match (phase, touch) {
+                    (TouchPhase::Started, None) => {
+                        self.touches
+                            .push((*device_id, *finger, *id, position.clone()));
+                    }
+                    (TouchPhase::Ended | TouchPhase::Cancelled, Some(index)) => {
+                        self.touches.remove(index);
+                    }
+                    (_, Some(index)) => self.touches[index].3 = position.clone(),
+                    (_, None) => {}
+                }
+                let window_size = self
+                    .window_sizes
+                    .iter()
+                    .find(|(i, _)| i == id)
+                    .map(|(_, size)| size.clone());
+                for scene in
+                    Self::receiving_scenes(&mut self.active_scenes, &mut self.suspended_scenes)
+                        .filter(|scene| scene.target_window.matches(id, &self.window_ids))
+                {
+                    let world_position = window_size
+                        .as_ref()
+                        .and_then(|size| scene.world_position(&position, size));
+                    scene.handle_touch_input(
+                        &TouchEvent {
+                            id: *finger,
+                            position: position.clone(),
+                            phase: *phase,
+                            world_position,
+                        },
+                        &mut self.input_events,
+                    );
+                }
+                for event in self.input_events.drain(..) {
+                    window_manager.send_event(GameEvent::External(event));
+                }
+            }
             WindowEvent::MouseInput {
                 state,
                 button,
                 device_id,
-            } => match self.get_window_name(id) {
-                Some(window_name) => {
-                    let window_name = window_name.clone();
-                    for scene in self
-                        .active_scenes
-                        .iter_mut()
-                        .filter(|scene| scene.target_window == window_name)
+            } => {
+                for scene in
+                    Self::receiving_scenes(&mut self.active_scenes, &mut self.suspended_scenes)
+                        .filter(|scene| scene.target_window.matches(id, &self.window_ids))
+                {
+                    if let Some((_, _, position)) = self
+                        .cursors
+                        .iter()
+                        .find(|(device, window, _)| device == device_id && window == id)
                     {
-                        if let Some((_, _, position)) = self
-                            .cursors
+                        let world_position = self
+                            .window_sizes
                             .iter()
-                            .find(|(device, window, _)| device == device_id && window == id)
-                        {
-                            let events = scene.handle_mouse_input(&MouseEvent {
+                            .find(|(i, _)| i == id)
+                            .and_then(|(_, size)| scene.world_position(position, size));
+                        scene.handle_mouse_input(
+                            &MouseEvent {
                                 state: *state,
                                 button: *button,
                                 position: position.clone(),
-                            });
-                            for event in events {
-                                window_manager.send_event(GameEvent::External(event));
-                            }
-                        }
+                                world_position,
+                                modifiers: *self.input.modifiers(),
+                            },
+                            &mut self.input_events,
+                        );
                     }
                 }
-                None => {
-                    warn!("No window name found for window id {:?}", id)
+                for event in self.input_events.drain(..) {
+                    window_manager.send_event(GameEvent::External(event));
                 }
-            },
+                self.handle_binding(id, Binding::Mouse(*button), *state, window_manager);
+            }
+            WindowEvent::Ime(ime) => {
+                let input = match ime {
+                    Ime::Commit(text) => TextInputEvent::Commit(text.clone()),
+                    Ime::Preedit(text, cursor) if self.ime_preedit => {
+                        TextInputEvent::Preedit(text.clone(), *cursor)
+                    }
+                    _ => return true,
+                };
+                for scene in
+                    Self::receiving_scenes(&mut self.active_scenes, &mut self.suspended_scenes)
+                        .filter(|scene| scene.target_window.matches(id, &self.window_ids))
+                {
+                    scene.handle_text_input(&input, &mut self.input_events);
+                }
+                for event in self.input_events.drain(..) {
+                    window_manager.send_event(GameEvent::External(event));
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.input.set_modifiers(modifiers.state());
+            }
+            WindowEvent::Focused(false) => {
+                self.input.clear();
+            }
             WindowEvent::KeyboardInput { event, .. } => {
-                match self.get_window_name(id) {
-                    Some(window_name) => {
-                        let window_name = window_name.clone();
-                        for scene in self
-                            .active_scenes
-                            .iter_mut()
-                            .filter(|scene| scene.target_window == window_name)
-                        {
-                            let events = scene.handle_key_input(event);
-                            for event in events {
-                                window_manager.send_event(GameEvent::External(event));
-                            }
-                        }
+                self.input.handle_key_event(event);
+                if let (Some(key), ElementState::Pressed, false) =
+                    (self.debug_overlay_key, event.state, event.repeat)
+                {
+                    if event.physical_key == PhysicalKey::Code(key) {
+                        self.toggle_debug_overlay(window_manager);
                     }
-                    None => {
-                        warn!("No window name found for window id {:?}", id)
+                }
+                if let (PhysicalKey::Code(key), false) = (event.physical_key, event.repeat) {
+                    self.handle_binding(id, Binding::Key(key), event.state, window_manager);
+                }
+                if event.repeat && !self.key_repeat {
+                    return true;
+                }
+                for scene in
+                    Self::receiving_scenes(&mut self.active_scenes, &mut self.suspended_scenes)
+                        .filter(|scene| scene.target_window.matches(id, &self.window_ids))
+                {
+                    scene.handle_key_input(event, self.input.modifiers(), &mut self.input_events);
+                }
+                //Shortcuts like ctrl+a are no text, but AltGr reports ctrl+alt on Windows
+                let modifiers = self.input.modifiers();
+                let shortcut =
+                    (modifiers.control_key() && !modifiers.alt_key()) || modifiers.super_key();
+                let ime = self.ime_windows.contains(id);
+                if let (Some(text), false, false) = (typed_text(event), shortcut, ime) {
+                    let input = TextInputEvent::Character(text.to_string());
+                    for scene in
+                        Self::receiving_scenes(&mut self.active_scenes, &mut self.suspended_scenes)
+                            .filter(|scene| scene.target_window.matches(id, &self.window_ids))
+                    {
+                        scene.handle_text_input(&input, &mut self.input_events);
                     }
-                };
+                }
+                for event in self.input_events.drain(..) {
+                    window_manager.send_event(GameEvent::External(event));
+                }
             }
             _ => {}
         }
         true
     }
 
+    fn about_to_wait(
+        &mut self,
+        window_manager: &mut WindowManager<GameEvent<E>>,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+    ) -> Option<Instant>
+    where
+        Self: Sized,
+    {
+        let time_source = self.event_loop_time_source.as_mut()?;
+        let now = Instant::now();
+        match time_source.poll_frame(now) {
+            FramePoll::Due(delta_t) => {
+                window_manager.send_event(GameEvent::Timer(delta_t));
+                Some(now)
+            }
+            FramePoll::Wait(next_frame) => Some(next_frame),
+            FramePoll::Stopped => {
+                self.event_loop_time_source = None;
+                None
+            }
+        }
+    }
+
     fn user_event(
         &mut self,
         window_manager: &mut WindowManager<GameEvent<E>>,
         graphics_provider: &mut GraphicsProvider,
-        _event_loop: &winit::event_loop::ActiveEventLoop,
+        event_loop: &winit::event_loop::ActiveEventLoop,
         event: GameEvent<E>,
     ) where
         Self: Sized,
@@ -361,110 +1534,313 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
         match event {
             GameEvent::Resumed => {
                 self.activate_scenes(window_manager);
+                self.request_preload(window_manager, graphics_provider);
 
-                let ns_per_frame = 1e9 / (self.target_fps as f64);
-                let frame_duration = Duration::from_nanos(ns_per_frame as u64);
-                let timer_event_loop = window_manager.create_event_loop_proxy();
-                thread::spawn(move || {
-                    let mut last_update = Instant::now();
-                    loop {
-                        match timer_event_loop.send_event(GameEvent::Timer(last_update.elapsed())) {
-                            Ok(()) => {}
-                            Err(_) => break,
-                        };
-                        last_update = Instant::now();
-                        thread::sleep(frame_duration);
+                if self.use_event_loop_timer {
+                    if self.event_loop_time_source.is_none() {
+                        self.event_loop_time_source = self.time_source.take();
                     }
-                });
+                } else if let Some(mut time_source) = self.time_source.take() {
+                    let timer_event_loop = window_manager.create_event_loop_proxy();
+                    thread::spawn(move || {
+                        while let Some(delta_t) = time_source.next_frame() {
+                            match timer_event_loop.send_event(GameEvent::Timer(delta_t)) {
+                                Ok(()) => {}
+                                Err(_) => break,
+                            };
+                        }
+                    });
+                }
             }
             GameEvent::NewWindow(id, name) => {
                 self.window_ids.push((name.clone(), id.clone()));
-                for i in 0..self.pending_scenes.len() {
-                    let scene = &self.pending_scenes[i];
-                    if scene.target_window == name {
-                        self.request_render_scene(
-                            &id,
-                            window_manager,
-                            scene.render_scene.clone(),
-                            scene.shader_descriptor.clone(),
-                        );
-                    }
+                if let Some(event) = E::window_created(&name, &id) {
+                    window_manager.send_event(GameEvent::External(event));
+                }
+                if let Some(window) = window_manager.get_window(&id) {
+                    let scale_factor = window.scale_factor();
+                    self.set_scale_factor(window_manager, &id, scale_factor);
+                }
+                let render_scenes = self
+                    .pending_scenes
+                    .iter()
+                    .filter(|scene| scene.target_window.matches(&id, &self.window_ids))
+                    .flat_map(|scene| {
+                        iter::once((scene.render_scene.clone(), scene.shader_descriptor.clone()))
+                            .chain(scene.extra_render_scenes.iter().cloned())
+                    })
+                    .collect::<Vec<_>>();
+                for (render_scene, shader_descriptor) in render_scenes {
+                    self.request_render_scene(&id, window_manager, render_scene, shader_descriptor);
                 }
+                self.request_debug_overlay(&id, window_manager);
+                self.request_preload(window_manager, graphics_provider);
             }
             GameEvent::NewRenderScene(render_scene) => {
-                let index = self
+                if self
+                    .debug_overlay_windows
+                    .iter()
+                    .any(|window| debug_overlay_name(window) == render_scene)
+                {
+                    graphics_provider.set_z_index_render_scene(&render_scene, i32::MAX);
+                    return;
+                }
+                let index = match self
                     .pending_scenes
                     .iter()
                     .position(|scene| scene.render_scene == render_scene)
-                    .expect("Scene Vanished before getting created fully");
-                for sprite_sheet in self.pending_scenes[index]
-                    .entities
-                    .iter()
-                    .map(|e| e.sprite_sheets())
-                    .flatten()
                 {
-                    self.request_sprite_sheet(&sprite_sheet, window_manager);
+                    Some(index) => index,
+                    None => {
+                        //Extra render scenes do not activate their scene
+                        if let Some(scene) = self
+                            .pending_scenes
+                            .iter()
+                            .chain(self.active_scenes.iter())
+                            .chain(self.suspended_scenes.iter())
+                            .find(|scene| {
+                                scene.extra_render_scenes.iter().any(|(r, _)| *r == render_scene)
+                            })
+                        {
+                            graphics_provider.set_z_index_render_scene(&render_scene, scene.z_index);
+                            if self.hidden_scenes.contains(&scene.name) {
+                                graphics_provider
+                                    .set_visibility_render_scene(&render_scene, &Visibility::Hidden);
+                            }
+                            return;
+                        }
+                        warn!(
+                            "No pending scene for the new RenderScene {:?}. It vanished before getting created fully",
+                            render_scene
+                        );
+                        return;
+                    }
+                };
+                if index > 0 {
+                    debug!(
+                        "Scene {:?} waits for the scenes started before it",
+                        self.pending_scenes[index].name
+                    );
                 }
-                let scene = self.pending_scenes.remove(index);
-                window_manager.send_event(GameEvent::External(E::new_scene(&scene)));
-                self.active_scenes.push(scene);
-                self.active_scenes.sort_by_key(|s| s.z_index);
+                self.created_render_scenes.push(render_scene);
+                self.activate_created_scenes(window_manager, graphics_provider);
             }
+            GameEvent::NewSpriteSheet(label, _)
+                if self.drop_sprite_sheet_reply(&label, window_manager) => {}
             GameEvent::NewSpriteSheet(label, None) => {
-                panic!("Could not load SpriteSheet '{:?}'", label)
-                // self.request_sprite_sheet(label, window_manager)
+                self.sprite_sheet_failed(&label, window_manager, graphics_provider)
             }
             GameEvent::NewSpriteSheet(label, Some(id)) => {
-                if self
-                    .sprite_sheets
+                let dimensions = self
+                    .scene_sprite_sheets
                     .iter()
-                    .find(|(l, _)| label == *l)
-                    .is_none()
-                {
-                    let dimensions = &self.ressources.get_sprite_sheet(&label).1;
-                    let sprite_sheet = SpriteSheet::new(id, dimensions);
-                    self.sprite_sheets.push((label.clone(), sprite_sheet));
+                    .find_map(|(scene, scoped)| {
+                        scoped
+                            .iter()
+                            .find(|(_, l)| *l == label)
+                            .map(|(name, _)| (scene, name))
+                    })
+                    .and_then(|(scene, name)| self.ressources.get_scene_sprite_sheet(scene, name))
+                    .map(|(_, dimensions)| dimensions)
+                    .unwrap_or_else(|| self.ressources.get_sprite_sheet(&label).1);
+                //A sprite sheet requested again reports its current index
+                self.sprite_sheets
+                    .insert(label.clone(), SpriteSheet::new(id, &dimensions));
+                //Entities rendered without the sprite sheet until now
+                for scene in self.active_scenes.iter() {
+                    Self::mark_scene_dirty(&mut self.dirty_scenes, &scene.name);
+                    let sprite_sheets = Self::sprite_sheet_scope(
+                        &self.sprite_sheets,
+                        &self.scene_sprite_sheets,
+                        &scene.name,
+                    );
+                    Self::bind_scene_texture(scene, sprite_sheets, graphics_provider);
                 }
+                self.sprite_sheet_loaded(&label, window_manager);
             }
             GameEvent::Timer(delta_t) => {
-                for scene in self
-                    .active_scenes
-                    .iter_mut()
-                    .chain(self.suspended_scenes.iter_mut())
-                {
-                    let mut vertices = VertexBuffer::new();
-                    let mut indices = IndexBuffer::new();
-                    let entities = &mut scene.entities;
-                    entities.sort_by(|a, b| a.z().partial_cmp(&b.z()).expect("NaN NaN NaN"));
-                    for i in 0..entities.len() {
-                        let (left, right) = entities.split_at_mut(i);
-                        let (entity, right) = right.split_first_mut().expect("i out of bounds");
-                        let interactions = left.iter().chain(right.iter()).map(|e| &*e).collect();
-                        let events = entity.update(&interactions, &delta_t, &scene.name);
-                        for event in events {
-                            window_manager.send_event(GameEvent::External(event))
+                let delta_t = delta_t.min(self.max_delta_t);
+                self.elapsed += delta_t;
+                self.render_stats = self
+                    .window_ids
+                    .iter()
+                    .filter_map(|(_, id)| {
+                        graphics_provider
+                            .render_stats(id)
+                            .map(|stats| (id.clone(), stats.clone()))
+                    })
+                    .collect();
+                for (tween, elapsed) in self.tweens.iter_mut() {
+                    *elapsed += delta_t;
+                    let values = tween.values(*elapsed);
+                    graphics_provider
+                        .update_uniform_buffer(&tween.uniform, bytemuck::cast_slice(&values));
+                }
+                let (finished, running) = std::mem::take(&mut self.tweens)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|(tween, elapsed)| tween.is_finished(*elapsed));
+                self.tweens = running;
+                for (tween, _) in finished {
+                    if let Some(event) = E::uniform_tween_finished(&tween.uniform) {
+                        window_manager.send_event(GameEvent::External(event));
+                    }
+                }
+                for event in self.state.update(&delta_t) {
+                    window_manager.send_event(GameEvent::External(event));
+                }
+                //The timer starts before the first scenes are fully created, the state and the
+                //tweens run meanwhile
+                if self.active_scenes.is_empty() {
+                    return;
+                }
+                let time = TimeUniform::new(self.elapsed.as_secs_f32()).to_bytes();
+                let mut render_updates = Vec::new();
+                //Suspended scenes keep their last buffers on the GPU
+                for scene in self.active_scenes.iter_mut() {
+                    for event in
+                        scene.update_entities(&delta_t, self.spatial_grid_cell_size, &self.input)
+                    {
+                        window_manager.send_event(GameEvent::External(event))
+                    }
+                    let shaders = iter::once((&scene.render_scene, &scene.shader_descriptor))
+                        .chain(scene.extra_render_scenes.iter().map(|(r, s)| (r, s)));
+                    for (render_scene, shader_descriptor) in shaders {
+                        if shader_descriptor.uniforms.contains(&TIME_UNIFORM) {
+                            graphics_provider
+                                .update_uniform_buffer(&time_uniform_name(render_scene), &time);
                         }
-                        let sprite_sheets = entity
-                            .sprite_sheets()
+                    }
+                    if let Some(index) = self.dirty_scenes.iter().position(|s| *s == scene.name) {
+                        self.dirty_scenes.swap_remove(index);
+                    } else if !scene.is_render_dirty() {
+                        continue;
+                    }
+                    let sprite_sheets = Self::sprite_sheet_scope(
+                        &self.sprite_sheets,
+                        &self.scene_sprite_sheets,
+                        &scene.name,
+                    );
+                    render_updates.append(&mut scene.render_updates(sprite_sheets));
+                }
+                if self.show_debug_overlay {
+                    for id in self.debug_overlay_windows.iter() {
+                        let window_size = match self.window_sizes.iter().find(|(i, _)| i == id) {
+                            Some((_, size)) => size,
+                            None => continue,
+                        };
+                        let ressources = &self.ressources;
+                        let scenes = self
+                            .active_scenes
                             .iter()
-                            .map(|entity_sprite_sheet| {
-                                self.sprite_sheets
-                                    .iter()
-                                    .find(|(l, _)| l == *entity_sprite_sheet)
-                                    .map(|(_, s)| s)
-                            })
-                            .collect();
-                        entity.render(&mut vertices, &mut indices, sprite_sheets);
+                            .filter(|scene| scene.target_window.matches(id, &self.window_ids))
+                            .map(|scene| {
+                                (scene, ressources.render_scene_descriptor(&scene.render_scene))
+                            });
+                        let (vertices, indices) = build_debug_overlay(scenes, window_size);
+                        render_updates.push((debug_overlay_name(id), vertices, indices));
                     }
-                    window_manager.send_event(GameEvent::RenderUpdate(
-                        scene.render_scene.clone(),
-                        vertices,
-                        indices,
-                    ));
+                }
+                if !render_updates.is_empty() {
+                    window_manager.send_event(GameEvent::RenderBatch(render_updates));
+                }
+            }
+            GameEvent::DeviceLost => {
+                warn!("Recreating all scenes on the new graphics device");
+                self.recreated_suspended_scenes
+                    .extend(self.suspended_scenes.iter().map(|s| s.name.clone()));
+                let mut scenes = std::mem::take(&mut self.active_scenes);
+                scenes.append(&mut self.suspended_scenes);
+                scenes.append(&mut self.pending_scenes);
+                self.pending_scenes = scenes;
+                self.created_render_scenes.clear();
+                self.loading_scenes.clear();
+                self.sprite_sheets.clear();
+                self.scene_sprite_sheets.clear();
+                self.preload_started = false;
+                self.request_preload(window_manager, graphics_provider);
+                for id in std::mem::take(&mut self.debug_overlay_windows) {
+                    self.request_debug_overlay(&id, window_manager);
+                }
+                self.activate_scenes(window_manager);
+            }
+            GameEvent::GraphicsReady => {
+                info!("The graphics device is ready");
+                self.request_preload(window_manager, graphics_provider);
+                if let Some(event) = E::graphics_ready() {
+                    window_manager.send_event(GameEvent::External(event));
+                }
+            }
+            GameEvent::WindowResized(id, size) => {
+                let name = self.window_ids.iter().find(|(_, i)| *i == id).map(|(n, _)| n);
+                let mut events = self.state.handle_window_resized(&id, name, &size);
+                for scene in
+                    Self::receiving_scenes(&mut self.active_scenes, &mut self.suspended_scenes)
+                        .filter(|scene| scene.target_window.matches(&id, &self.window_ids))
+                {
+                    scene.handle_resize(&id, name, &size, &mut events);
+                }
+                for event in events {
+                    window_manager.send_event(GameEvent::External(event));
+                }
+            }
+            #[cfg(feature = "audio")]
+            GameEvent::PlaySound(sound, volume) => {
+                let path = match self.ressources.get_sound(&sound) {
+                    Some(path) => path,
+                    None => {
+                        warn!("Sound {:?} is not in the RessourceDescriptor", sound);
+                        return;
+                    }
+                };
+                if let Some(audio) = self.audio.get_or_insert_with(audio::AudioPlayer::new) {
+                    audio.play(&sound, path, volume);
+                }
+            }
+            GameEvent::RequestImeAllowed(window_name, allowed) => {
+                match self.window_ids.iter().find(|(name, _)| *name == window_name) {
+                    Some((_, id)) => {
+                        window_manager.set_ime_allowed(id, allowed);
+                        self.ime_windows.retain(|w| w != id);
+                        if allowed {
+                            self.ime_windows.push(id.clone());
+                        }
+                    }
+                    None => warn!(
+                        "Tried to allow IME on window {:?}, but it does not exist",
+                        window_name
+                    ),
+                }
+            }
+            GameEvent::RequestWindowLevel(window_name, level) => {
+                match self.window_ids.iter().find(|(name, _)| *name == window_name) {
+                    Some((_, id)) => window_manager.set_window_level(id, level),
+                    None => warn!(
+                        "Tried to set the level of window {:?}, but it does not exist",
+                        window_name
+                    ),
+                }
+            }
+            GameEvent::RequestCursor(window_name, request) => {
+                let id = match self.window_ids.iter().find(|(name, _)| *name == window_name) {
+                    Some((_, id)) => *id,
+                    None => {
+                        warn!(
+                            "Tried to change the cursor of window {:?}, but it does not exist",
+                            window_name
+                        );
+                        return;
+                    }
+                };
+                match request {
+                    CursorRequest::Visible(visible) => {
+                        window_manager.set_cursor_visible(&id, visible)
+                    }
+                    CursorRequest::Grab(mode) => window_manager.set_cursor_grab(&id, mode),
+                    CursorRequest::Icon(icon) => window_manager.set_cursor_icon(&id, icon),
                 }
             }
             GameEvent::External(event) => {
-                println!("EXTERN EVENT: {:?}", event);
+                debug!("External event: {:?}", event);
                 if event.is_request_new_scenes() {
                     info!("Creating new Scenes");
                     let scenes = event
@@ -479,21 +1855,52 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
                     let (mut entities, scene) = event
                         .consume_add_entities_request()
                         .expect("Bad implementation of ExternalEvent::is_add_entities() should only return true, if ExternalEvent::consume_add_entities_request() returns Some(entities, scene)");
-                    let scene = &mut self
-                        .active_scenes
-                        .iter_mut()
-                        .find(|s| s.name == scene)
-                        .unwrap_or_else(|| {
-                            self.suspended_scenes
-                                .iter_mut()
-                                .find(|s| s.name == scene)
-                                .expect(&format!("Found no active nor suspended scene {:?}", scene))
-                        });
-                    scene.entities.append(&mut entities);
+                    match Self::receiving_scenes(
+                        &mut self.active_scenes,
+                        &mut self.suspended_scenes,
+                    )
+                    .find(|s| s.name == scene)
+                    {
+                        Some(scene) => {
+                            scene.entities.append(&mut entities);
+                            Self::mark_scene_dirty(&mut self.dirty_scenes, &scene.name);
+                        }
+                        None => warn!(
+                            "Tried to add entities to Scene {:?}, but it is neither active nor receiving while suspended",
+                            scene
+                        ),
+                    }
                     return;
                 }
+                if let Some(scene) = event.is_request_scene_status() {
+                    if let Some(reply) = E::scene_status(scene, self.scene_status(scene)) {
+                        window_manager.send_event(GameEvent::External(reply));
+                    }
+                }
+                if let Some((action, bindings)) = event.is_request_rebind_action() {
+                    match &mut self.action_map {
+                        Some(action_map) => action_map.rebind(action.clone(), bindings.to_vec()),
+                        None => log::warn!("Cannot rebind {:?} without an ActionMap", action),
+                    }
+                }
+                if let Some(scene) = event.is_request_scene_visibility() {
+                    let visibility = self.scene_visibility(scene);
+                    if let Some(reply) = E::scene_visibility(scene, visibility) {
+                        window_manager.send_event(GameEvent::External(reply));
+                    }
+                }
+                if let Some(window) = event.is_request_render_stats() {
+                    let stats = self.render_stats(window).cloned();
+                    if let Some(reply) = E::render_stats(window, stats) {
+                        window_manager.send_event(GameEvent::External(reply));
+                    }
+                }
                 if let Some((scene, visibility)) = event.is_request_set_visibility_scene() {
-                    let render_scene = &self
+                    self.hidden_scenes.retain(|s| s != scene);
+                    if *visibility == Visibility::Hidden {
+                        self.hidden_scenes.push(scene.clone());
+                    }
+                    let render_scenes = self
                         .active_scenes
                         .iter()
                         .find(|s| s.name == *scene)
@@ -503,11 +1910,51 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
                                 .find(|s| s.name == *scene)
                                 .expect(&format!("Found no active nor suspended scene {:?}", scene))
                         })
-                        .render_scene;
-                    window_manager.send_event(GameEvent::RequestSetVisibilityRenderScene(
-                        render_scene.clone(),
-                        visibility.clone(),
-                    ));
+                        .render_scenes();
+                    for render_scene in render_scenes {
+                        window_manager.send_event(GameEvent::RequestSetVisibilityRenderScene(
+                            render_scene.clone(),
+                            visibility.clone(),
+                        ));
+                    }
+                }
+                if let Some((scene, z_index)) = event.is_request_set_scene_z_index() {
+                    match self
+                        .active_scenes
+                        .iter_mut()
+                        .chain(self.suspended_scenes.iter_mut())
+                        .find(|s| s.name == *scene)
+                    {
+                        Some(scene) => {
+                            scene.z_index = z_index;
+                            for render_scene in scene.render_scenes() {
+                                graphics_provider.set_z_index_render_scene(render_scene, z_index);
+                            }
+                        }
+                        None => warn!(
+                            "Tried to set the z_index of Scene {:?}, but it is neither active nor suspended",
+                            scene
+                        ),
+                    }
+                    self.active_scenes.sort_by_key(|s| s.z_index);
+                }
+                if let Some((scene, scissor)) = event.is_request_set_scissor_scene() {
+                    match self
+                        .active_scenes
+                        .iter()
+                        .chain(self.suspended_scenes.iter())
+                        .find(|s| s.name == *scene)
+                    {
+                        Some(scene) => {
+                            for render_scene in scene.render_scenes() {
+                                graphics_provider.set_scissor_render_scene(render_scene, scissor);
+                            }
+                        }
+                        None => warn!(
+                            "Tried to set the scissor of Scene {:?}, but it is neither active nor suspended",
+                            scene
+                        ),
+                    }
                 }
                 if let Some(suspendable_scene) = event.is_request_suspend_scene() {
                     info!("Suspending Scene {:?}", suspendable_scene);
@@ -535,6 +1982,13 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
                         let scene = self.suspended_scenes.remove(index);
                         self.active_scenes.push(scene);
                         self.active_scenes.sort_by_key(|s| s.z_index);
+                    } else if let Some(index) = self
+                        .recreated_suspended_scenes
+                        .iter()
+                        .position(|s| s == activatable_scene)
+                    {
+                        //Still being recreated, it becomes active once its render scene exists
+                        self.recreated_suspended_scenes.swap_remove(index);
                     } else {
                         warn!(
                             "Tried to activate suspended Scene {:?}, but it is not suspended",
@@ -543,55 +1997,69 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
                     }
                 }
                 if let Some(deletable_scene) = event.is_request_delete_scene() {
-                    info!("Deleting Scene {:?}", deletable_scene);
-                    if let Some(active_index) = self
-                        .active_scenes
-                        .iter()
-                        .position(|s| s.name == *deletable_scene)
-                    {
-                        let scene = self.active_scenes.remove(active_index);
-                        graphics_provider.remove_render_scene(&scene.render_scene);
-                    } else if let Some(suspended_index) = self
-                        .suspended_scenes
-                        .iter()
-                        .position(|s| s.name == *deletable_scene)
-                    {
-                        let scene = self.suspended_scenes.remove(suspended_index);
-                        graphics_provider.remove_render_scene(&scene.render_scene);
-                    } else {
-                        warn!(
-                            "Tried to delete Scene {:?}, but its neither active nor suspended",
-                            deletable_scene
-                        );
-                    }
+                    self.delete_scene(deletable_scene, window_manager, graphics_provider);
+                }
+                if let Some(tween) = event.is_request_tween_uniform() {
+                    //A running tween of the same uniform would overwrite the new one every frame
+                    self.tweens.retain(|(t, _)| t.uniform != tween.uniform);
+                    self.tweens.push((tween.clone(), Duration::ZERO));
                 }
                 if let Some((uniform_name, contents)) = event.is_update_uniform_buffer() {
                     graphics_provider.update_uniform_buffer(uniform_name, contents);
                 }
                 if let Some((entity, scene)) = event.is_delete_entity() {
                     info!("Deleting Entiy {:?} from Scene {:?}", entity, scene);
-                    let scene = self
-                        .active_scenes
-                        .iter_mut()
-                        .find(|s| s.name == *scene)
-                        .unwrap_or_else(|| {
-                            self.suspended_scenes
-                                .iter_mut()
-                                .find(|s| s.name == *scene)
-                                .expect(&format!("Found no active nor suspended scene {:?}", scene))
-                        });
-                    scene.entities.retain(|e| e.name() != entity);
-                    for e in scene.entities.iter_mut() {
-                        e.delete_child_entity(entity);
+                    match Self::receiving_scenes(
+                        &mut self.active_scenes,
+                        &mut self.suspended_scenes,
+                    )
+                    .find(|s| s.name == *scene)
+                    {
+                        Some(scene) => {
+                            scene.entities.retain(|e| e.name() != entity);
+                            for e in scene.entities.iter_mut() {
+                                e.delete_child_entity(entity);
+                            }
+                            Self::mark_scene_dirty(&mut self.dirty_scenes, &scene.name);
+                        }
+                        None => warn!(
+                            "Tried to delete Entity {:?} from Scene {:?}, but it is neither active nor receiving while suspended",
+                            entity, scene
+                        ),
                     }
                 }
                 if let Some(scene) = event.is_request_render_scene() {
                     if let Some(scene) = self.active_scenes.iter_mut().find(|s| s.name == *scene) {
-                        scene.simple_render(&self.sprite_sheets, window_manager)
+                        let sprite_sheets = Self::sprite_sheet_scope(
+                            &self.sprite_sheets,
+                            &self.scene_sprite_sheets,
+                            &scene.name,
+                        );
+                        scene.simple_render(sprite_sheets, window_manager)
                     } else {
                         warn!("Tried to render Scene {:?}, but it is not active", scene);
                     }
                 }
+                if let Some((window, request)) = event.is_request_cursor() {
+                    window_manager.send_event(GameEvent::RequestCursor(window.clone(), *request));
+                }
+                #[cfg(feature = "audio")]
+                if let Some((sound, volume)) = event.is_play_sound() {
+                    window_manager.send_event(GameEvent::PlaySound(sound.clone(), volume));
+                }
+                if let Some(max_fps) = event.is_request_frame_limit() {
+                    window_manager.send_event(GameEvent::RequestFrameLimit(max_fps));
+                }
+                if let Some((window, level)) = event.is_request_window_level() {
+                    window_manager.send_event(GameEvent::RequestWindowLevel(window.clone(), level));
+                }
+                if let Some((window, allowed)) = event.is_request_ime_allowed() {
+                    window_manager
+                        .send_event(GameEvent::RequestImeAllowed(window.clone(), allowed));
+                }
+                if event.is_request_toggle_debug_overlay() {
+                    self.toggle_debug_overlay(window_manager);
+                }
                 if event.is_end_game() {
                     window_manager.send_event(GameEvent::EndGame);
                     return;
@@ -599,7 +2067,9 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
                 let response_events = if event.is_entity_event() {
                     let (target, event) = event.consume_entity_event().expect("unreachable");
                     let mut target_entity = None;
-                    for scene in &mut self.active_scenes {
+                    for scene in
+                        Self::receiving_scenes(&mut self.active_scenes, &mut self.suspended_scenes)
+                    {
                         match scene.entities.iter_mut().find(|e| e.name() == &target) {
                             Some(entity) => {
                                 target_entity = Some(entity);
@@ -610,15 +2080,26 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
                     }
                     if let Some(target) = target_entity {
                         target.handle_event(event)
+                    } else if let Some(scene) = self
+                        .suspended_scenes
+                        .iter()
+                        .find(|s| s.entities.iter().any(|e| e.name() == &target))
+                    {
+                        warn!(
+                            "Dropped event to entity {:?}, as its Scene {:?} is suspended without receive_while_suspended",
+                            target, scene.name
+                        );
+                        vec![]
                     } else {
                         warn!(
-                            "Tried to send event to entity {:?}, but it does not exist in an active scene",
+                            "Tried to send event to entity {:?}, but it does not exist in a scene receiving events",
                             target
                         );
                         vec![]
                     }
                 } else {
-                    self.state.handle_event(event)
+                    self.state
+                        .handle_event_with_loop(event, &ActiveLoop::new(event_loop))
                 };
 
                 for event in response_events {
@@ -629,3 +2110,84 @@ impl<E: ExternalEvent + 'static, S: State<E>> EventManager<GameEvent<E>> for Gam
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::WindowDescriptor;
+    use example::{EmptyExternalEvent, SimpleGameState};
+    use ressource_descriptor::RessourceDescriptorBuilder;
+
+    fn scene(name: &str) -> Scene<EmptyExternalEvent> {
+        Scene {
+            name: name.into(),
+            shader_descriptor: ShaderDescriptor {
+                file: "shader.wgsl",
+                vertex_shader: "vs_main",
+                fragment_shader: "fs_main",
+                uniforms: &[],
+            },
+            render_scene: name.into(),
+            target_window: "window".into(),
+            entities: vec![],
+            z_index: 0,
+            extra_render_scenes: vec![],
+            receive_while_suspended: false,
+        }
+    }
+
+    fn game() -> Game<EmptyExternalEvent, SimpleGameState> {
+        let ressources = RessourceDescriptorBuilder::new(RenderSceneDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffer_layout: SimpleVertex::describe_buffer_layout(),
+            use_textures: false,
+            target_aspect: None,
+            use_depth: false,
+            blend_mode: BlendMode::Alpha,
+            use_global_uniforms: false,
+            load_op: SceneLoadOp::Clear,
+        })
+        .with_windows(vec![("window".into(), WindowDescriptor::new())])
+        .with_preload_sprite_sheets(vec!["tiles".into()])
+        .build();
+        Game::new(ressources, 60, SimpleGameState::new(scene("level")))
+    }
+
+    fn window_manager() -> WindowManager<GameEvent<EmptyExternalEvent>> {
+        let mut window_manager = WindowManager::default();
+        window_manager.set_event_coalescing(true);
+        window_manager
+    }
+
+    #[test]
+    fn preload_waits_for_the_device() {
+        let mut window_manager = window_manager();
+        let graphics_provider = GraphicsProvider::new();
+        let mut game = game().with_preloading(None);
+        game.request_preload(&mut window_manager, &graphics_provider);
+        let events = window_manager.take_queued_events();
+        //Only the window of the held back scene, whose device creates the textures
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0],
+            GameEvent::RequestNewWindow(_, name) if *name == "window".into()
+        ));
+        assert!(!game.preload_started);
+        assert!(game.preloading.is_empty());
+    }
+
+    #[test]
+    fn sprite_sheet_failed_without_device_is_preloaded_again() {
+        let mut window_manager = window_manager();
+        let graphics_provider = GraphicsProvider::new();
+        let mut game = game().with_preloading(Some(scene("loading")));
+        //Requested before the device was lost
+        game.preload_started = true;
+        game.preloading.push("tiles".into());
+        game.sprite_sheet_failed(&"tiles".into(), &mut window_manager, &graphics_provider);
+        assert!(!game.preload_started);
+        assert_eq!(game.preloading, vec![SpriteSheetName::from("tiles")]);
+        assert!(game.preload_scenes.is_some());
+        assert!(window_manager.take_queued_events().is_empty());
+    }
+}
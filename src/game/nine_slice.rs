@@ -0,0 +1,272 @@
+use log::error;
+use twod::Vector;
+
+use crate::{
+    app::{fits_u16_indices, write_regular_ngon_u16, IndexBuffer, VertexBuffer},
+    game_engine::BoundingBox,
+    graphics::Vertex,
+};
+
+use super::sprite_sheet::{SpritePosition, SpriteSheet, TextureCoordinates};
+
+pub mod exports {
+    pub use super::{Insets, NineSlice};
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Insets {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+impl Insets {
+    pub const fn uniform(inset: f32) -> Self {
+        Self {
+            left: inset,
+            right: inset,
+            top: inset,
+            bottom: inset,
+        }
+    }
+}
+
+///A sprite split into 9 parts by its insets. When drawn into a box, the corners keep the size of
+///the border, the edges stretch along one axis and the center along both
+#[derive(Debug, Clone)]
+pub struct NineSlice {
+    pub sprite: SpritePosition,
+    ///Borders of the sprite as fractions of its width and height
+    pub sprite_insets: Insets,
+    ///Size of the borders in the world. Shrinks to fit boxes smaller than two borders
+    pub border: Insets,
+}
+impl NineSlice {
+    ///Writes one quad per part that is not empty, eg. only the corners and the horizontal edges
+    ///for a box exactly as high as its two borders. `create_vertex` turns each corner of the 4x4
+    ///grid with its texture coordinates into the vertex type of the render scene. Nothing is
+    ///written if the quads do not fit into the u16 indices
+    pub fn write<V: Vertex>(
+        &self,
+        vertices: &mut VertexBuffer,
+        indices: &mut IndexBuffer,
+        sprite_sheet: &SpriteSheet,
+        target: &BoundingBox,
+        create_vertex: fn(Vector<f32>, &TextureCoordinates, u32) -> V,
+    ) {
+        let (width, height) = (target.size.width(), target.size.height());
+        let horizontal_scale = fit_scale(self.border.left + self.border.right, width);
+        let vertical_scale = fit_scale(self.border.top + self.border.bottom, height);
+        let left = target.anchor.x - width / 2.0;
+        let right = target.anchor.x + width / 2.0;
+        let top = target.anchor.y + height / 2.0;
+        let bottom = target.anchor.y - height / 2.0;
+        let xs = [
+            left,
+            left + self.border.left * horizontal_scale,
+            right - self.border.right * horizontal_scale,
+            right,
+        ];
+        let ys = [
+            top,
+            top - self.border.top * vertical_scale,
+            bottom + self.border.bottom * vertical_scale,
+            bottom,
+        ];
+
+        let [top_left, _, bottom_right, _] = sprite_sheet.get_sprite_coordinates(&self.sprite);
+        let sprite_width = bottom_right.u - top_left.u;
+        let sprite_height = bottom_right.v - top_left.v;
+        let us = [
+            top_left.u,
+            top_left.u + sprite_width * self.sprite_insets.left,
+            bottom_right.u - sprite_width * self.sprite_insets.right,
+            bottom_right.u,
+        ];
+        let vs = [
+            top_left.v,
+            top_left.v + sprite_height * self.sprite_insets.top,
+            bottom_right.v - sprite_height * self.sprite_insets.bottom,
+            bottom_right.v,
+        ];
+
+        let columns = (0..3).filter(|&c| xs[c] != xs[c + 1]).count();
+        let rows = (0..3).filter(|&r| ys[r] != ys[r + 1]).count();
+        if !fits_u16_indices(vertices, columns * rows * 4) {
+            error!("NineSlice does not fit into the u16 indices of its render scene");
+            return;
+        }
+        let texture = sprite_sheet.texture();
+        for row in 0..3 {
+            for column in 0..3 {
+                if xs[column] == xs[column + 1] || ys[row] == ys[row + 1] {
+                    continue;
+                }
+                let corner = |c: usize, r: usize| {
+                    create_vertex(
+                        Vector::new(xs[c], ys[r]),
+                        &TextureCoordinates { u: us[c], v: vs[r] },
+                        texture,
+                    )
+                };
+                write_regular_ngon_u16(
+                    vertices,
+                    indices,
+                    &[
+                        corner(column, row),
+                        corner(column + 1, row),
+                        corner(column + 1, row + 1),
+                        corner(column, row + 1),
+                    ],
+                );
+            }
+        }
+    }
+}
+
+///Factor shrinking the borders to fit the available length
+fn fit_scale(borders: f32, available: f32) -> f32 {
+    if borders > available && borders > 0.0 {
+        available / borders
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::MAX_U16_VERTICES,
+        game_engine::SpriteSheetDimensions,
+        graphics::BufferWriter,
+        Size,
+    };
+    use bytemuck::Zeroable;
+    use repr_trait::C;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, repr_trait::C)]
+    struct UvVertex {
+        position: [f32; 2],
+        uv: [f32; 2],
+    }
+    const UV_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+    impl Vertex for UvVertex {
+        fn attributes() -> &'static [wgpu::VertexAttribute] {
+            &UV_VERTEX_ATTRIBUTES
+        }
+    }
+    fn uv_vertex(position: Vector<f32>, uv: &TextureCoordinates, _texture: u32) -> UvVertex {
+        UvVertex {
+            position: [position.x, position.y],
+            uv: [uv.u, uv.v],
+        }
+    }
+
+    ///The positions and texture coordinates written, with the index count
+    fn write(width: f32, height: f32) -> (Vec<([f32; 2], [f32; 2])>, u32) {
+        let nine_slice = NineSlice {
+            sprite: SpritePosition::new(0, 0),
+            sprite_insets: Insets::uniform(0.25),
+            border: Insets::uniform(1.0),
+        };
+        let sprite_sheet = SpriteSheet::new(2, &SpriteSheetDimensions::new(1, 1));
+        let target = BoundingBox {
+            anchor: Vector::new(0.0, 0.0),
+            size: Size::new(width, height),
+        };
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        nine_slice.write(&mut vertices, &mut indices, &sprite_sheet, &target, uv_vertex);
+        let written = vertices
+            .buffer_data()
+            .expect("A vertex buffer always has data")
+            .chunks_exact(std::mem::size_of::<UvVertex>())
+            .map(bytemuck::pod_read_unaligned::<UvVertex>)
+            .map(|vertex| (vertex.position, vertex.uv))
+            .collect();
+        (written, indices.len())
+    }
+
+    #[test]
+    fn nine_parts() {
+        let (vertices, indices) = write(6.0, 4.0);
+        assert_eq!((vertices.len(), indices), (36, 54));
+        //Top left corner, keeping the size of the border
+        assert_eq!(
+            &vertices[..4],
+            [
+                ([-3.0, 2.0], [0.0, 0.0]),
+                ([-2.0, 2.0], [0.25, 0.0]),
+                ([-2.0, 1.0], [0.25, 0.25]),
+                ([-3.0, 1.0], [0.0, 0.25]),
+            ]
+        );
+        //The center stretches the middle of the sprite over the rest of the box
+        assert_eq!(
+            &vertices[16..20],
+            [
+                ([-2.0, 1.0], [0.25, 0.25]),
+                ([2.0, 1.0], [0.75, 0.25]),
+                ([2.0, -1.0], [0.75, 0.75]),
+                ([-2.0, -1.0], [0.25, 0.75]),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_parts_are_skipped() {
+        //As high as both borders, so the middle row is empty
+        let (vertices, indices) = write(6.0, 2.0);
+        assert_eq!((vertices.len(), indices), (24, 36));
+        //The bottom left corner directly follows the top row
+        assert_eq!(
+            &vertices[12..16],
+            [
+                ([-3.0, 0.0], [0.0, 0.75]),
+                ([-2.0, 0.0], [0.25, 0.75]),
+                ([-2.0, -1.0], [0.25, 1.0]),
+                ([-3.0, -1.0], [0.0, 1.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn borders_shrink_to_fit() {
+        let (vertices, indices) = write(1.0, 1.0);
+        //Only the corners remain, each half a border wide
+        assert_eq!((vertices.len(), indices), (16, 24));
+        assert_eq!(
+            &vertices[12..],
+            [
+                ([0.0, 0.0], [0.75, 0.75]),
+                ([0.5, 0.0], [1.0, 0.75]),
+                ([0.5, -0.5], [1.0, 1.0]),
+                ([0.0, -0.5], [0.75, 1.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn u16_limit() {
+        let nine_slice = NineSlice {
+            sprite: SpritePosition::new(0, 0),
+            sprite_insets: Insets::uniform(0.25),
+            border: Insets::uniform(1.0),
+        };
+        let sprite_sheet = SpriteSheet::new(2, &SpriteSheetDimensions::new(1, 1));
+        let target = BoundingBox {
+            anchor: Vector::new(0.0, 0.0),
+            size: Size::new(6.0, 4.0),
+        };
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        vertices.extend_from_slice(&vec![UvVertex::zeroed(); MAX_U16_VERTICES - 35]);
+        //One vertex short of the 36 of the nine parts
+        nine_slice.write(&mut vertices, &mut indices, &sprite_sheet, &target, uv_vertex);
+        assert_eq!(vertices.len() as usize, MAX_U16_VERTICES - 35);
+        assert_eq!(indices.len(), 0);
+    }
+}
@@ -0,0 +1,305 @@
+use std::{fmt::Debug, time::Duration};
+
+use log::error;
+use twod::Vector;
+
+use crate::{
+    app::{fits_u16_indices, write_regular_ngon_u16, IndexBuffer, VertexBuffer},
+    game_engine::{BoundingBox, Color, WorldView},
+    graphics::Vertex,
+    Size,
+};
+
+use super::{
+    entity::{EntityName, EntityType},
+    Entity, ExternalEvent, SpriteSheet, SpriteSheetName,
+};
+
+pub mod exports {
+    pub use super::{ParticleEmitter, ParticleEmitterDescriptor};
+}
+
+#[derive(Debug, Clone)]
+pub struct ParticleEmitterDescriptor {
+    ///Particles spawned per second
+    pub spawn_rate: f32,
+    pub lifetime: Duration,
+    ///Initial velocity in units per second
+    pub velocity: Vector<f32>,
+    ///Each axis of the initial velocity is offset by a random value in [-spread, spread]
+    pub velocity_spread: f32,
+    ///Acceleration in units per second squared
+    pub gravity: Vector<f32>,
+    pub start_color: Color,
+    pub end_color: Color,
+    ///Edge length of the particle quads at spawn and at the end of their lifetime
+    pub start_size: f32,
+    pub end_size: f32,
+    ///Spawning pauses while this many particles are alive
+    pub max_particles: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Particle {
+    position: Vector<f32>,
+    velocity: Vector<f32>,
+    age: f32,
+}
+
+///Spawns, moves and renders particles as colored quads. Dead particles are recycled, so the
+///particle list never grows beyond `max_particles`
+pub struct ParticleEmitter<V: Vertex> {
+    name: EntityName,
+    descriptor: ParticleEmitterDescriptor,
+    position: Vector<f32>,
+    particles: Vec<Option<Particle>>,
+    spawn_accumulator: f32,
+    emitting: bool,
+    rng_state: u32,
+    create_vertex: fn(Vector<f32>, Color) -> V,
+}
+impl<V: Vertex> ParticleEmitter<V> {
+    ///`create_vertex` builds a vertex of the render scene from a position and a color
+    pub fn new(
+        name: EntityName,
+        descriptor: ParticleEmitterDescriptor,
+        position: Vector<f32>,
+        create_vertex: fn(Vector<f32>, Color) -> V,
+    ) -> Self {
+        Self {
+            name,
+            particles: Vec::with_capacity(descriptor.max_particles),
+            descriptor,
+            position,
+            spawn_accumulator: 0.0,
+            emitting: true,
+            rng_state: 0x9E37_79B9,
+            create_vertex,
+        }
+    }
+
+    pub fn set_position(&mut self, position: Vector<f32>) {
+        self.position = position;
+    }
+
+    ///Stop spawning new particles. Living particles finish their lifetime
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+    }
+
+    pub fn alive_particles(&self) -> usize {
+        self.particles.iter().filter(|p| p.is_some()).count()
+    }
+
+    ///xorshift, mapped to [-1, 1]
+    fn random(&mut self) -> f32 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+        self.rng_state as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    fn spawn(&mut self) {
+        let spread = self.descriptor.velocity_spread;
+        let velocity = Vector::new(
+            self.descriptor.velocity.x + self.random() * spread,
+            self.descriptor.velocity.y + self.random() * spread,
+        );
+        let particle = Particle {
+            position: self.position.clone(),
+            velocity,
+            age: 0.0,
+        };
+        if let Some(slot) = self.particles.iter_mut().find(|p| p.is_none()) {
+            *slot = Some(particle);
+        } else if self.particles.len() < self.descriptor.max_particles {
+            self.particles.push(Some(particle));
+        }
+    }
+}
+impl<V: Vertex> Debug for ParticleEmitter<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParticleEmitter")
+            .field("name", &self.name)
+            .field("position", &self.position)
+            .field("alive_particles", &self.alive_particles())
+            .finish()
+    }
+}
+
+impl<T: EntityType, E: ExternalEvent, V: Vertex + Send> Entity<T, E> for ParticleEmitter<V> {
+    fn update(
+        &mut self,
+        _world: &mut WorldView<T, E>,
+        delta_t: &Duration,
+        _scene: &super::SceneName,
+    ) -> Vec<E> {
+        let dt = delta_t.as_secs_f32();
+        let lifetime = self.descriptor.lifetime.as_secs_f32();
+        let gravity = self.descriptor.gravity.clone();
+        for slot in self.particles.iter_mut() {
+            if let Some(particle) = slot {
+                particle.age += dt;
+                if particle.age >= lifetime {
+                    *slot = None;
+                    continue;
+                }
+                particle.velocity += gravity.clone() * dt;
+                particle.position += particle.velocity.clone() * dt;
+            }
+        }
+        if self.emitting {
+            self.spawn_accumulator += self.descriptor.spawn_rate * dt;
+            while self.spawn_accumulator >= 1.0 {
+                self.spawn_accumulator -= 1.0;
+                self.spawn();
+            }
+        }
+        vec![]
+    }
+    fn render(
+        &mut self,
+        vertices: &mut VertexBuffer,
+        indices: &mut IndexBuffer,
+        _sprite_sheet: Vec<Option<&SpriteSheet>>,
+    ) {
+        let lifetime = self.descriptor.lifetime.as_secs_f32();
+        for particle in self.particles.iter().flatten() {
+            if !fits_u16_indices(vertices, 4) {
+                error!(
+                    "Particles of {:?} do not fit into the u16 indices of its render scene",
+                    self.name
+                );
+                return;
+            }
+            let t = if lifetime > 0.0 {
+                particle.age / lifetime
+            } else {
+                1.0
+            };
+            let color = self.descriptor.start_color.lerp(&self.descriptor.end_color, t);
+            let size = self.descriptor.start_size
+                + (self.descriptor.end_size - self.descriptor.start_size) * t;
+            let half = size / 2.0;
+            let (x, y) = (particle.position.x, particle.position.y);
+            let quad = [
+                (self.create_vertex)(Vector::new(x - half, y + half), color.clone()),
+                (self.create_vertex)(Vector::new(x + half, y + half), color.clone()),
+                (self.create_vertex)(Vector::new(x + half, y - half), color.clone()),
+                (self.create_vertex)(Vector::new(x - half, y - half), color),
+            ];
+            write_regular_ngon_u16(vertices, indices, &quad);
+        }
+    }
+    fn sprite_sheets(&self) -> Vec<&SpriteSheetName> {
+        vec![]
+    }
+    fn name(&self) -> &EntityName {
+        &self.name
+    }
+    fn bounding_box(&self) -> BoundingBox {
+        let size = self.descriptor.start_size.max(self.descriptor.end_size);
+        BoundingBox {
+            anchor: self.position.clone(),
+            size: Size::new(size, size),
+        }
+    }
+    fn entity_type(&self) -> T {
+        T::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::MAX_U16_VERTICES,
+        game_engine::{
+            example::{EmptyEntityType, EmptyExternalEvent, SimpleVertex},
+            InputState,
+        },
+    };
+
+    fn emitter(spawn_rate: f32, max_particles: usize) -> ParticleEmitter<SimpleVertex> {
+        let descriptor = ParticleEmitterDescriptor {
+            spawn_rate,
+            lifetime: Duration::from_secs(1),
+            velocity: Vector::new(1.0, 0.0),
+            velocity_spread: 0.0,
+            gravity: Vector::new(0.0, -2.0),
+            start_color: Color::new_rgba(255, 255, 255, 255),
+            end_color: Color::new_rgba(255, 255, 255, 0),
+            start_size: 1.0,
+            end_size: 0.0,
+            max_particles,
+        };
+        ParticleEmitter::new("emitter".into(), descriptor, Vector::new(0.0, 0.0), SimpleVertex::new)
+    }
+
+    fn update(emitter: &mut ParticleEmitter<SimpleVertex>, seconds: f32) {
+        let input = InputState::new();
+        let mut world =
+            WorldView::<EmptyEntityType, EmptyExternalEvent>::new(&mut [], &mut [], &input);
+        Entity::<EmptyEntityType, EmptyExternalEvent>::update(
+            emitter,
+            &mut world,
+            &Duration::from_secs_f32(seconds),
+            &"scene".into(),
+        );
+    }
+
+    #[test]
+    fn recycles_particles() {
+        let mut emitter = emitter(4.0, 3);
+        let mut alive = vec![];
+        for _ in 0..5 {
+            update(&mut emitter, 0.25);
+            alive.push(emitter.alive_particles());
+        }
+        //The first particle dies after a second and its slot takes the next one
+        assert_eq!(alive, [1, 2, 3, 3, 3]);
+        assert_eq!(emitter.particles.len(), 3);
+        emitter.set_emitting(false);
+        update(&mut emitter, 1.0);
+        assert_eq!(emitter.alive_particles(), 0);
+    }
+
+    #[test]
+    fn moves_and_renders_particles() {
+        let mut emitter = emitter(1.0, 1);
+        update(&mut emitter, 1.0);
+        update(&mut emitter, 0.5);
+        let particle = emitter.particles[0].as_ref().expect("A particle was spawned");
+        assert_eq!((particle.velocity.x, particle.velocity.y), (1.0, -1.0));
+        assert_eq!((particle.position.x, particle.position.y), (0.5, -0.5));
+
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        Entity::<EmptyEntityType, EmptyExternalEvent>::render(
+            &mut emitter,
+            &mut vertices,
+            &mut indices,
+            vec![],
+        );
+        assert_eq!((vertices.len(), indices.len()), (4, 6));
+    }
+
+    #[test]
+    fn u16_limit() {
+        let mut emitter = emitter(2.0, 2);
+        update(&mut emitter, 1.0);
+        assert_eq!(emitter.alive_particles(), 2);
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        let filler = SimpleVertex::new(Vector::new(0.0, 0.0), Color::new_rgba(0, 0, 0, 0));
+        vertices.extend_from_slice(&vec![filler; MAX_U16_VERTICES - 6]);
+        Entity::<EmptyEntityType, EmptyExternalEvent>::render(
+            &mut emitter,
+            &mut vertices,
+            &mut indices,
+            vec![],
+        );
+        //Only the first quad still fits
+        assert_eq!((vertices.len() as usize, indices.len()), (MAX_U16_VERTICES - 2, 6));
+    }
+}
@@ -8,7 +8,9 @@ use crate::{
     graphics::{RenderSceneDescriptor, RenderSceneName, UniformBufferName},
 };
 
-use super::sprite_sheet::SpriteSheetDimensions;
+#[cfg(feature = "audio")]
+use super::audio::SoundName;
+use super::{scene::SceneName, sprite_sheet::SpriteSheetDimensions};
 
 pub mod exports {
     pub use super::{RessourceDescriptor, RessourceDescriptorBuilder, SpriteSheetName, WindowName};
@@ -24,9 +26,13 @@ impl RessourceDescriptorBuilder {
                 windows: vec![],
                 image_directory: PathBuf::from(""),
                 sprite_sheets: vec![],
+                scene_sprite_sheets: vec![],
+                preload_sprite_sheets: vec![],
                 uniforms: vec![],
                 default_render_scene,
                 render_scenes: vec![],
+                #[cfg(feature = "audio")]
+                sounds: vec![],
             },
         }
     }
@@ -51,6 +57,22 @@ impl RessourceDescriptorBuilder {
         self.ressources.sprite_sheets = sprite_sheets;
         self
     }
+    pub fn with_scene_sprite_sheets(
+        mut self,
+        sprite_sheets: Vec<(SceneName, SpriteSheetName, PathBuf, SpriteSheetDimensions)>,
+    ) -> Self {
+        self.ressources.scene_sprite_sheets = sprite_sheets;
+        self
+    }
+    pub fn with_preload_sprite_sheets(mut self, sprite_sheets: Vec<SpriteSheetName>) -> Self {
+        self.ressources.preload_sprite_sheets = sprite_sheets;
+        self
+    }
+    #[cfg(feature = "audio")]
+    pub fn with_sounds(mut self, sounds: Vec<(SoundName, PathBuf)>) -> Self {
+        self.ressources.sounds = sounds;
+        self
+    }
     pub fn with_uniforms(
         mut self,
         uniforms: Vec<(UniformBufferName, Vec<u8>, wgpu::ShaderStages)>,
@@ -73,8 +95,16 @@ pub struct RessourceDescriptor {
     /// self.image_directory + n + ".png", (1, 1))
     pub image_directory: PathBuf,
     pub sprite_sheets: Vec<(SpriteSheetName, PathBuf, SpriteSheetDimensions)>,
+    ///Sprite sheets visible only to the entities of one scene, eg. the art of a level. They shadow
+    ///the sprite sheets of the same name and are freed when the scene is deleted
+    pub scene_sprite_sheets: Vec<(SceneName, SpriteSheetName, PathBuf, SpriteSheetDimensions)>,
+    ///Loaded before the first scenes are activated, if the Game is created with_preloading
+    pub preload_sprite_sheets: Vec<SpriteSheetName>,
     pub uniforms: Vec<(UniformBufferName, Vec<u8>, wgpu::ShaderStages)>,
     pub default_render_scene: RenderSceneDescriptor,
+    ///Sound files by name. Sounds are read when they are first played
+    #[cfg(feature = "audio")]
+    pub sounds: Vec<(SoundName, PathBuf)>,
     pub render_scenes: Vec<(
         Vec<RenderSceneName>,
         RenderSceneDescriptor,
@@ -112,6 +142,23 @@ impl RessourceDescriptor {
             self.default_render_scene.clone()
         }
     }
+    ///Like get_render_scene, without cloning or logging, eg. for every frame
+    pub(crate) fn render_scene_descriptor(
+        &self,
+        name: &RenderSceneName,
+    ) -> &RenderSceneDescriptor {
+        self.render_scenes
+            .iter()
+            .find(|(render_scenes, _)| render_scenes.contains(name))
+            .map_or(&self.default_render_scene, |(_, descriptor)| descriptor)
+    }
+    #[cfg(feature = "audio")]
+    pub fn get_sound(&self, name: &SoundName) -> Option<&PathBuf> {
+        self.sounds
+            .iter()
+            .find(|(sound_name, _)| sound_name == name)
+            .map(|(_, path)| path)
+    }
     pub fn get_sprite_sheet(&self, name: &SpriteSheetName) -> (PathBuf, SpriteSheetDimensions) {
         self.sprite_sheets
             .iter()
@@ -129,6 +176,24 @@ impl RessourceDescriptor {
                 (path, SpriteSheetDimensions::new(1, 1))
             })
     }
+    pub fn get_scene_sprite_sheet(
+        &self,
+        scene: &SceneName,
+        name: &SpriteSheetName,
+    ) -> Option<(PathBuf, SpriteSheetDimensions)> {
+        self.scene_sprite_sheets
+            .iter()
+            .find(|(s, sprite_sheet_name, _, _)| s == scene && sprite_sheet_name == name)
+            .map(|(_, _, path, dimensions)| (path.clone(), dimensions.clone()))
+    }
+    ///The names scoped to the scene
+    pub fn scene_sprite_sheet_names(&self, scene: &SceneName) -> Vec<&SpriteSheetName> {
+        self.scene_sprite_sheets
+            .iter()
+            .filter(|(s, _, _, _)| s == scene)
+            .map(|(_, name, _, _)| name)
+            .collect()
+    }
 }
 
 create_name_struct!(WindowName);
@@ -1,75 +1,525 @@
 use crate::{
-    app::{IndexBuffer, MouseEvent, VertexBuffer, WindowManager},
+    app::{IndexBuffer, MouseEvent, TextInputEvent, TouchEvent, VertexBuffer, WindowManager},
     create_name_struct,
     graphics::{RenderSceneName, ShaderDescriptor},
+    Position, Size,
 };
-use winit::event::KeyEvent;
+use twod::Vector;
+use std::{iter, time::Duration};
+use winit::{event::KeyEvent, keyboard::ModifiersState, window::WindowId};
 
 use super::{
-    entity::Entity, ressource_descriptor::WindowName, ExternalEvent, GameEvent, SpriteSheet,
-    SpriteSheetName,
+    action_map::ActionEvent, camera::Camera, entity::Entity, input_state::InputState,
+    ressource_descriptor::WindowName, spatial_grid::SpatialGrid, world_view::WorldView,
+    ExternalEvent, GameEvent, SpriteSheet, SpriteSheetScope,
 };
 
 pub mod exports {
-    pub use super::{Scene, SceneName};
+    pub use super::{Scene, SceneName, SceneStatus, WindowTarget};
 }
 
 create_name_struct!(SceneName);
 
+///The window a scene is drawn in
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowTarget {
+    ///A window of the RessourceDescriptor, which is created when the first scene needs it
+    Name(WindowName),
+    ///A window that already exists, eg. one created on demand without a name
+    Id(WindowId),
+}
+impl WindowTarget {
+    ///Names are resolved with the windows created by the game
+    pub fn matches(&self, id: &WindowId, window_ids: &[(WindowName, WindowId)]) -> bool {
+        match self {
+            Self::Name(name) => window_ids.iter().any(|(n, i)| n == name && i == id),
+            Self::Id(target) => target == id,
+        }
+    }
+}
+impl From<WindowName> for WindowTarget {
+    fn from(name: WindowName) -> Self {
+        Self::Name(name)
+    }
+}
+impl From<&str> for WindowTarget {
+    fn from(name: &str) -> Self {
+        Self::Name(name.into())
+    }
+}
+impl From<WindowId> for WindowTarget {
+    fn from(id: WindowId) -> Self {
+        Self::Id(id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneStatus {
+    ///Waiting for its window, render scene or sprite sheets
+    Pending,
+    Active,
+    ///Still drawn, but neither updated nor rendered again
+    Suspended,
+}
+
 #[derive(Debug)]
 pub struct Scene<E: ExternalEvent> {
     pub name: SceneName,
     pub shader_descriptor: ShaderDescriptor,
     pub render_scene: RenderSceneName,
-    pub target_window: WindowName,
+    pub target_window: WindowTarget,
     pub entities: Vec<Box<dyn Entity<E::EntityType, E>>>,
     pub z_index: i32,
+    ///Further render scenes on the same window, drawn above render_scene in this order. Entities
+    ///draw into them with Entity::render_extra, eg. a screen space label of a world object, or
+    ///with Entity::render if Entity::render_scene names them
+    pub extra_render_scenes: Vec<(RenderSceneName, ShaderDescriptor)>,
+    ///A suspended scene receives neither input nor entity events and keeps its entities, unless
+    ///this is set, eg. for a paused level whose entities still react to network events
+    pub receive_while_suspended: bool,
 }
 impl<E: ExternalEvent> Scene<E> {
     pub fn simple_render(
         &mut self,
-        sprite_sheets: &[(SpriteSheetName, SpriteSheet)],
+        sprite_sheets: SpriteSheetScope<'_>,
         window_manager: &mut WindowManager<GameEvent<E>>,
     ) {
-        let mut vertices = VertexBuffer::new();
-        let mut indices = IndexBuffer::new();
+        for (render_scene, vertices, indices) in self.render_updates(sprite_sheets) {
+            window_manager.send_event(GameEvent::RenderUpdate(render_scene, vertices, indices));
+        }
+    }
+
+    ///The buffers of the main and the extra render scenes, eg. to send several scenes in one
+    ///GameEvent::RenderBatch
+    pub fn render_updates(
+        &mut self,
+        sprite_sheets: SpriteSheetScope<'_>,
+    ) -> Vec<(RenderSceneName, VertexBuffer, IndexBuffer)> {
+        let (vertices, indices) = self.build_render_buffers(sprite_sheets);
+        let mut updates = vec![(self.render_scene.clone(), vertices, indices)];
+        updates.append(&mut self.build_extra_render_buffers(sprite_sheets));
+        updates
+    }
+
+    fn sort_entities(&mut self) {
+        self.entities
+            .sort_by(|a, b| a.z().partial_cmp(&b.z()).expect("NaN NaN NaN"));
+    }
+
+    ///Updates every entity, passing all other entities of the scene as its WorldView. With a
+    ///`grid_cell_size` a SpatialGrid is built once for all entities before updating them
+    pub fn update_entities(
+        &mut self,
+        delta_t: &Duration,
+        grid_cell_size: Option<f32>,
+        input: &InputState,
+    ) -> Vec<E> {
+        self.sort_entities();
+        let grid = grid_cell_size
+            .map(|cell_size| SpatialGrid::from_entities(cell_size, &self.entities));
+        let mut events = vec![];
         let entities = &mut self.entities;
-        entities.sort_by(|a, b| a.z().partial_cmp(&b.z()).expect("NaN NaN NaN"));
         for i in 0..entities.len() {
-            let (_, right) = entities.split_at_mut(i);
-            let (entity, _) = right.split_first_mut().expect("i out of bounds");
-            let sprite_sheets = entity
-                .sprite_sheets()
-                .iter()
-                .map(|entity_sprite_sheet| {
-                    sprite_sheets
-                        .iter()
-                        .find(|(l, _)| l == *entity_sprite_sheet)
-                        .map(|(_, s)| s)
-                })
-                .collect();
+            let (left, right) = entities.split_at_mut(i);
+            let (entity, right) = right.split_first_mut().expect("i out of bounds");
+            if !entity.is_enabled() {
+                continue;
+            }
+            let mut world = WorldView::new(left, right, input);
+            if let Some(grid) = &grid {
+                world = world.with_grid(grid);
+            }
+            events.append(&mut entity.update(&mut world, delta_t, &self.name));
+        }
+        events
+    }
+
+    ///If any entity reports changed geometry
+    pub fn is_render_dirty(&self) -> bool {
+        self.entities.iter().any(|e| e.is_render_dirty())
+    }
+
+    ///Renders all entities of the main render scene sorted by their z value
+    pub fn build_render_buffers(
+        &mut self,
+        sprite_sheets: SpriteSheetScope<'_>,
+    ) -> (VertexBuffer, IndexBuffer) {
+        self.sort_entities();
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        let main = &self.render_scene;
+        for entity in self
+            .entities
+            .iter_mut()
+            .filter(|e| e.is_visible() && e.render_scene().map_or(true, |r| r == main))
+        {
+            let sprite_sheets = Self::entity_sprite_sheets(entity.as_ref(), sprite_sheets);
             entity.render(&mut vertices, &mut indices, sprite_sheets);
         }
-        window_manager.send_event(GameEvent::RenderUpdate(
-            self.render_scene.clone(),
-            vertices,
-            indices,
-        ));
+        (vertices, indices)
     }
 
-    pub fn handle_key_input(&mut self, input: &KeyEvent) -> Vec<E> {
-        let mut events = vec![];
+    ///Renders the entities of each extra render scene, then all entities with render_extra
+    pub fn build_extra_render_buffers(
+        &mut self,
+        sprite_sheets: SpriteSheetScope<'_>,
+    ) -> Vec<(RenderSceneName, VertexBuffer, IndexBuffer)> {
+        let mut buffers = Vec::with_capacity(self.extra_render_scenes.len());
+        for (render_scene, _) in self.extra_render_scenes.iter() {
+            let mut vertices = VertexBuffer::new();
+            let mut indices = IndexBuffer::new();
+            for entity in self.entities.iter_mut().filter(|e| e.is_visible()) {
+                if entity.render_scene() == Some(render_scene) {
+                    let sprite_sheets = Self::entity_sprite_sheets(entity.as_ref(), sprite_sheets);
+                    entity.render(&mut vertices, &mut indices, sprite_sheets);
+                }
+                let sprite_sheets = Self::entity_sprite_sheets(entity.as_ref(), sprite_sheets);
+                entity.render_extra(render_scene, &mut vertices, &mut indices, sprite_sheets);
+            }
+            buffers.push((render_scene.clone(), vertices, indices));
+        }
+        buffers
+    }
+
+    fn entity_sprite_sheets<'a>(
+        entity: &dyn Entity<E::EntityType, E>,
+        sprite_sheets: SpriteSheetScope<'a>,
+    ) -> Vec<Option<&'a SpriteSheet>> {
+        entity
+            .sprite_sheets()
+            .into_iter()
+            .map(|entity_sprite_sheet| sprite_sheets.get(entity_sprite_sheet))
+            .collect()
+    }
+
+    ///The main and the extra render scenes
+    pub fn render_scenes(&self) -> impl Iterator<Item = &RenderSceneName> {
+        iter::once(&self.render_scene).chain(self.extra_render_scenes.iter().map(|(r, _)| r))
+    }
+
+    ///The first camera of the scene
+    pub fn camera_mut(&mut self) -> Option<&mut Camera> {
+        self.entities.iter_mut().find_map(|entity| entity.camera_mut())
+    }
+
+    ///Transform a window-centered pixel position with the first camera of the scene
+    pub fn world_position(
+        &self,
+        position: &Position<i32>,
+        window_size: &Size<u32>,
+    ) -> Option<Vector<f32>> {
+        self.entities
+            .iter()
+            .find_map(|entity| entity.camera())
+            .map(|camera| camera.window_to_world(position, window_size))
+    }
+
+    ///The handle_* methods push the events of the entities into `events`, so the caller can reuse
+    ///one buffer
+    pub fn handle_key_input(
+        &mut self,
+        input: &KeyEvent,
+        modifiers: &ModifiersState,
+        events: &mut Vec<E>,
+    ) {
         for entity in self.entities.iter_mut() {
-            events.append(&mut entity.handle_key_input(input));
+            events.append(&mut entity.handle_key_input_with_modifiers(input, modifiers));
         }
-        events
     }
 
-    pub fn handle_mouse_input(&mut self, input: &MouseEvent) -> Vec<E> {
-        let mut events = vec![];
+    pub fn handle_mouse_input(&mut self, input: &MouseEvent, events: &mut Vec<E>) {
         for entity in self.entities.iter_mut() {
             events.append(&mut entity.handle_mouse_input(input));
         }
-        events
+    }
+
+    pub fn handle_action(&mut self, input: &ActionEvent, events: &mut Vec<E>) {
+        for entity in self.entities.iter_mut() {
+            events.append(&mut entity.handle_action(input));
+        }
+    }
+
+    pub fn handle_touch_input(&mut self, input: &TouchEvent, events: &mut Vec<E>) {
+        for entity in self.entities.iter_mut() {
+            events.append(&mut entity.handle_touch_input(input));
+        }
+    }
+
+    pub fn handle_text_input(&mut self, input: &TextInputEvent, events: &mut Vec<E>) {
+        for entity in self.entities.iter_mut() {
+            events.append(&mut entity.handle_text_input(input));
+        }
+    }
+
+    pub fn handle_resize(
+        &mut self,
+        window: &WindowId,
+        name: Option<&WindowName>,
+        size: &Size<f32>,
+        events: &mut Vec<E>,
+    ) {
+        for entity in self.entities.iter_mut() {
+            events.append(&mut entity.handle_resize(window, name, size));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::game_engine::{
+        example::{Color, EmptyEntityType, EmptyExternalEvent, SimpleVertex},
+        BoundingBox, CameraDescriptor, CameraEasing, EntityName, SpriteSheetName, SteppedClock,
+        TimeSource, DEFAULT_CAMERA_DECELERATION_THRESHOLD,
+    };
+
+    ///Moves with a constant velocity in units per second
+    #[derive(Debug)]
+    struct Mover {
+        name: EntityName,
+        position: Vector<f32>,
+        velocity: Vector<f32>,
+        enabled: bool,
+    }
+    impl Entity<EmptyEntityType, EmptyExternalEvent> for Mover {
+        fn update(
+            &mut self,
+            _world: &mut WorldView<EmptyEntityType, EmptyExternalEvent>,
+            delta_t: &Duration,
+            _scene: &SceneName,
+        ) -> Vec<EmptyExternalEvent> {
+            self.position += self.velocity.clone() * delta_t.as_secs_f32();
+            vec![]
+        }
+        fn is_enabled(&self) -> bool {
+            self.enabled
+        }
+        fn render(
+            &mut self,
+            _vertices: &mut VertexBuffer,
+            _indices: &mut IndexBuffer,
+            _sprite_sheet: Vec<Option<&SpriteSheet>>,
+        ) {
+        }
+        fn sprite_sheets(&self) -> Vec<&SpriteSheetName> {
+            vec![]
+        }
+        fn name(&self) -> &EntityName {
+            &self.name
+        }
+        fn bounding_box(&self) -> BoundingBox {
+            BoundingBox {
+                anchor: self.position.clone(),
+                size: Size::new(1.0, 1.0),
+            }
+        }
+        fn entity_type(&self) -> EmptyEntityType {
+            EmptyEntityType::default()
+        }
+    }
+
+    ///Draws a triangle into the render scene it names
+    #[derive(Debug)]
+    struct Marker {
+        name: EntityName,
+        render_scene: Option<RenderSceneName>,
+        visible: bool,
+    }
+    impl Entity<EmptyEntityType, EmptyExternalEvent> for Marker {
+        fn update(
+            &mut self,
+            _world: &mut WorldView<EmptyEntityType, EmptyExternalEvent>,
+            _delta_t: &Duration,
+            _scene: &SceneName,
+        ) -> Vec<EmptyExternalEvent> {
+            vec![]
+        }
+        fn render(
+            &mut self,
+            vertices: &mut VertexBuffer,
+            indices: &mut IndexBuffer,
+            _sprite_sheet: Vec<Option<&SpriteSheet>>,
+        ) {
+            let vertex = SimpleVertex::new(Vector::new(0.0, 0.0), Color::new_rgba(0, 0, 0, 255));
+            let start = vertices.len() as u16;
+            vertices.extend_from_slice(&[vertex; 3]);
+            indices.extend_from_slice(&[start, start + 1, start + 2]);
+        }
+        fn render_scene(&self) -> Option<&RenderSceneName> {
+            self.render_scene.as_ref()
+        }
+        fn is_visible(&self) -> bool {
+            self.visible
+        }
+        fn sprite_sheets(&self) -> Vec<&SpriteSheetName> {
+            vec![]
+        }
+        fn name(&self) -> &EntityName {
+            &self.name
+        }
+        fn bounding_box(&self) -> BoundingBox {
+            BoundingBox {
+                anchor: Vector::new(0.0, 0.0),
+                size: Size::new(1.0, 1.0),
+            }
+        }
+        fn entity_type(&self) -> EmptyEntityType {
+            EmptyEntityType::default()
+        }
+    }
+
+    fn scene(
+        entities: Vec<Box<dyn Entity<EmptyEntityType, EmptyExternalEvent>>>,
+    ) -> Scene<EmptyExternalEvent> {
+        Scene {
+            name: "scene".into(),
+            shader_descriptor: ShaderDescriptor {
+                file: "shader.wgsl",
+                vertex_shader: "vs_main",
+                fragment_shader: "fs_main",
+                uniforms: &[],
+            },
+            render_scene: "scene".into(),
+            target_window: "window".into(),
+            entities,
+            z_index: 0,
+            extra_render_scenes: vec![],
+            receive_while_suspended: false,
+        }
+    }
+
+    #[test]
+    fn steer_camera() {
+        let camera = Camera::from(&CameraDescriptor {
+            name: "camera".into(),
+            view_size: Size::new(4.0, 2.0),
+            speed: 1.0,
+            acceleration_steps: 1,
+            target_entity: "missing".into(),
+            bound_entity: None,
+            bound_rect: None,
+            max_offset_position: 1.0,
+            deceleration_threshold: DEFAULT_CAMERA_DECELERATION_THRESHOLD,
+            easing: CameraEasing::Linear,
+        });
+        let mut scene = scene(vec![Box::new(camera)]);
+        scene
+            .camera_mut()
+            .expect("The scene should have a camera")
+            .set_target_position(Some(Vector::new(3.0, 4.0)));
+        scene.update_entities(&Duration::from_millis(16), None, &InputState::new());
+        let view = scene.entities[0].bounding_box();
+        assert_eq!((view.anchor.x, view.anchor.y), (3.0, 4.0));
+        assert_eq!(
+            scene
+                .world_position(&Position::new(0, 0), &Size::new(100, 100))
+                .map(|p| (p.x, p.y)),
+            Some((3.0, 4.0))
+        );
+    }
+
+    #[test]
+    fn stepped_updates() {
+        let mover = |name: &str, velocity| Mover {
+            name: name.into(),
+            position: Vector::new(0.0, 0.0),
+            velocity,
+            enabled: true,
+        };
+        let mut scene = scene(vec![
+            Box::new(mover("right", Vector::new(2.0, 0.0))),
+            Box::new(mover("down", Vector::new(0.0, -1.0))),
+        ]);
+        let mut clock = SteppedClock::new([16, 33, 1000].map(Duration::from_millis));
+        let input = InputState::new();
+        while let Some(delta_t) = clock.next_frame() {
+            scene.update_entities(&delta_t, None, &input);
+        }
+        let positions = scene
+            .entities
+            .iter()
+            .map(|entity| entity.bounding_box().anchor)
+            .collect::<Vec<_>>();
+        for (position, (x, y)) in positions.iter().zip([(2.098, 0.0), (0.0, -1.049)]) {
+            assert!((position.x - x).abs() < 1e-5, "{} != {}", position.x, x);
+            assert!((position.y - y).abs() < 1e-5, "{} != {}", position.y, y);
+        }
+    }
+
+    #[test]
+    fn render_scene_routing() {
+        let marker = |name: &str, render_scene: Option<&str>| Marker {
+            name: name.into(),
+            render_scene: render_scene.map(RenderSceneName::from),
+            visible: true,
+        };
+        let mut scene = scene(vec![
+            Box::new(marker("world", None)),
+            Box::new(marker("named world", Some("scene"))),
+            Box::new(marker("hud", Some("hud"))),
+            Box::new(marker("lost", Some("missing"))),
+        ]);
+        let shader_descriptor = scene.shader_descriptor.clone();
+        scene.extra_render_scenes = vec![("hud".into(), shader_descriptor)];
+        let sprite_sheets = HashMap::new();
+        let updates = scene.render_updates(SpriteSheetScope::global(&sprite_sheets));
+        let counts = updates
+            .iter()
+            .map(|(render_scene, vertices, indices)| {
+                (render_scene.clone(), vertices.len(), indices.len())
+            })
+            .collect::<Vec<_>>();
+        //The entity naming a render scene the scene does not have is not drawn
+        assert_eq!(
+            counts,
+            [(RenderSceneName::from("scene"), 6, 6), (RenderSceneName::from("hud"), 3, 3)]
+        );
+    }
+
+    #[test]
+    fn disabled_entities_are_not_updated() {
+        let mover = |name: &str, enabled| Mover {
+            name: name.into(),
+            position: Vector::new(0.0, 0.0),
+            velocity: Vector::new(1.0, 0.0),
+            enabled,
+        };
+        let mut scene = scene(vec![Box::new(mover("on", true)), Box::new(mover("off", false))]);
+        scene.update_entities(&Duration::from_secs(1), None, &InputState::new());
+        let positions = scene
+            .entities
+            .iter()
+            .map(|entity| (entity.name().clone(), entity.bounding_box().anchor.x))
+            .collect::<Vec<_>>();
+        assert!(positions.contains(&("on".into(), 1.0)));
+        //Kept in the scene with its state
+        assert!(positions.contains(&("off".into(), 0.0)));
+    }
+
+    #[test]
+    fn hidden_entities_are_not_rendered() {
+        let marker = |name: &str, render_scene: Option<&str>, visible| Marker {
+            name: name.into(),
+            render_scene: render_scene.map(RenderSceneName::from),
+            visible,
+        };
+        let mut scene = scene(vec![
+            Box::new(marker("shown", None, true)),
+            Box::new(marker("hidden", None, false)),
+            Box::new(marker("hidden hud", Some("hud"), false)),
+        ]);
+        let shader_descriptor = scene.shader_descriptor.clone();
+        scene.extra_render_scenes = vec![("hud".into(), shader_descriptor)];
+        let sprite_sheets = HashMap::new();
+        let updates = scene.render_updates(SpriteSheetScope::global(&sprite_sheets));
+        let counts = updates
+            .iter()
+            .map(|(render_scene, vertices, _)| (render_scene.clone(), vertices.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [(RenderSceneName::from("scene"), 3), (RenderSceneName::from("hud"), 0)]
+        );
     }
 }
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use super::{entity::EntityType, BoundingBox, Entity, ExternalEvent};
+
+pub mod exports {
+    pub use super::SpatialGrid;
+}
+
+///Boxes overlapping more cells are not put into cells, but returned by every query
+const MAX_CELLS_PER_BOX: i64 = 1024;
+
+///Uniform grid of square cells holding the indices of the entities whose bounding box overlaps
+///each cell
+#[derive(Debug)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    ///Indices of the boxes spanning more than MAX_CELLS_PER_BOX cells
+    oversized: Vec<usize>,
+}
+impl SpatialGrid {
+    ///Panics if the cell size is not positive and finite
+    pub fn new(cell_size: f32) -> Self {
+        assert!(
+            cell_size.is_finite() && cell_size > 0.0,
+            "The cell size of a SpatialGrid has to be positive and finite, not {}",
+            cell_size
+        );
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            oversized: Vec::new(),
+        }
+    }
+
+    ///Cameras are not inserted, so they never show up as neighbors
+    pub fn from_entities<T: EntityType, E: ExternalEvent>(
+        cell_size: f32,
+        entities: &[Box<dyn Entity<T, E>>],
+    ) -> Self {
+        let mut grid = Self::new(cell_size);
+        for (index, entity) in entities
+            .iter()
+            .enumerate()
+            .filter(|(_, entity)| entity.camera().is_none())
+        {
+            grid.insert(index, &entity.bounding_box());
+        }
+        grid
+    }
+
+    fn cell_range(&self, area: &BoundingBox) -> ((i32, i32), (i32, i32)) {
+        let half_width = area.size.width() / 2.0;
+        let half_height = area.size.height() / 2.0;
+        let cell = |x: f32, y: f32| {
+            (
+                (x / self.cell_size).floor() as i32,
+                (y / self.cell_size).floor() as i32,
+            )
+        };
+        (
+            cell(area.anchor.x - half_width, area.anchor.y - half_height),
+            cell(area.anchor.x + half_width, area.anchor.y + half_height),
+        )
+    }
+
+    fn cell_count(((min_x, min_y), (max_x, max_y)): ((i32, i32), (i32, i32))) -> i64 {
+        (max_x as i64 - min_x as i64 + 1).max(0) * (max_y as i64 - min_y as i64 + 1).max(0)
+    }
+
+    pub fn insert(&mut self, index: usize, bounding_box: &BoundingBox) {
+        let range = self.cell_range(bounding_box);
+        if Self::cell_count(range) > MAX_CELLS_PER_BOX {
+            self.oversized.push(index);
+            return;
+        }
+        let ((min_x, min_y), (max_x, max_y)) = range;
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    ///Sorted indices of all entities sharing a cell with the area. These are candidates, their
+    ///bounding boxes do not necessarily intersect the area
+    pub fn query(&self, area: &BoundingBox) -> Vec<usize> {
+        let range = self.cell_range(area);
+        let ((min_x, min_y), (max_x, max_y)) = range;
+        let mut indices = self.oversized.clone();
+        //A huge area is checked against the occupied cells instead of visiting all of its cells
+        if Self::cell_count(range) > self.cells.len() as i64 {
+            for (_, cell) in self.cells.iter().filter(|((x, y), _)| {
+                (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y)
+            }) {
+                indices.extend_from_slice(cell);
+            }
+        } else {
+            for x in min_x..=max_x {
+                for y in min_y..=max_y {
+                    if let Some(cell) = self.cells.get(&(x, y)) {
+                        indices.extend_from_slice(cell);
+                    }
+                }
+            }
+        }
+        indices.sort();
+        indices.dedup();
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Size;
+    use twod::Vector;
+
+    #[test]
+    fn query() {
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert(0, &BoundingBox {
+            anchor: Vector::new(5.0, 5.0),
+            size: Size::new(2.0, 2.0),
+        });
+        grid.insert(1, &BoundingBox {
+            anchor: Vector::new(55.0, 5.0),
+            size: Size::new(2.0, 2.0),
+        });
+        grid.insert(2, &BoundingBox {
+            anchor: Vector::new(10.0, 5.0),
+            size: Size::new(30.0, 2.0),
+        });
+        let area = BoundingBox {
+            anchor: Vector::new(3.0, 3.0),
+            size: Size::new(4.0, 4.0),
+        };
+        assert_eq!(grid.query(&area), vec![0, 2]);
+    }
+
+    #[test]
+    fn huge_boxes() {
+        let mut grid = SpatialGrid::new(1.0);
+        grid.insert(0, &BoundingBox {
+            anchor: Vector::new(5.0, 5.0),
+            size: Size::new(2.0, 2.0),
+        });
+        grid.insert(1, &BoundingBox {
+            anchor: Vector::new(0.0, 0.0),
+            size: Size::new(1e30, 1e30),
+        });
+        let small = BoundingBox {
+            anchor: Vector::new(-100.0, -100.0),
+            size: Size::new(1.0, 1.0),
+        };
+        assert_eq!(grid.query(&small), vec![1]);
+        let huge = BoundingBox {
+            anchor: Vector::new(0.0, 0.0),
+            size: Size::new(f32::MAX, f32::MAX),
+        };
+        assert_eq!(grid.query(&huge), vec![0, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_cell_size() {
+        SpatialGrid::new(0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nan_cell_size() {
+        SpatialGrid::new(f32::NAN);
+    }
+}
@@ -1,5 +1,13 @@
+use std::collections::HashMap;
+
+use crate::graphics::PLACEHOLDER_TEXTURE_INDEX;
+
+use super::ressource_descriptor::SpriteSheetName;
+
 pub mod exports {
-    pub use super::{SpritePosition, SpriteSheet, SpriteSheetDimensions, TextureCoordinates};
+    pub use super::{
+        SpritePosition, SpriteSheet, SpriteSheetDimensions, SpriteSheetScope, TextureCoordinates,
+    };
 }
 
 #[derive(Debug)]
@@ -8,6 +16,7 @@ pub struct TextureCoordinates {
     pub v: f32,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct SpritePosition {
     pub x: u8,
     pub y: u8,
@@ -29,22 +38,35 @@ impl SpriteSheetDimensions {
     }
 }
 
+static PLACEHOLDER: SpriteSheet = SpriteSheet {
+    texture: PLACEHOLDER_TEXTURE_INDEX,
+    sprites_per_row: 1,
+    sprites_per_column: 1,
+};
+
 #[derive(Debug)]
 pub struct SpriteSheet {
     texture: u32,
     pub sprites_per_row: u8,
     pub sprites_per_column: u8,
 }
+///The magenta placeholder texture, every sprite of it is the same
 impl Default for SpriteSheet {
     fn default() -> Self {
         Self {
-            texture: 0,
-            sprites_per_row: 1,
-            sprites_per_column: 1,
+            texture: PLACEHOLDER.texture,
+            sprites_per_row: PLACEHOLDER.sprites_per_row,
+            sprites_per_column: PLACEHOLDER.sprites_per_column,
         }
     }
 }
 impl SpriteSheet {
+    ///Substitutes the default placeholder for a sprite sheet that is not loaded, so the entity is
+    ///drawn in magenta instead of missing
+    pub fn or_placeholder(sprite_sheet: Option<&SpriteSheet>) -> &SpriteSheet {
+        sprite_sheet.unwrap_or(&PLACEHOLDER)
+    }
+
     pub fn texture(&self) -> u32 {
         self.texture
     }
@@ -55,6 +77,11 @@ impl SpriteSheet {
             sprites_per_column: dimensions.columns,
         }
     }
+    ///The same texture divided into another grid, eg. for a repacked animation. The texture is not
+    ///loaded again
+    pub fn with_dimensions(&self, dimensions: &SpriteSheetDimensions) -> SpriteSheet {
+        Self::new(self.texture, dimensions)
+    }
     pub fn get_sprite_coordinates(&self, position: &SpritePosition) -> [TextureCoordinates; 4] {
         let width = 1.0 / self.sprites_per_row as f32;
         let height = 1.0 / self.sprites_per_column as f32;
@@ -80,3 +107,81 @@ impl SpriteSheet {
         ]
     }
 }
+
+///The sprite sheets visible to the entities of one scene. Sprite sheets scoped to the scene
+///shadow the global ones of the same name, even before they are loaded
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteSheetScope<'a> {
+    sprite_sheets: &'a HashMap<SpriteSheetName, SpriteSheet>,
+    ///The names scoped to the scene with the labels they are loaded under
+    scoped: &'a [(SpriteSheetName, SpriteSheetName)],
+}
+impl<'a> SpriteSheetScope<'a> {
+    ///Only the global sprite sheets
+    pub fn global(sprite_sheets: &'a HashMap<SpriteSheetName, SpriteSheet>) -> Self {
+        Self {
+            sprite_sheets,
+            scoped: &[],
+        }
+    }
+    pub fn new(
+        sprite_sheets: &'a HashMap<SpriteSheetName, SpriteSheet>,
+        scoped: &'a [(SpriteSheetName, SpriteSheetName)],
+    ) -> Self {
+        Self {
+            sprite_sheets,
+            scoped,
+        }
+    }
+    pub fn get(&self, name: &SpriteSheetName) -> Option<&'a SpriteSheet> {
+        match self.scoped.iter().find(|(scoped, _)| scoped == name) {
+            Some((_, label)) => self.sprite_sheets.get(label),
+            None => self.sprite_sheets.get(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholder() {
+        let sprite_sheet = SpriteSheet::new(3, &SpriteSheetDimensions::new(2, 2));
+        assert_eq!(SpriteSheet::or_placeholder(Some(&sprite_sheet)).texture(), 3);
+        assert_eq!(
+            SpriteSheet::or_placeholder(None).texture(),
+            SpriteSheet::default().texture()
+        );
+        assert_eq!(SpriteSheet::default().texture(), PLACEHOLDER_TEXTURE_INDEX);
+    }
+
+    #[test]
+    fn with_dimensions() {
+        let sprite_sheet = SpriteSheet::new(3, &SpriteSheetDimensions::new(2, 2));
+        let repacked = sprite_sheet.with_dimensions(&SpriteSheetDimensions::new(4, 1));
+        assert_eq!(repacked.texture(), 3);
+        assert_eq!((repacked.sprites_per_row, repacked.sprites_per_column), (4, 1));
+    }
+
+    #[test]
+    fn scope() {
+        let dimensions = SpriteSheetDimensions::new(1, 1);
+        let sprite_sheets = HashMap::from([
+            (SpriteSheetName::from("tiles"), SpriteSheet::new(2, &dimensions)),
+            (SpriteSheetName::from("level tiles"), SpriteSheet::new(3, &dimensions)),
+            (SpriteSheetName::from("player"), SpriteSheet::new(4, &dimensions)),
+        ]);
+        let scoped = [
+            ("tiles".into(), "level tiles".into()),
+            ("enemy".into(), "level enemy".into()),
+        ];
+        let scope = SpriteSheetScope::new(&sprite_sheets, &scoped);
+        assert_eq!(scope.get(&"tiles".into()).map(|s| s.texture()), Some(3));
+        assert_eq!(scope.get(&"player".into()).map(|s| s.texture()), Some(4));
+        //Not loaded yet, but still not the global one
+        assert!(scope.get(&"enemy".into()).is_none());
+        let global = SpriteSheetScope::global(&sprite_sheets);
+        assert_eq!(global.get(&"tiles".into()).map(|s| s.texture()), Some(2));
+    }
+}
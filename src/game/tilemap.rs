@@ -0,0 +1,351 @@
+use std::{fmt::Debug, time::Duration};
+
+use log::error;
+use twod::Vector;
+
+use crate::{
+    app::{fits_u16_indices, IndexBuffer, VertexBuffer, MAX_U16_VERTICES},
+    game_engine::{BoundingBox, WorldView},
+    graphics::Vertex,
+    Size,
+};
+
+use super::{
+    entity::{EntityName, EntityType},
+    sprite_sheet::{SpritePosition, TextureCoordinates},
+    Entity, ExternalEvent, SpriteSheet, SpriteSheetName,
+};
+
+pub mod exports {
+    pub use super::Tilemap;
+}
+
+///First and last visible column and row, inclusive
+type TileRange = (usize, usize, usize, usize);
+
+///Grid of sprites from a single SpriteSheet. Only the tiles inside the view of the scene's camera
+///are rendered, and the vertices are only rebuilt when tiles or the visible range change.
+///The indices are u16, so at most 16384 tiles are drawn at once, further ones are dropped with an
+///error. Split bigger views into several tilemaps on their own render scenes
+pub struct Tilemap<V: Vertex> {
+    name: EntityName,
+    sprite_sheet: SpriteSheetName,
+    ///Middle point of the map
+    anchor: Vector<f32>,
+    tile_size: f32,
+    columns: usize,
+    rows: usize,
+    ///Row major, row 0 at the top
+    tiles: Vec<Option<SpritePosition>>,
+    ///None if no tile is in view
+    visible: Option<TileRange>,
+    cache: Option<(TileRange, u32, Vec<V>, Vec<u16>)>,
+    create_vertex: fn(Vector<f32>, &TextureCoordinates, u32) -> V,
+}
+impl<V: Vertex> Tilemap<V> {
+    ///`create_vertex` builds a vertex of the render scene from a position, texture coordinates
+    ///and the texture index of the SpriteSheet
+    pub fn new(
+        name: EntityName,
+        sprite_sheet: SpriteSheetName,
+        anchor: Vector<f32>,
+        tile_size: f32,
+        columns: usize,
+        rows: usize,
+        create_vertex: fn(Vector<f32>, &TextureCoordinates, u32) -> V,
+    ) -> Self {
+        Self {
+            name,
+            sprite_sheet,
+            anchor,
+            tile_size,
+            columns,
+            rows,
+            tiles: (0..columns * rows).map(|_| None).collect(),
+            //Until the first update every tile is rendered
+            visible: Some((0, columns.saturating_sub(1), 0, rows.saturating_sub(1))),
+            cache: None,
+            create_vertex,
+        }
+    }
+
+    pub fn get_tile(&self, column: usize, row: usize) -> Option<&SpritePosition> {
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+        self.tiles[row * self.columns + column].as_ref()
+    }
+
+    ///Tiles outside of the map are ignored
+    pub fn set_tile(&mut self, column: usize, row: usize, tile: Option<SpritePosition>) {
+        if column >= self.columns || row >= self.rows {
+            return;
+        }
+        self.tiles[row * self.columns + column] = tile;
+        self.cache = None;
+    }
+
+    fn size(&self) -> Size<f32> {
+        Size::new(
+            self.columns as f32 * self.tile_size,
+            self.rows as f32 * self.tile_size,
+        )
+    }
+
+    fn all_tiles(&self) -> TileRange {
+        (
+            0,
+            self.columns.saturating_sub(1),
+            0,
+            self.rows.saturating_sub(1),
+        )
+    }
+
+    ///Tiles overlapping the view, None if none do
+    fn visible_range(&self, view: &BoundingBox) -> Option<TileRange> {
+        let size = self.size();
+        let left = self.anchor.x - size.width() / 2.0;
+        let top = self.anchor.y + size.height() / 2.0;
+        let view_left = view.anchor.x - view.size.width() / 2.0 - left;
+        let view_right = view.anchor.x + view.size.width() / 2.0 - left;
+        let view_top = top - (view.anchor.y + view.size.height() / 2.0);
+        let view_bottom = top - (view.anchor.y - view.size.height() / 2.0);
+        if view_right < 0.0
+            || view_bottom < 0.0
+            || view_left > size.width()
+            || view_top > size.height()
+            || self.columns == 0
+            || self.rows == 0
+        {
+            return None;
+        }
+        let to_tile = |distance: f32, tiles: usize| {
+            ((distance / self.tile_size).floor().max(0.0) as usize).min(tiles - 1)
+        };
+        Some((
+            to_tile(view_left, self.columns),
+            to_tile(view_right, self.columns),
+            to_tile(view_top, self.rows),
+            to_tile(view_bottom, self.rows),
+        ))
+    }
+
+    fn build(&self, range: TileRange, sprite_sheet: &SpriteSheet) -> (Vec<V>, Vec<u16>) {
+        let (first_column, last_column, first_row, last_row) = range;
+        let size = self.size();
+        let left = self.anchor.x - size.width() / 2.0;
+        let top = self.anchor.y + size.height() / 2.0;
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for row in first_row..=last_row {
+            for column in first_column..=last_column {
+                let tile = match &self.tiles[row * self.columns + column] {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let x = left + column as f32 * self.tile_size;
+                let y = top - row as f32 * self.tile_size;
+                let corners = [
+                    Vector::new(x, y),
+                    Vector::new(x + self.tile_size, y),
+                    Vector::new(x + self.tile_size, y - self.tile_size),
+                    Vector::new(x, y - self.tile_size),
+                ];
+                if vertices.len() + 4 > MAX_U16_VERTICES {
+                    error!(
+                        "Tilemap {:?} shows more tiles than u16 indices address, dropping the rest",
+                        self.name
+                    );
+                    return (vertices, indices);
+                }
+                let start = vertices.len() as u16;
+                for (corner, uv) in corners
+                    .into_iter()
+                    .zip(sprite_sheet.get_sprite_coordinates(tile).iter())
+                {
+                    vertices.push((self.create_vertex)(corner, uv, sprite_sheet.texture()));
+                }
+                indices.extend_from_slice(&[
+                    start,
+                    start + 1,
+                    start + 2,
+                    start,
+                    start + 2,
+                    start + 3,
+                ]);
+            }
+        }
+        (vertices, indices)
+    }
+}
+impl<V: Vertex> Debug for Tilemap<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tilemap")
+            .field("name", &self.name)
+            .field("sprite_sheet", &self.sprite_sheet)
+            .field("columns", &self.columns)
+            .field("rows", &self.rows)
+            .field("visible", &self.visible)
+            .finish()
+    }
+}
+
+impl<T: EntityType, E: ExternalEvent, V: Vertex + Send> Entity<T, E> for Tilemap<V> {
+    fn update(
+        &mut self,
+        world: &mut WorldView<T, E>,
+        _delta_t: &Duration,
+        _scene: &super::SceneName,
+    ) -> Vec<E> {
+        self.visible = match world.camera() {
+            Some(camera) => self.visible_range(&Entity::<T, E>::bounding_box(camera)),
+            None => Some(self.all_tiles()),
+        };
+        vec![]
+    }
+    fn render(
+        &mut self,
+        vertices: &mut VertexBuffer,
+        indices: &mut IndexBuffer,
+        sprite_sheet: Vec<Option<&SpriteSheet>>,
+    ) {
+        if self.tiles.is_empty() {
+            return;
+        }
+        let sprite_sheet = match sprite_sheet.first() {
+            Some(Some(sprite_sheet)) => *sprite_sheet,
+            _ => return,
+        };
+        let range = match self.visible {
+            Some(range) => range,
+            None => return,
+        };
+        let cache_valid = matches!(
+            &self.cache,
+            Some((r, texture, _, _)) if *r == range && *texture == sprite_sheet.texture()
+        );
+        if !cache_valid {
+            let (tile_vertices, tile_indices) = self.build(range, sprite_sheet);
+            self.cache = Some((range, sprite_sheet.texture(), tile_vertices, tile_indices));
+        }
+        if let Some((_, _, tile_vertices, tile_indices)) = &self.cache {
+            if !fits_u16_indices(vertices, tile_vertices.len()) {
+                error!(
+                    "Tilemap {:?} does not fit into the u16 indices of its render scene",
+                    self.name
+                );
+                return;
+            }
+            let start = vertices.len() as u16;
+            vertices.extend_from_slice(tile_vertices);
+            indices.extend_from_slice(
+                &tile_indices.iter().map(|i| i + start).collect::<Vec<_>>(),
+            );
+        }
+    }
+    fn sprite_sheets(&self) -> Vec<&SpriteSheetName> {
+        vec![&self.sprite_sheet]
+    }
+    fn name(&self) -> &EntityName {
+        &self.name
+    }
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox {
+            anchor: self.anchor.clone(),
+            size: self.size(),
+        }
+    }
+    fn entity_type(&self) -> T {
+        T::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::{
+        example::{EmptyEntityType, EmptyExternalEvent, TexturedVertex},
+        Camera, CameraDescriptor, CameraEasing, InputState, SpriteSheetDimensions,
+        DEFAULT_CAMERA_DECELERATION_THRESHOLD,
+    };
+
+    fn tilemap(columns: usize, rows: usize) -> Tilemap<TexturedVertex> {
+        Tilemap::new(
+            "map".into(),
+            "tiles".into(),
+            Vector::new(0.0, 0.0),
+            1.0,
+            columns,
+            rows,
+            TexturedVertex::new,
+        )
+    }
+
+    fn view(x: f32, y: f32, width: f32, height: f32) -> BoundingBox {
+        BoundingBox {
+            anchor: Vector::new(x, y),
+            size: Size::new(width, height),
+        }
+    }
+
+    #[test]
+    fn visible_range() {
+        //Spans -5 to 5 on both axes, row 0 at the top
+        let map = tilemap(10, 10);
+        assert_eq!(map.visible_range(&view(0.0, 0.0, 2.0, 2.0)), Some((4, 6, 4, 6)));
+        assert_eq!(map.visible_range(&view(-4.5, 4.5, 0.5, 0.5)), Some((0, 0, 0, 0)));
+        assert_eq!(map.visible_range(&view(0.0, 0.0, 100.0, 100.0)), Some(map.all_tiles()));
+        assert_eq!(map.visible_range(&view(100.0, 0.0, 2.0, 2.0)), None);
+        assert_eq!(map.visible_range(&view(0.0, -100.0, 2.0, 2.0)), None);
+        assert_eq!(tilemap(0, 0).visible_range(&view(0.0, 0.0, 2.0, 2.0)), None);
+    }
+
+    #[test]
+    fn culls_to_the_camera() {
+        let mut map = tilemap(10, 10);
+        let camera: Box<dyn Entity<EmptyEntityType, EmptyExternalEvent>> =
+            Box::new(Camera::from(&CameraDescriptor {
+                name: "camera".into(),
+                view_size: Size::new(2.0, 2.0),
+                speed: 1.0,
+                acceleration_steps: 1,
+                target_entity: "map".into(),
+                bound_entity: None,
+                bound_rect: None,
+                max_offset_position: 1.0,
+                deceleration_threshold: DEFAULT_CAMERA_DECELERATION_THRESHOLD,
+                easing: CameraEasing::Linear,
+            }));
+        let input = InputState::new();
+        let mut left = [camera];
+        let mut world = WorldView::new(&mut left, &mut [], &input);
+        Entity::<EmptyEntityType, EmptyExternalEvent>::update(
+            &mut map,
+            &mut world,
+            &Duration::from_millis(16),
+            &"scene".into(),
+        );
+        assert_eq!(map.visible, Some((4, 6, 4, 6)));
+    }
+
+    #[test]
+    fn u16_limit() {
+        let mut map = tilemap(200, 100);
+        for row in 0..100 {
+            for column in 0..200 {
+                map.set_tile(column, row, Some(SpritePosition::new(0, 0)));
+            }
+        }
+        let sprite_sheet = SpriteSheet::new(2, &SpriteSheetDimensions::new(1, 1));
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        Entity::<EmptyEntityType, EmptyExternalEvent>::render(
+            &mut map,
+            &mut vertices,
+            &mut indices,
+            vec![Some(&sprite_sheet)],
+        );
+        assert_eq!(vertices.len() as usize, MAX_U16_VERTICES);
+        assert_eq!(indices.len() as usize, MAX_U16_VERTICES / 4 * 6);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+pub mod exports {
+    pub use super::{FramePoll, RealClock, SteppedClock, TimeSource};
+}
+
+///Drives the timer of the Game. It runs on its own thread, or in the event loop with
+///`Game::with_event_loop_timer`
+pub trait TimeSource: Send {
+    ///Blocks until the next frame is due and returns the time passed since the last one. The
+    ///timer stops once None is returned
+    fn next_frame(&mut self) -> Option<Duration>;
+
+    ///Polled by the event loop instead of next_frame, so it does not block. The default calls
+    ///next_frame, which blocks the event loop while it waits
+    fn poll_frame(&mut self, _now: Instant) -> FramePoll {
+        match self.next_frame() {
+            Some(delta_t) => FramePoll::Due(delta_t),
+            None => FramePoll::Stopped,
+        }
+    }
+}
+
+///Answer of `TimeSource::poll_frame`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePoll {
+    ///The next frame is due, with the time passed since the last one
+    Due(Duration),
+    ///Poll again at this instant
+    Wait(Instant),
+    Stopped,
+}
+
+///Measures the wall clock time between frames, which are spaced by the target fps
+#[derive(Debug)]
+pub struct RealClock {
+    frame_duration: Duration,
+    last_update: Option<Instant>,
+    ///Started by the first poll_frame
+    event_loop_timer: Option<EventLoopTimer>,
+}
+impl RealClock {
+    pub fn new(target_fps: u8) -> Self {
+        Self {
+            frame_duration: frame_duration(target_fps),
+            last_update: None,
+            event_loop_timer: None,
+        }
+    }
+}
+
+fn frame_duration(target_fps: u8) -> Duration {
+    let ns_per_frame = 1e9 / (target_fps as f64);
+    Duration::from_nanos(ns_per_frame as u64)
+}
+impl TimeSource for RealClock {
+    fn next_frame(&mut self) -> Option<Duration> {
+        let delta_t = match self.last_update {
+            Some(last_update) => {
+                thread::sleep(self.frame_duration);
+                last_update.elapsed()
+            }
+            None => Duration::ZERO,
+        };
+        self.last_update = Some(Instant::now());
+        Some(delta_t)
+    }
+
+    fn poll_frame(&mut self, now: Instant) -> FramePoll {
+        let frame_duration = self.frame_duration;
+        let timer = self
+            .event_loop_timer
+            .get_or_insert_with(|| EventLoopTimer::new(frame_duration, now));
+        match timer.poll(now) {
+            Some(delta_t) => FramePoll::Due(delta_t),
+            None => FramePoll::Wait(timer.next_frame()),
+        }
+    }
+}
+
+///Hands out a fixed sequence of deltas, eg. to replay a recording or for deterministic tests
+#[derive(Debug)]
+pub struct SteppedClock {
+    deltas: VecDeque<Duration>,
+    pacing: Option<Duration>,
+    ///When poll_frame hands out the next delta, with_pacing
+    next_poll: Option<Instant>,
+}
+impl SteppedClock {
+    pub fn new(deltas: impl IntoIterator<Item = Duration>) -> Self {
+        Self {
+            deltas: deltas.into_iter().collect(),
+            pacing: None,
+            next_poll: None,
+        }
+    }
+
+    ///Wait this long between frames instead of sending them as fast as possible
+    pub fn with_pacing(mut self, pacing: Duration) -> Self {
+        self.pacing = Some(pacing);
+        self
+    }
+}
+impl TimeSource for SteppedClock {
+    fn next_frame(&mut self) -> Option<Duration> {
+        let delta_t = self.deltas.pop_front()?;
+        if let Some(pacing) = self.pacing {
+            thread::sleep(pacing);
+        }
+        Some(delta_t)
+    }
+
+    fn poll_frame(&mut self, now: Instant) -> FramePoll {
+        match self.next_poll {
+            Some(next_poll) if now < next_poll => return FramePoll::Wait(next_poll),
+            _ => {}
+        }
+        let delta_t = match self.deltas.pop_front() {
+            Some(delta_t) => delta_t,
+            None => return FramePoll::Stopped,
+        };
+        self.next_poll = self.pacing.map(|pacing| now + pacing);
+        FramePoll::Due(delta_t)
+    }
+}
+
+///Schedules the frames of a RealClock polled from the event loop, which wakes up at next_frame
+#[derive(Debug)]
+struct EventLoopTimer {
+    frame_duration: Duration,
+    last_update: Option<Instant>,
+    next_frame: Instant,
+}
+impl EventLoopTimer {
+    fn new(frame_duration: Duration, now: Instant) -> Self {
+        Self {
+            frame_duration,
+            last_update: None,
+            next_frame: now,
+        }
+    }
+
+    fn next_frame(&self) -> Instant {
+        self.next_frame
+    }
+
+    ///The time passed since the last frame, if the next one is due. Frames missed while the loop
+    ///was busy are skipped, not sent in a burst
+    fn poll(&mut self, now: Instant) -> Option<Duration> {
+        if now < self.next_frame {
+            return None;
+        }
+        let delta_t = self
+            .last_update
+            .map_or(Duration::ZERO, |last_update| now - last_update);
+        self.last_update = Some(now);
+        self.next_frame += self.frame_duration;
+        if self.next_frame <= now {
+            self.next_frame = now + self.frame_duration;
+        }
+        Some(delta_t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stepped_clock() {
+        let deltas = [Duration::from_millis(16), Duration::from_millis(33)];
+        let mut clock = SteppedClock::new(deltas);
+        assert_eq!(clock.next_frame(), Some(deltas[0]));
+        assert_eq!(clock.next_frame(), Some(deltas[1]));
+        assert_eq!(clock.next_frame(), None);
+    }
+
+    #[test]
+    fn event_loop_timer() {
+        let start = Instant::now();
+        let mut timer = EventLoopTimer::new(frame_duration(10), start);
+        assert_eq!(timer.poll(start), Some(Duration::ZERO));
+        assert_eq!(timer.next_frame(), start + Duration::from_millis(100));
+        assert_eq!(timer.poll(start + Duration::from_millis(50)), None);
+        let late = start + Duration::from_millis(350);
+        assert_eq!(timer.poll(late), Some(Duration::from_millis(350)));
+        assert_eq!(timer.next_frame(), late + Duration::from_millis(100));
+    }
+
+    #[test]
+    fn polled_stepped_clock() {
+        let start = Instant::now();
+        let pacing = Duration::from_millis(50);
+        let deltas = [Duration::from_millis(16), Duration::from_millis(33)];
+        let mut clock = SteppedClock::new(deltas).with_pacing(pacing);
+        assert_eq!(clock.poll_frame(start), FramePoll::Due(deltas[0]));
+        assert_eq!(clock.poll_frame(start), FramePoll::Wait(start + pacing));
+        assert_eq!(clock.poll_frame(start + pacing), FramePoll::Due(deltas[1]));
+        assert_eq!(clock.poll_frame(start + pacing * 2), FramePoll::Stopped);
+    }
+
+    #[test]
+    fn polled_real_clock() {
+        let start = Instant::now();
+        let mut clock = RealClock::new(10);
+        assert_eq!(clock.poll_frame(start), FramePoll::Due(Duration::ZERO));
+        let next_frame = start + Duration::from_millis(100);
+        assert_eq!(clock.poll_frame(start), FramePoll::Wait(next_frame));
+        let delta_t = Duration::from_millis(100);
+        assert_eq!(clock.poll_frame(next_frame), FramePoll::Due(delta_t));
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use super::Color;
+
+pub mod exports {
+    pub use super::Tint;
+}
+
+///Color multiplied onto the vertex colors of an entity, so the stock shaders can tint it without a
+///uniform. A flash, eg. red on damage, fades back to the base tint over its duration. Entities
+///carrying a Tint pass their colors through `apply` when building vertices and advance it in
+///their update
+#[derive(Debug, Clone, Default)]
+pub struct Tint {
+    base: Option<Color>,
+    ///Color, duration and the time left
+    flash: Option<(Color, Duration, Duration)>,
+}
+impl Tint {
+    pub fn new(base: Option<Color>) -> Self {
+        Self { base, flash: None }
+    }
+
+    pub fn set_base(&mut self, base: Option<Color>) {
+        self.base = base;
+    }
+
+    ///Replaces a running flash
+    pub fn flash(&mut self, color: Color, duration: Duration) {
+        self.flash = Some((color, duration, duration));
+    }
+
+    pub fn is_flashing(&self) -> bool {
+        self.flash.is_some()
+    }
+
+    ///Returns true while the tint changes, so the entity can report itself as render dirty
+    pub fn update(&mut self, delta_t: &Duration) -> bool {
+        match &mut self.flash {
+            Some((_, _, left)) => {
+                *left = left.saturating_sub(*delta_t);
+                if left.is_zero() {
+                    self.flash = None;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///The current tint, None if the colors stay as they are
+    pub fn color(&self) -> Option<Color> {
+        let white = Color::new_rgba(255, 255, 255, 255);
+        let base = self.base.clone();
+        match &self.flash {
+            Some((color, duration, left)) => {
+                let t = if duration.is_zero() {
+                    0.0
+                } else {
+                    left.as_secs_f32() / duration.as_secs_f32()
+                };
+                Some(base.unwrap_or(white).lerp(color, t))
+            }
+            None => base,
+        }
+    }
+
+    pub fn apply(&self, color: &Color) -> Color {
+        match self.color() {
+            Some(tint) => color.multiply(&tint),
+            None => color.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flash_fades() {
+        let white = Color::new_rgba(255, 255, 255, 255);
+        let mut tint = Tint::default();
+        assert_eq!(tint.apply(&white).to_slice(), white.to_slice());
+        tint.flash(Color::new_rgba(255, 0, 0, 255), Duration::from_secs(2));
+        assert_eq!(tint.apply(&white).to_slice(), [255, 0, 0, 255]);
+        assert!(tint.update(&Duration::from_secs(1)));
+        assert_eq!(tint.apply(&white).to_slice(), [255, 128, 128, 255]);
+        assert!(tint.update(&Duration::from_secs(1)));
+        assert!(!tint.is_flashing());
+        assert!(!tint.update(&Duration::from_secs(1)));
+        assert_eq!(tint.apply(&white).to_slice(), white.to_slice());
+    }
+}
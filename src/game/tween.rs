@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use crate::graphics::UniformBufferName;
+
+pub mod exports {
+    pub use super::{Easing, UniformTween};
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    ///Starts slow
+    EaseIn,
+    ///Ends slow
+    EaseOut,
+    EaseInOut,
+}
+impl Easing {
+    ///Maps the progress `t`, clamped to [0, 1], onto the curve
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut if t < 0.5 => 2.0 * t * t,
+            Easing::EaseInOut => -1.0 + (4.0 - 2.0 * t) * t,
+        }
+    }
+}
+
+///Interpolates the f32 values of a uniform buffer, eg. the alpha of a fade. The Game writes the
+///buffer every frame until the duration passed
+#[derive(Debug, Clone)]
+pub struct UniformTween {
+    pub uniform: UniformBufferName,
+    pub start: Vec<f32>,
+    pub end: Vec<f32>,
+    pub duration: Duration,
+    pub easing: Easing,
+}
+impl UniformTween {
+    ///`start` and `end` must have the layout of the uniform buffer
+    pub fn new(
+        uniform: UniformBufferName,
+        start: Vec<f32>,
+        end: Vec<f32>,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            uniform,
+            start,
+            end,
+            duration,
+            easing: Easing::default(),
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    ///The values after `elapsed`, which stay at `end` once the duration passed
+    pub fn values(&self, elapsed: Duration) -> Vec<f32> {
+        let t = if self.duration.is_zero() {
+            1.0
+        } else {
+            elapsed.as_secs_f32() / self.duration.as_secs_f32()
+        };
+        let t = self.easing.apply(t);
+        self.start
+            .iter()
+            .zip(self.end.iter())
+            .map(|(start, end)| start + (end - start) * t)
+            .collect()
+    }
+
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing() {
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+        }
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn tween_values() {
+        let tween = UniformTween::new(
+            "Fade".into(),
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+            Duration::from_secs(2),
+        );
+        assert_eq!(tween.values(Duration::from_secs(1)), vec![0.5, 0.5]);
+        assert_eq!(tween.values(Duration::from_secs(3)), vec![1.0, 0.0]);
+        assert!(!tween.is_finished(Duration::from_secs(1)));
+        assert!(tween.is_finished(Duration::from_secs(2)));
+    }
+}
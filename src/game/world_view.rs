@@ -0,0 +1,122 @@
+use super::{
+    camera::Camera,
+    entity::{EntityName, EntityType},
+    input_state::InputState,
+    spatial_grid::SpatialGrid,
+    BoundingBox, Entity, ExternalEvent,
+};
+
+pub mod exports {
+    pub use super::WorldView;
+}
+
+///All entities of a scene except the one currently being updated. Borrows the entities of the
+///scene directly, so no list has to be built per entity. Cameras are left out of the iterators
+///and the nearby queries, they can only be looked up by name
+pub struct WorldView<'a, T: EntityType, E: ExternalEvent> {
+    left: &'a mut [Box<dyn Entity<T, E>>],
+    right: &'a mut [Box<dyn Entity<T, E>>],
+    ///Grid over all entities of the scene, including the one being updated at `left.len()`
+    grid: Option<&'a SpatialGrid>,
+    input: &'a InputState,
+}
+impl<'a, T: EntityType, E: ExternalEvent> WorldView<'a, T, E> {
+    pub fn new(
+        left: &'a mut [Box<dyn Entity<T, E>>],
+        right: &'a mut [Box<dyn Entity<T, E>>],
+        input: &'a InputState,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            grid: None,
+            input,
+        }
+    }
+
+    ///Keys and modifiers held during this update
+    pub fn input(&self) -> &InputState {
+        self.input
+    }
+
+    ///The grid must have been built from the entities of the scene in their current order
+    pub fn with_grid(mut self, grid: &'a SpatialGrid) -> Self {
+        self.grid = Some(grid);
+        self
+    }
+
+    ///Index into the entities of the scene
+    fn get_index(&self, index: usize) -> Option<&Box<dyn Entity<T, E>>> {
+        let own_index = self.left.len();
+        if index < own_index {
+            self.left.get(index)
+        } else if index > own_index {
+            self.right.get(index - own_index - 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Box<dyn Entity<T, E>>> {
+        self.left
+            .iter()
+            .chain(self.right.iter())
+            .filter(|entity| entity.camera().is_none())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn Entity<T, E>>> {
+        self.left
+            .iter_mut()
+            .chain(self.right.iter_mut())
+            .filter(|entity| entity.camera().is_none())
+    }
+
+    ///Also finds cameras
+    pub fn get(&self, name: &EntityName) -> Option<&Box<dyn Entity<T, E>>> {
+        self.left
+            .iter()
+            .chain(self.right.iter())
+            .find(|entity| entity.name() == name)
+    }
+
+    ///Also finds cameras
+    pub fn get_mut(&mut self, name: &EntityName) -> Option<&mut Box<dyn Entity<T, E>>> {
+        self.left
+            .iter_mut()
+            .chain(self.right.iter_mut())
+            .find(|entity| entity.name() == name)
+    }
+
+    ///The first camera of the scene, eg. to cull against its view
+    pub fn camera(&self) -> Option<&Camera> {
+        self.left
+            .iter()
+            .chain(self.right.iter())
+            .find_map(|entity| entity.camera())
+    }
+
+    ///All entities whose bounding box intersects the area. Uses the spatial grid if present,
+    ///which holds the bounding boxes from the start of the frame
+    pub fn nearby(&self, area: &BoundingBox) -> Vec<&Box<dyn Entity<T, E>>> {
+        match self.grid {
+            Some(grid) => grid
+                .query(area)
+                .into_iter()
+                .filter_map(|index| self.get_index(index))
+                .filter(|entity| entity.bounding_box().intersects(area))
+                .collect(),
+            None => self
+                .iter()
+                .filter(|entity| entity.bounding_box().intersects(area))
+                .collect(),
+        }
+    }
+}
@@ -15,6 +15,8 @@ pub trait Vertex:
         }
     }
 
+    ///One per field in order, eg. from `wgpu::vertex_attr_array!`. Colors packed with
+    ///`Color::to_packed_u32` are Uint32 and unpacked by `unpack4x8unorm` in the shader
     fn attributes() -> &'static [wgpu::VertexAttribute];
 }
 pub trait Index: Debug + Clone + Copy + bytemuck::Pod + bytemuck::Zeroable {
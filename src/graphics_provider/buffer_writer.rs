@@ -8,6 +8,8 @@ pub mod exports {
 }
 
 pub trait BufferWriter {
+    ///Data that is not a multiple of COPY_BUFFER_ALIGNMENT is copied and padded before writing,
+    ///so writers of big buffers should keep it padded
     fn buffer_data<'a>(&'a self) -> Option<&'a [u8]>;
     fn buffer_len(&self) -> u32;
 
@@ -0,0 +1,192 @@
+use std::ops::RangeInclusive;
+
+use crate::Size;
+
+use super::UniformBufferName;
+
+pub mod exports {
+    pub use super::{
+        DefaultTexture, GraphicsDescriptor, DEFAULT_MAX_FRAME_LATENCY, DEFAULT_MAX_TEXTURES,
+    };
+}
+
+///Length of the texture and sampler binding arrays, if not configured otherwise
+pub const DEFAULT_MAX_TEXTURES: u32 = 16;
+///Frames queued ahead of the one presented, if not configured otherwise
+pub const DEFAULT_MAX_FRAME_LATENCY: u32 = 2;
+
+///Maximum frame latencies the backend accepts. Metal keeps 2 or 3 drawables, DX12 up to 16
+///frames. Vulkan may clamp it further to the swapchain images of the surface
+pub(crate) fn supported_frame_latencies(backend: wgpu::Backend) -> RangeInclusive<u32> {
+    match backend {
+        wgpu::Backend::Metal => 2..=3,
+        _ => 1..=16,
+    }
+}
+
+///Texture at index 0, used by untextured vertices and unused texture slots
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefaultTexture {
+    ///Opaque white keeps the vertex colors as they are
+    Solid([u8; 4]),
+    ///2x2 squares of both colors, eg. magenta and black to spot entities without a sprite
+    Checkerboard([u8; 4], [u8; 4]),
+}
+impl DefaultTexture {
+    ///Rgba rows and the width and height
+    pub(crate) fn pixels(&self) -> (Vec<u8>, u32, u32) {
+        match self {
+            DefaultTexture::Solid(color) => (color.to_vec(), 1, 1),
+            DefaultTexture::Checkerboard(a, b) => ([a, b, b, a].concat(), 2, 2),
+        }
+    }
+}
+impl Default for DefaultTexture {
+    fn default() -> Self {
+        DefaultTexture::Solid([255; 4])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphicsDescriptor {
+    ///Fixed length N of the `binding_array<texture_2d<f32>, N>` and `binding_array<sampler, N>`
+    ///in the shaders. Unused slots point to the default texture. Will be clamped to the limits of
+    ///the adapter
+    pub max_textures: u32,
+    ///Surface formats tried in order when creating a window surface. If none is supported, the
+    ///first sRGB format of the surface is used
+    pub preferred_formats: Vec<wgpu::TextureFormat>,
+    ///With false, non sRGB surface formats are preferred and textures are not decoded from
+    ///sRGB, so the bytes written are the bytes shown, eg. for color accurate tools
+    pub srgb: bool,
+    ///Multiply the color of loaded textures with their alpha, for render scenes using
+    ///BlendMode::PremultipliedAlpha
+    pub premultiply_textures: bool,
+    pub default_texture: DefaultTexture,
+    ///Render every window at this size and scale it up to the window, eg. for pixel art
+    pub internal_resolution: Option<Size<u32>>,
+    ///Nearest scales by whole numbers for crisp pixels, Linear fills the window smoothly
+    pub upscale_filter: wgpu::FilterMode,
+    ///Uniforms written once and shared by every render scene with `use_global_uniforms`, eg. the
+    ///resolution or the time. They are one bind group, at the @group directly after the textures:
+    ///@group(1) with textures, @group(0) without. The uniform at index i is at @binding(i). The
+    ///uniforms of the scene follow at the next groups
+    pub global_uniforms: Vec<(UniformBufferName, Vec<u8>, wgpu::ShaderStages)>,
+    ///Request the device on a worker thread instead of blocking the first window. Render scenes
+    ///and textures requested until it is ready are created afterwards
+    pub async_init: bool,
+    ///Frames the GPU may queue ahead of the presented one. 1 has the lowest input latency, 3
+    ///smooths the throughput. Clamped to the range the backend supports, with a warning
+    pub max_frame_latency: u32,
+    ///With false, one texture is bound per render scene even if the adapter supports texture
+    ///arrays, eg. to test the fallback of adapters without them
+    pub texture_arrays: bool,
+}
+impl GraphicsDescriptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_textures(mut self, max_textures: u32) -> Self {
+        self.max_textures = max_textures;
+        self
+    }
+
+    ///Appends to the preferred formats, so earlier calls take precedence
+    pub fn with_preferred_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.preferred_formats.push(format);
+        self
+    }
+
+    pub fn with_internal_resolution(mut self, size: Size<u32>) -> Self {
+        self.internal_resolution = Some(size);
+        self
+    }
+
+    pub fn with_upscale_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.upscale_filter = filter;
+        self
+    }
+
+    ///True, the default, prefers sRGB surfaces and decodes textures from sRGB. False prefers non
+    ///sRGB surfaces and uploads the texture bytes as they are
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    ///Appends to the global uniforms, so the first call is at @binding(0)
+    pub fn with_global_uniform(
+        mut self,
+        uniform: (UniformBufferName, Vec<u8>, wgpu::ShaderStages),
+    ) -> Self {
+        self.global_uniforms.push(uniform);
+        self
+    }
+
+    pub fn with_default_texture(mut self, default_texture: DefaultTexture) -> Self {
+        self.default_texture = default_texture;
+        self
+    }
+
+    pub fn with_max_frame_latency(mut self, max_frame_latency: u32) -> Self {
+        self.max_frame_latency = max_frame_latency;
+        self
+    }
+
+    pub fn with_async_init(mut self) -> Self {
+        self.async_init = true;
+        self
+    }
+
+    pub fn with_texture_arrays(mut self, texture_arrays: bool) -> Self {
+        self.texture_arrays = texture_arrays;
+        self
+    }
+
+    pub fn with_premultiplied_textures(mut self) -> Self {
+        self.premultiply_textures = true;
+        self
+    }
+}
+impl Default for GraphicsDescriptor {
+    fn default() -> Self {
+        Self {
+            max_textures: DEFAULT_MAX_TEXTURES,
+            preferred_formats: Vec::new(),
+            srgb: true,
+            premultiply_textures: false,
+            default_texture: DefaultTexture::default(),
+            internal_resolution: None,
+            upscale_filter: wgpu::FilterMode::Nearest,
+            global_uniforms: Vec::new(),
+            async_init: false,
+            max_frame_latency: DEFAULT_MAX_FRAME_LATENCY,
+            texture_arrays: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_texture_pixels() {
+        assert_eq!(DefaultTexture::default().pixels(), (vec![255; 4], 1, 1));
+        let (bytes, width, height) =
+            DefaultTexture::Checkerboard([255, 0, 255, 255], [0, 0, 0, 255]).pixels();
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(bytes.len(), (4 * width * height) as usize);
+        assert_eq!(&bytes[0..4], &bytes[12..16]);
+        assert_eq!(&bytes[4..8], &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn frame_latencies() {
+        let metal = supported_frame_latencies(wgpu::Backend::Metal);
+        assert_eq!(1.clamp(*metal.start(), *metal.end()), 2);
+        let vulkan = supported_frame_latencies(wgpu::Backend::Vulkan);
+        assert!(vulkan.contains(&1) && vulkan.contains(&DEFAULT_MAX_FRAME_LATENCY));
+    }
+}
@@ -1,21 +1,37 @@
 #![allow(deprecated)]
 use std::fs;
 use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, Arc,
+};
+use std::thread;
 
+use log::{error, warn};
 use wgpu::rwh::{HasRawDisplayHandle, HasRawWindowHandle};
 use winit::window::{Window, WindowId};
 
+mod accumulation;
 mod buffer_primitives;
 mod surface;
-use surface::{Surface, WindowSurface};
+use surface::{HeadlessSurface, Surface, WindowSurface};
 mod shader_descriptor;
 use shader_descriptor::ShaderDescriptor;
 mod texture;
 use texture::TextureProvider;
 mod buffer_writer;
 use buffer_writer::{IndexBufferWriter, VertexBufferWriter};
+mod graphics_descriptor;
+use graphics_descriptor::{supported_frame_latencies, DefaultTexture, GraphicsDescriptor};
 mod render_scene;
-use render_scene::{RenderScene, RenderSceneDescriptor, RenderSceneName, UniformBufferName};
+use render_scene::{
+    BlendMode, RenderScene, RenderSceneDescriptor, RenderSceneName, RenderStats, ScissorRect,
+    UniformBufferName,
+};
+mod uniforms;
+use uniforms::GlobalUniforms;
+mod upscaler;
+use upscaler::Upscaler;
 
 pub mod exports {
     pub use super::shader_descriptor::exports::*;
@@ -23,15 +39,38 @@ pub mod exports {
     pub use super::buffer_writer::exports::*;
     pub use super::buffer_primitives::exports::*;
     pub use super::texture::exports::*;
+    pub use super::graphics_descriptor::exports::*;
+    pub use super::uniforms::exports::*;
     pub use super::{GraphicsProvider, Visibility};
 }
 
-#[derive(Debug, Clone)]
+///Required to index all textures in the shader. Without them one texture is bound per render scene
+const TEXTURE_ARRAY_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_BINDING_ARRAY
+    .union(wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Visibility {
     Visible,
     Hidden,
 }
 
+///Everything a render pipeline depends on. Render scenes with equal keys share one pipeline and
+///shader module, even across windows. Pipelines depend on the surface format, so windows with
+///different formats still compile their own
+#[derive(Debug, PartialEq)]
+struct PipelineKey {
+    shader_file: &'static str,
+    vertex_shader: &'static str,
+    fragment_shader: &'static str,
+    format: wgpu::TextureFormat,
+    vertex_buffer_layout: wgpu::VertexBufferLayout<'static>,
+    use_textures: bool,
+    use_depth: bool,
+    blend_mode: BlendMode,
+    use_global_uniforms: bool,
+    uniform_visibilities: Vec<wgpu::ShaderStages>,
+}
+
 pub struct GraphicsProvider {
     instance: wgpu::Instance,
     adapter: Option<wgpu::Adapter>,
@@ -40,12 +79,36 @@ pub struct GraphicsProvider {
     ///One to one relationship
     surfaces: Vec<(WindowId, Box<dyn WindowSurface>)>,
     ///One to many relationship
-    render_scenes: Vec<(WindowId, RenderScene, wgpu::ShaderModule, ShaderDescriptor)>,
+    render_scenes: Vec<(WindowId, RenderScene, Arc<wgpu::ShaderModule>, ShaderDescriptor)>,
+    ///Compiled pipelines, dropped once no render scene uses them anymore
+    pipelines: Vec<(PipelineKey, Arc<wgpu::ShaderModule>, Arc<wgpu::RenderPipeline>)>,
     texture_provider: Option<TextureProvider>,
     uniform_buffers: Vec<(RenderSceneName, UniformBufferName)>,
+    ///Created with the device from the descriptor, which keeps their latest values for the next
+    ///device after a loss
+    global_uniforms: Option<GlobalUniforms>,
+    descriptor: GraphicsDescriptor,
+    ///Windows whose content changed since they were last drawn
+    dirty_windows: Vec<WindowId>,
+    ///Set from wgpu's device lost callback
+    device_lost: Arc<AtomicBool>,
+    ///Of the last frame of each window
+    render_stats: Vec<(WindowId, RenderStats)>,
+    ///The device requested on a worker thread with `GraphicsDescriptor::async_init`
+    pending_device: Option<mpsc::Receiver<(wgpu::Adapter, u32, DeviceResult)>>,
+    ///Surfaces of the windows created before the device was ready, with their latest size
+    pending_surfaces: Vec<(WindowId, wgpu::Surface<'static>, winit::dpi::PhysicalSize<u32>)>,
+    ///Kept across device losses, so the recreated render scenes are clipped again
+    scissors: Vec<(RenderSceneName, ScissorRect)>,
 }
+
+type DeviceResult = Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError>;
 impl GraphicsProvider {
     pub fn new() -> Self {
+        Self::with_descriptor(GraphicsDescriptor::default())
+    }
+
+    pub fn with_descriptor(descriptor: GraphicsDescriptor) -> Self {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             ..Default::default()
@@ -57,20 +120,164 @@ impl GraphicsProvider {
             queue: None,
             surfaces: Vec::new(),
             render_scenes: Vec::new(),
+            pipelines: Vec::new(),
             uniform_buffers: Vec::new(),
+            global_uniforms: None,
             texture_provider: None,
+            descriptor,
+            dirty_windows: Vec::new(),
+            device_lost: Arc::new(AtomicBool::new(false)),
+            render_stats: Vec::new(),
+            pending_device: None,
+            pending_surfaces: Vec::new(),
+            scissors: Vec::new(),
         }
     }
 
+    fn mark_dirty(&mut self, id: &WindowId) {
+        if !self.dirty_windows.contains(id) {
+            self.dirty_windows.push(id.clone());
+        }
+    }
+
+    fn mark_render_scene_dirty(&mut self, render_scene: &RenderSceneName) {
+        if let Some(id) = self.get_window(render_scene).cloned() {
+            self.mark_dirty(&id);
+        }
+    }
+
+    ///Windows that changed since the last call and need to be redrawn
+    pub fn take_dirty_windows(&mut self) -> Vec<WindowId> {
+        std::mem::take(&mut self.dirty_windows)
+    }
+
     pub fn set_visibility_render_scene(&mut self, render_scene: &RenderSceneName, visibility: &Visibility) {
         if let Some((_, scene, _, _)) = self.render_scenes.iter_mut().find(|(_, r, _, _)| r.name() == render_scene) {
             scene.set_visibility(visibility);
         }
+        self.mark_render_scene_dirty(render_scene);
+    }
+
+    ///None if there is no render scene with the name
+    pub fn get_visibility(&self, render_scene: &RenderSceneName) -> Option<Visibility> {
+        self.render_scenes
+            .iter()
+            .find(|(_, r, _, _)| r.name() == render_scene)
+            .map(|(_, scene, _, _)| scene.visibility().clone())
+    }
+
+    ///Vertex and index count of the render scene, see RenderScene::num_vertices
+    pub fn render_scene_geometry(&self, render_scene: &RenderSceneName) -> Option<(u32, u32)> {
+        self.render_scenes
+            .iter()
+            .find(|(_, r, _, _)| r.name() == render_scene)
+            .map(|(_, scene, _, _)| (scene.num_vertices(), scene.num_indices()))
+    }
+
+    ///Render scenes with the same z_index are drawn in the order they were added
+    pub fn set_z_index_render_scene(&mut self, render_scene: &RenderSceneName, z_index: i32) {
+        if let Some((_, scene, _, _)) = self.render_scenes.iter_mut().find(|(_, r, _, _)| r.name() == render_scene) {
+            scene.set_z_index(z_index);
+        }
+        self.mark_render_scene_dirty(render_scene);
+    }
+
+    ///None draws the whole surface again
+    pub fn set_scissor_render_scene(
+        &mut self,
+        render_scene: &RenderSceneName,
+        scissor: Option<ScissorRect>,
+    ) {
+        self.scissors.retain(|(r, _)| r != render_scene);
+        if let Some(scissor) = scissor {
+            self.scissors.push((render_scene.clone(), scissor));
+        }
+        if let Some((_, scene, _, _)) = self
+            .render_scenes
+            .iter_mut()
+            .find(|(_, r, _, _)| r.name() == render_scene)
+        {
+            scene.set_scissor(scissor);
+        }
+        self.mark_render_scene_dirty(render_scene);
+    }
+
+    ///If the shaders of textured render scenes index `binding_array<texture_2d<f32>>` with the
+    ///texture index of the vertices. Otherwise they declare a single `texture_2d<f32>` and
+    ///`sampler`, and each render scene draws the single texture bound with
+    ///`bind_texture_render_scene`, whatever the texture index of its vertices
+    pub fn supports_texture_arrays(&self) -> bool {
+        self.texture_provider
+            .as_ref()
+            .map_or(true, |texture_provider| texture_provider.supports_texture_arrays())
+    }
+
+    ///Without texture arrays the render scene draws this texture instead of the default one.
+    ///With them the vertices select their textures and this does nothing. Returns false if
+    ///there is no such render scene, or no such texture without texture arrays
+    pub fn bind_texture_render_scene(
+        &mut self,
+        render_scene: &RenderSceneName,
+        texture_index: u32,
+    ) -> bool {
+        let texture_provider = match &self.texture_provider {
+            Some(texture_provider) => texture_provider,
+            None => return false,
+        };
+        let scene = match self
+            .render_scenes
+            .iter_mut()
+            .find(|(_, r, _, _)| r.name() == render_scene)
+        {
+            Some((_, scene, _, _)) => scene,
+            None => return false,
+        };
+        if texture_provider.supports_texture_arrays() {
+            return true;
+        }
+        match texture_provider.texture_bind_group(texture_index) {
+            Some(bind_group) => {
+                scene.bind_texture(bind_group);
+                self.mark_render_scene_dirty(render_scene);
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn remove_render_scene(&mut self, render_scene: &RenderSceneName) {
+        self.mark_render_scene_dirty(render_scene);
+        self.scissors.retain(|(r, _)| r != render_scene);
         self.render_scenes
             .retain(|(_, r, _, _)| r.name() != render_scene);
+        self.prune_pipelines();
+    }
+
+    ///In the order they were added
+    pub fn render_scenes_for_window(&self, id: &WindowId) -> Vec<RenderSceneName> {
+        self.render_scenes
+            .iter()
+            .filter(|(i, _, _, _)| i == id)
+            .map(|(_, scene, _, _)| scene.name().clone())
+            .collect()
+    }
+
+    ///Removes all render scenes and their uniform buffers of the window, but keeps its surface,
+    ///eg. to rebuild the layers for the next level
+    pub fn clear_render_scenes(&mut self, id: &WindowId) {
+        let render_scenes = self.render_scenes_for_window(id);
+        self.uniform_buffers
+            .retain(|(r, _)| !render_scenes.contains(r));
+        self.scissors.retain(|(r, _)| !render_scenes.contains(r));
+        self.render_scenes.retain(|(i, _, _, _)| i != id);
+        self.prune_pipelines();
+        self.mark_dirty(id);
+    }
+
+    ///Drops the pipelines only held by the cache
+    fn prune_pipelines(&mut self) {
+        self.pipelines
+            .retain(|(_, _, pipeline)| Arc::strong_count(pipeline) > 1);
     }
 
     pub fn get_window(&self, render_scene: &RenderSceneName) -> Option<&WindowId> {
@@ -80,30 +287,165 @@ impl GraphicsProvider {
             .map(|(window_id, _, _, _)| window_id)
     }
 
-    fn init(&mut self, surface: &wgpu::Surface) {
-        let adapter = futures::executor::block_on(self.instance.request_adapter(
+    ///Create a GraphicsProvider without any window, rendering into offscreen targets added with
+    ///`init_headless_target`. Returns None if no adapter with the required features is found
+    pub fn new_headless() -> Option<Self> {
+        Self::new_headless_with_descriptor(GraphicsDescriptor::default())
+    }
+
+    pub fn new_headless_with_descriptor(descriptor: GraphicsDescriptor) -> Option<Self> {
+        let mut provider = Self::with_descriptor(descriptor);
+        provider.instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = provider.request_adapter(None)?;
+        provider.init(adapter).ok()?;
+        Some(provider)
+    }
+
+    fn request_adapter(&self, compatible_surface: Option<&wgpu::Surface>) -> Option<wgpu::Adapter> {
+        futures::executor::block_on(self.instance.request_adapter(
             &wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
+                compatible_surface,
                 force_fallback_adapter: false,
             },
         ))
-        .expect("Buy a new GPU. Not all prerequisites met");
-
-        let (device, queue) = futures::executor::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::TEXTURE_BINDING_ARRAY
-                    | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
-                required_limits: wgpu::Limits {
-                    // max_vertex_attributes: 32,
-                    ..Default::default()
-                },
-                label: None,
+    }
+
+    fn init(&mut self, adapter: wgpu::Adapter) -> Result<(), wgpu::RequestDeviceError> {
+        let (max_textures, device_descriptor) = self.device_descriptor(&adapter);
+        let (device, queue) =
+            futures::executor::block_on(adapter.request_device(&device_descriptor, None))?;
+        self.init_device(adapter, max_textures, device, queue);
+        Ok(())
+    }
+
+    ///Requests the device on a worker thread. It is set up in poll_device once it arrives
+    fn init_async(&mut self, adapter: wgpu::Adapter) {
+        let (max_textures, device_descriptor) = self.device_descriptor(&adapter);
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let result =
+                futures::executor::block_on(adapter.request_device(&device_descriptor, None));
+            //The provider was dropped in the meantime
+            let _ = sender.send((adapter, max_textures, result));
+        });
+        self.pending_device = Some(receiver);
+    }
+
+    ///If the device is still requested on the worker thread of `GraphicsDescriptor::async_init`.
+    ///Render scenes and textures can only be created once it is ready
+    pub fn is_initializing(&self) -> bool {
+        self.pending_device.is_some()
+    }
+
+    ///If the device exists. It is created with the first window, before render scenes and textures
+    ///fail
+    pub fn has_device(&self) -> bool {
+        self.device.is_some()
+    }
+
+    ///Returns true once the device requested asynchronously is ready. The surfaces of the windows
+    ///created in the meantime are configured then
+    pub fn poll_device(&mut self) -> bool {
+        let received = match &self.pending_device {
+            Some(receiver) => match receiver.try_recv() {
+                Ok(received) => received,
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    panic!("The thread requesting the graphics device vanished")
+                }
             },
-            None, // Trace path
-        ))
-        .expect("Buy a new GPU. Not all prerequisites met");
-        self.texture_provider = Some(TextureProvider::new(&device, &queue));
+            None => return false,
+        };
+        self.pending_device = None;
+        let (adapter, max_textures, result) = received;
+        let (device, queue) = result.expect("Buy a new GPU. Not all prerequisites met");
+        self.init_device(adapter, max_textures, device, queue);
+        for (id, surface, size) in std::mem::take(&mut self.pending_surfaces) {
+            self.configure_surface(id, surface, size);
+        }
+        true
+    }
+
+    fn use_texture_arrays(&self, adapter: &wgpu::Adapter) -> bool {
+        self.descriptor.texture_arrays && adapter.features().contains(TEXTURE_ARRAY_FEATURES)
+    }
+
+    ///The amount of texture slots and the descriptor to request the device with
+    fn device_descriptor(&self, adapter: &wgpu::Adapter) -> (u32, wgpu::DeviceDescriptor<'static>) {
+        let adapter_limits = adapter.limits();
+        let max_textures = self
+            .descriptor
+            .max_textures
+            .min(adapter_limits.max_sampled_textures_per_shader_stage)
+            .min(adapter_limits.max_samplers_per_shader_stage)
+            //The default and the placeholder texture
+            .max(2);
+        if max_textures != self.descriptor.max_textures {
+            warn!(
+                "The adapter supports {} textures per shader stage, not {}",
+                max_textures, self.descriptor.max_textures
+            );
+        }
+        let required_features = if self.use_texture_arrays(adapter) {
+            TEXTURE_ARRAY_FEATURES
+        } else if !self.descriptor.texture_arrays {
+            wgpu::Features::empty()
+        } else {
+            warn!("The adapter does not support texture arrays. Binding one texture per render scene");
+            wgpu::Features::empty()
+        };
+        let default_limits = wgpu::Limits::default();
+        let device_descriptor = wgpu::DeviceDescriptor {
+            required_features,
+            required_limits: wgpu::Limits {
+                // max_vertex_attributes: 32,
+                max_sampled_textures_per_shader_stage: max_textures
+                    .max(default_limits.max_sampled_textures_per_shader_stage),
+                max_samplers_per_shader_stage: max_textures
+                    .max(default_limits.max_samplers_per_shader_stage),
+                ..default_limits
+            },
+            label: None,
+        };
+        (max_textures, device_descriptor)
+    }
+
+    fn init_device(
+        &mut self,
+        adapter: wgpu::Adapter,
+        max_textures: u32,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) {
+        let device_lost = self.device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            //Reported when the device is dropped with the provider, or the callback is replaced
+            if matches!(
+                reason,
+                wgpu::DeviceLostReason::Dropped | wgpu::DeviceLostReason::ReplacedCallback
+            ) {
+                return;
+            }
+            error!("Lost the graphics device ({:?}): {}", reason, message);
+            device_lost.store(true, Ordering::SeqCst);
+        });
+        self.texture_provider = Some(TextureProvider::new(
+            &device,
+            &queue,
+            max_textures,
+            self.descriptor.premultiply_textures,
+            self.descriptor.srgb,
+            &self.descriptor.default_texture,
+            self.use_texture_arrays(&adapter),
+        ));
+        self.global_uniforms = Some(GlobalUniforms::new(
+            &device,
+            &self.descriptor.global_uniforms,
+        ));
         self.adapter = Some(adapter);
         self.device = Some(device);
         self.queue = Some(queue);
@@ -127,24 +469,71 @@ impl GraphicsProvider {
         }
         .expect("Could not create a surface");
 
+        if self.is_initializing() {
+            self.pending_surfaces.push((window.id(), surface, size));
+            return;
+        }
         if self.adapter.is_none() {
-            self.init(&surface);
+            let adapter = self
+                .request_adapter(Some(&surface))
+                .expect("Buy a new GPU. Not all prerequisites met");
+            if self.descriptor.async_init {
+                self.init_async(adapter);
+                self.pending_surfaces.push((window.id(), surface, size));
+                return;
+            }
+            self.init(adapter)
+                .expect("Buy a new GPU. Not all prerequisites met");
         }
+        self.configure_surface(window.id(), surface, size);
+    }
 
-        let capabilities = surface.get_capabilities(
-            &self
-                .adapter
-                .as_ref()
-                .expect("The surface is not compatible with the adapter"),
-        );
-        let format = capabilities
-            .formats
+    fn configure_surface(
+        &mut self,
+        id: WindowId,
+        surface: wgpu::Surface<'static>,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        let adapter = self
+            .adapter
+            .as_ref()
+            .expect("The surface is not compatible with the adapter");
+        let capabilities = surface.get_capabilities(adapter);
+        let backend = adapter.get_info().backend;
+        let format = self
+            .descriptor
+            .preferred_formats
             .iter()
             .copied()
-            .filter(|f| f.is_srgb())
-            .next()
-            .or(Some(capabilities.formats[0]))
+            .find(|f| capabilities.formats.contains(f))
+            .or_else(|| {
+                capabilities
+                    .formats
+                    .iter()
+                    .copied()
+                    .find(|f| f.is_srgb() == self.descriptor.srgb)
+            })
+            .or(capabilities.formats.first().copied())
             .expect("No compatible format found");
+        if !self.descriptor.preferred_formats.is_empty()
+            && !self.descriptor.preferred_formats.contains(&format)
+        {
+            warn!(
+                "None of the preferred formats {:?} is supported by the surface. Using {:?}",
+                self.descriptor.preferred_formats, format
+            );
+        }
+        let latencies = supported_frame_latencies(backend);
+        let max_frame_latency = self
+            .descriptor
+            .max_frame_latency
+            .clamp(*latencies.start(), *latencies.end());
+        if max_frame_latency != self.descriptor.max_frame_latency {
+            warn!(
+                "A maximum frame latency of {} is not supported by {:?}. Using {}",
+                self.descriptor.max_frame_latency, backend, max_frame_latency
+            );
+        }
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format,
@@ -153,24 +542,76 @@ impl GraphicsProvider {
             present_mode: capabilities.present_modes[0],
             alpha_mode: capabilities.alpha_modes[0],
             view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+            desired_maximum_frame_latency: max_frame_latency,
         };
 
-        self.surfaces.push((
-            window.id(),
-            Box::new(Surface {
-                wgpu_surface: surface,
-                config,
-            }),
-        ));
+        let mut surface = Surface::new(surface, config);
+        if let Some(upscaler) = self.create_upscaler(format) {
+            surface.set_upscaler(upscaler);
+        }
+        self.surfaces.push((id, Box::new(surface)));
+    }
+
+    fn create_upscaler(&self, format: wgpu::TextureFormat) -> Option<Upscaler> {
+        let size = self.descriptor.internal_resolution.as_ref()?;
+        let device = self.device.as_ref().expect("The device vanished");
+        Some(Upscaler::new(
+            device,
+            format,
+            size.width(),
+            size.height(),
+            self.descriptor.upscale_filter,
+        ))
+    }
+
+    ///The texture format the window is rendered in, eg. to create matching render targets
+    pub fn surface_format(&self, id: &WindowId) -> Option<wgpu::TextureFormat> {
+        self.surfaces
+            .iter()
+            .find(|(i, _)| i == id)
+            .map(|(_, surface)| surface.config().format)
+    }
+
+    ///If the window is rendered in an sRGB format, so shaders writing final colors can compensate
+    pub fn surface_is_srgb(&self, id: &WindowId) -> Option<bool> {
+        self.surface_format(id).map(|format| format.is_srgb())
+    }
+
+    ///Add an offscreen render target, which is treated like a window with the given id
+    pub fn init_headless_target(&mut self, id: WindowId, size: &winit::dpi::PhysicalSize<u32>) {
+        let device = self.device.as_ref().expect("The device vanished");
+        let format = if self.descriptor.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let mut surface = HeadlessSurface::new(device, size, format);
+        if let Some(upscaler) = self.create_upscaler(format) {
+            surface.set_upscaler(upscaler);
+        }
+        self.surfaces.push((id, Box::new(surface)));
+    }
+
+    ///Read back the last frame rendered to a headless target as rgba rows
+    pub fn read_pixels(&self, id: &WindowId) -> Option<Vec<u8>> {
+        let (_, surface) = self.surfaces.iter().find(|(i, _)| i == id)?;
+        if let (Some(device), Some(queue)) = (&self.device, &self.queue) {
+            surface.read_pixels(device, queue)
+        } else {
+            None
+        }
     }
 
     pub fn resize_window(&mut self, id: &WindowId, new_size: &winit::dpi::PhysicalSize<u32>) {
+        if let Some((_, _, size)) = self.pending_surfaces.iter_mut().find(|(i, _, _)| i == id) {
+            *size = *new_size;
+        }
         if let Some((_, surface)) = self.surfaces.iter_mut().find(|(i, _)| i == id) {
             if let Some(device) = &self.device {
                 surface.resize(new_size, device);
             }
         }
+        self.mark_dirty(id);
     }
 
     pub fn render_window(&mut self, id: &WindowId) {
@@ -180,16 +621,40 @@ impl GraphicsProvider {
             {
                 let texture_bind_group =
                     texture_provider.bind_group.as_ref().expect("No bind group");
-                let render_scenes = self
+                let mut stats = RenderStats::default();
+                let target_size = match &self.descriptor.internal_resolution {
+                    Some(size) => (size.width(), size.height()),
+                    None => (surface.config().width, surface.config().height),
+                };
+                for (_, render_scene, _, _) in
+                    self.render_scenes.iter_mut().filter(|(i, _, _, _)| i == id)
+                {
+                    render_scene.record_stats(&mut stats);
+                    render_scene.ensure_accumulation(device, surface.config().format, target_size);
+                }
+                match self.render_stats.iter_mut().find(|(i, _)| i == id) {
+                    Some((_, s)) => *s = stats,
+                    None => self.render_stats.push((id.clone(), stats)),
+                }
+                let mut render_scenes = self
                     .render_scenes
                     .iter()
                     .filter_map(|(i, s, _, _)| if i == id { Some(s) } else { None })
                     .collect::<Vec<_>>();
+                render_scenes.sort_by_key(|s| s.z_index());
                 surface.render(device, queue, &render_scenes, texture_bind_group);
             }
         }
     }
 
+    ///Counts of the last frame rendered to the window, eg. to spot missing batching
+    pub fn render_stats(&self, id: &WindowId) -> Option<&RenderStats> {
+        self.render_stats
+            .iter()
+            .find(|(i, _)| i == id)
+            .map(|(_, stats)| stats)
+    }
+
     /// Update the vertex and index buffers of a window
     pub fn update_scene(
         &mut self,
@@ -208,6 +673,20 @@ impl GraphicsProvider {
                 render_scene.update(device, queue, vertices, indices)
             }
         }
+        self.mark_render_scene_dirty(render_scene);
+    }
+
+    ///Update the buffers of several render scenes. The writes are submitted with the next frame
+    pub fn update_scenes<'a, V, I>(
+        &mut self,
+        updates: impl IntoIterator<Item = (&'a RenderSceneName, &'a V, &'a I)>,
+    ) where
+        V: VertexBufferWriter + 'a,
+        I: IndexBufferWriter + 'a,
+    {
+        for (render_scene, vertices, indices) in updates {
+            self.update_scene(render_scene, vertices, indices);
+        }
     }
 
     pub fn add_render_scene(
@@ -224,49 +703,152 @@ impl GraphicsProvider {
             self.surfaces.iter().find(|(id, _)| id == window_id),
             &self.texture_provider,
         ) {
-            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some(&format!("Shader Module {:?}", shader_descriptor.file)),
-                source: wgpu::ShaderSource::Wgsl(
-                    fs::read_to_string(shader_descriptor.file)
-                        .expect(&format!("Could not load '{}'\n", shader_descriptor.file))
-                        .into(),
-                ),
-            });
+            let use_global_uniforms = render_scene_descriptor.use_global_uniforms;
             let mut render_scene =
                 RenderScene::new(render_scene_name.clone(), device, render_scene_descriptor);
+            if use_global_uniforms {
+                let global_uniforms = self
+                    .global_uniforms
+                    .as_ref()
+                    .expect("The global uniforms vanished");
+                render_scene.set_global_uniforms(
+                    global_uniforms.bind_group_layout.clone(),
+                    global_uniforms.bind_group.clone(),
+                );
+            }
             for (uniform, content, visibility) in initial_uniforms {
+                Self::warn_shadowed_uniform(&self.global_uniforms, uniform, &render_scene_name);
                 render_scene.create_uniform_buffer(
                     device,
                     uniform.clone(),
                     content,
                     visibility.clone(),
                 );
-                self.uniform_buffers
-                    .push((render_scene_name.clone(), uniform.clone()));
+                Self::register_uniform(&mut self.uniform_buffers, &render_scene_name, uniform);
             }
-            let bind_groups_layouts = render_scene.bind_group_layouts(
-                texture_provider
-                    .bind_group_layout
-                    .as_ref()
-                    .expect("Default Texture vanished"),
-            );
-            let render_pipeline = surface.create_render_pipeline(
+            let (shader, render_pipeline) = Self::pipeline(
+                &mut self.pipelines,
                 device,
-                &bind_groups_layouts,
-                &shader,
+                surface.as_ref(),
+                texture_provider,
+                &render_scene,
                 &shader_descriptor,
-                render_scene.vertex_buffer_layout().clone(),
             );
             render_scene.update_pipeline(render_pipeline);
+            if let Some((_, scissor)) =
+                self.scissors.iter().find(|(r, _)| *r == render_scene_name)
+            {
+                render_scene.set_scissor(Some(*scissor));
+            }
             self.render_scenes
                 .push((window_id.clone(), render_scene, shader, shader_descriptor));
+            self.mark_dirty(window_id);
         } else {
             panic!("No surface on window {:?}", window_id)
         }
     }
 
+    ///The cached pipeline fitting the render scene, compiled if there is none yet
+    fn pipeline(
+        pipelines: &mut Vec<(PipelineKey, Arc<wgpu::ShaderModule>, Arc<wgpu::RenderPipeline>)>,
+        device: &wgpu::Device,
+        surface: &dyn WindowSurface,
+        texture_provider: &TextureProvider,
+        render_scene: &RenderScene,
+        shader_descriptor: &ShaderDescriptor,
+    ) -> (Arc<wgpu::ShaderModule>, Arc<wgpu::RenderPipeline>) {
+        let key = PipelineKey {
+            shader_file: shader_descriptor.file,
+            vertex_shader: shader_descriptor.vertex_shader,
+            fragment_shader: shader_descriptor.fragment_shader,
+            format: surface.config().format,
+            vertex_buffer_layout: render_scene.vertex_buffer_layout().clone(),
+            use_textures: render_scene.use_textures(),
+            use_depth: render_scene.use_depth(),
+            blend_mode: render_scene.blend_mode(),
+            use_global_uniforms: render_scene.use_global_uniforms(),
+            uniform_visibilities: render_scene.uniform_visibilities(),
+        };
+        match pipelines.iter().find(|(k, _, _)| *k == key) {
+            Some((_, shader, render_pipeline)) => (shader.clone(), render_pipeline.clone()),
+            None => {
+                let source = fs::read_to_string(shader_descriptor.file)
+                    .expect(&format!("Could not load '{}'\n", shader_descriptor.file));
+                let group_offset = key.use_textures as u32 + key.use_global_uniforms as u32;
+                let uniform_names = render_scene
+                    .uniform_names()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>();
+                match shader_descriptor.parse(&source) {
+                    Ok(module) => {
+                        for result in [
+                            shader_descriptor.validate_module(&module),
+                            shader_descriptor.check_uniform_groups(
+                                &module,
+                                &uniform_names,
+                                group_offset,
+                                key.use_global_uniforms.then_some(key.use_textures as u32),
+                            ),
+                        ] {
+                            if let Err(e) = result {
+                                error!("RenderScene {:?}: {}", render_scene.name(), e);
+                            }
+                        }
+                    }
+                    Err(e) => error!("RenderScene {:?}: {}", render_scene.name(), e),
+                }
+                let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(&format!("Shader Module {:?}", shader_descriptor.file)),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                });
+                let shader = Arc::new(shader);
+                let bind_groups_layouts = render_scene.bind_group_layouts(
+                    texture_provider
+                        .bind_group_layout
+                        .as_ref()
+                        .expect("Default Texture vanished"),
+                );
+                let render_pipeline = Arc::new(surface.create_render_pipeline(
+                    device,
+                    &bind_groups_layouts,
+                    &shader,
+                    shader_descriptor,
+                    render_scene.vertex_buffer_layout().clone(),
+                    render_scene.use_depth(),
+                    render_scene.blend_mode().blend_state(),
+                ));
+                pipelines.push((key, shader.clone(), render_pipeline.clone()));
+                (shader, render_pipeline)
+            }
+        }
+    }
+
+    ///Returns true once after the device was lost. All GPU state, including surfaces, render
+    ///scenes and textures, is dropped then. Windows have to be initialized again with
+    ///`init_window` and their render scenes and textures recreated
+    pub fn take_device_lost(&mut self) -> bool {
+        if !self.device_lost.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        self.surfaces.clear();
+        self.render_scenes.clear();
+        self.pipelines.clear();
+        self.render_stats.clear();
+        self.uniform_buffers.clear();
+        self.global_uniforms = None;
+        self.dirty_windows.clear();
+        self.texture_provider = None;
+        self.queue = None;
+        self.device = None;
+        self.adapter = None;
+        true
+    }
+
     pub fn remove_window(&mut self, id: &WindowId) {
         self.surfaces.retain(|(i, _)| i != id);
+        self.pending_surfaces.retain(|(i, _, _)| i != id);
+        self.dirty_windows.retain(|i| i != id);
+        self.render_stats.retain(|(i, _)| i != id);
         let render_scenes_to_delete = self
             .render_scenes
             .iter()
@@ -275,41 +857,29 @@ impl GraphicsProvider {
         self.uniform_buffers
             .retain(|(r, _)| !render_scenes_to_delete.contains(&r));
         self.render_scenes.retain(|(i, _, _, _)| i != id);
+        self.prune_pipelines();
     }
 
+    ///The texture bind group layout has a fixed length, so existing pipelines stay valid
     pub fn create_texture(&mut self, path: &Path, label: &str) -> Option<u32> {
         if let (Some(device), Some(queue), Some(texture_provider)) =
             (&self.device, &self.queue, &mut self.texture_provider)
         {
-            let index = texture_provider.create_texture(device, queue, path, Some(label));
-            let texture_bind_group_layout = texture_provider
-                .bind_group_layout
-                .as_ref()
-                .expect("No texture bind group layout");
-            self.render_scenes
-                .iter_mut()
-                .filter(|(_, s, _, _)| s.use_textures())
-                .for_each(|(window_id, render_scene, shader, shader_descriptor)| {
-                    if let Some((_, surface)) = self.surfaces.iter().find(|(id, _)| id == window_id)
-                    {
-                        let bind_groups_layouts =
-                            render_scene.bind_group_layouts(texture_bind_group_layout);
-                        let render_pipeline = surface.create_render_pipeline(
-                            device,
-                            &bind_groups_layouts,
-                            shader,
-                            shader_descriptor,
-                            render_scene.vertex_buffer_layout().clone(),
-                        );
-                        render_scene.update_pipeline(render_pipeline);
-                    }
-                });
-            Some(index)
+            texture_provider.create_texture(device, queue, path, Some(label))
         } else {
             None
         }
     }
 
+    ///The index is handed out to the next texture, so nothing may draw with it afterwards
+    pub fn remove_texture(&mut self, label: &str) -> bool {
+        if let (Some(device), Some(texture_provider)) = (&self.device, &mut self.texture_provider) {
+            texture_provider.remove_texture(device, label)
+        } else {
+            false
+        }
+    }
+
     pub fn create_uniform_buffer(
         &mut self,
         label: impl Into<UniformBufferName>,
@@ -324,9 +894,9 @@ impl GraphicsProvider {
             .find(|(_, s, _, _)| s.name() == target_render_scene)
         {
             let label = label.into();
+            Self::warn_shadowed_uniform(&self.global_uniforms, &label, target_render_scene);
             render_scene.create_uniform_buffer(device, label.clone(), contents, visibility);
-            self.uniform_buffers
-                .push((target_render_scene.clone(), label));
+            Self::register_uniform(&mut self.uniform_buffers, target_render_scene, &label);
         } else {
             panic!(
                 "Could not find any {:?} to attach {:?} to",
@@ -336,7 +906,161 @@ impl GraphicsProvider {
         }
     }
 
-    pub fn update_uniform_buffer(&self, label: &UniformBufferName, contents: &[u8]) {
+    ///Removes the uniform from the render scene, eg. to turn off an effect, and recreates its
+    ///pipeline. The @group of the following uniforms shifts down by one, so `shader_descriptor`
+    ///has to declare them there and must not declare the removed one. Returns false if there is
+    ///no such uniform on the render scene
+    pub fn remove_uniform_buffer(
+        &mut self,
+        render_scene_name: &RenderSceneName,
+        label: &UniformBufferName,
+        shader_descriptor: ShaderDescriptor,
+    ) -> bool {
+        let (device, texture_provider) = match (&self.device, &self.texture_provider) {
+            (Some(device), Some(texture_provider)) => (device, texture_provider),
+            _ => return false,
+        };
+        let (window_id, render_scene, shader, descriptor) = match self
+            .render_scenes
+            .iter_mut()
+            .find(|(_, s, _, _)| s.name() == render_scene_name)
+        {
+            Some(entry) => entry,
+            None => return false,
+        };
+        if !render_scene.remove_uniform_buffer(label) {
+            return false;
+        }
+        let window_id = window_id.clone();
+        let (_, surface) = self
+            .surfaces
+            .iter()
+            .find(|(id, _)| *id == window_id)
+            .expect(&format!("No surface on window {:?}", window_id));
+        let (new_shader, render_pipeline) = Self::pipeline(
+            &mut self.pipelines,
+            device,
+            surface.as_ref(),
+            texture_provider,
+            render_scene,
+            &shader_descriptor,
+        );
+        render_scene.update_pipeline(render_pipeline);
+        *shader = new_shader;
+        *descriptor = shader_descriptor;
+        self.uniform_buffers
+            .retain(|(r, u)| !(r == render_scene_name && u == label));
+        self.prune_pipelines();
+        self.mark_dirty(&window_id);
+        true
+    }
+
+    ///Updates by name only reach the first render scene with the uniform, until it is removed
+    fn register_uniform(
+        uniform_buffers: &mut Vec<(RenderSceneName, UniformBufferName)>,
+        render_scene: &RenderSceneName,
+        uniform: &UniformBufferName,
+    ) {
+        match uniform_buffers.iter().find(|(_, u)| u == uniform) {
+            Some((r, _)) if r == render_scene => {}
+            Some((r, _)) => {
+                warn!(
+                    "UniformBuffer {:?} of RenderScene {:?} is shadowed by the one of RenderScene {:?}",
+                    uniform, render_scene, r
+                );
+                uniform_buffers.push((render_scene.clone(), uniform.clone()));
+            }
+            None => uniform_buffers.push((render_scene.clone(), uniform.clone())),
+        }
+    }
+
+    ///Updates by name go to the global uniform, so the buffer of the render scene is never written
+    fn warn_shadowed_uniform(
+        global_uniforms: &Option<GlobalUniforms>,
+        uniform: &UniformBufferName,
+        render_scene: &RenderSceneName,
+    ) {
+        if global_uniforms.as_ref().is_some_and(|g| g.contains(uniform)) {
+            warn!(
+                "UniformBuffer {:?} of RenderScene {:?} is shadowed by the global uniform of the same name",
+                uniform, render_scene
+            );
+        }
+    }
+
+    ///Size in bytes of the uniform buffer, as created from its initial contents
+    pub fn uniform_buffer_size(&self, label: &UniformBufferName) -> Option<u64> {
+        if let Some(size) = self.global_uniforms.as_ref().and_then(|g| g.size(label)) {
+            return Some(size);
+        }
+        let (target_render_scene, _) = self.uniform_buffers.iter().find(|(_, u)| u == label)?;
+        self.render_scenes
+            .iter()
+            .find(|(_, s, _, _)| s.name() == target_render_scene)
+            .and_then(|(_, render_scene, _, _)| render_scene.uniform_buffer_size(label))
+    }
+
+    ///Warns about contents of another size than the buffer, false if they are too big to write
+    fn check_uniform_size(label: &UniformBufferName, size: u64, contents: &[u8]) -> bool {
+        if size == contents.len() as u64 {
+            return true;
+        }
+        if contents.len() as u64 > size {
+            error!(
+                "Cannot write {} bytes to UniformBuffer {:?} of {} bytes",
+                contents.len(),
+                label,
+                size
+            );
+            return false;
+        }
+        warn!(
+            "Writing {} bytes to UniformBuffer {:?} of {} bytes",
+            contents.len(),
+            label,
+            size
+        );
+        true
+    }
+
+    ///Like update_uniform_buffer, casting the value. The size of T has to match the buffer
+    pub fn update_uniform_typed<T: bytemuck::Pod>(
+        &mut self,
+        label: &UniformBufferName,
+        value: &T,
+    ) -> bool {
+        debug_assert!(
+            self.uniform_buffer_size(label)
+                .map_or(true, |size| size == std::mem::size_of::<T>() as u64),
+            "{} does not fit UniformBuffer {:?} of {:?} bytes",
+            std::any::type_name::<T>(),
+            label,
+            self.uniform_buffer_size(label)
+        );
+        self.update_uniform_buffer(label, bytemuck::bytes_of(value))
+    }
+
+    ///Returns false if there is no uniform buffer with the name, or if the contents do not fit
+    ///into it. Global uniforms are written once for all render scenes
+    pub fn update_uniform_buffer(&mut self, label: &UniformBufferName, contents: &[u8]) -> bool {
+        if let (Some(global_uniforms), Some(queue)) = (&self.global_uniforms, &self.queue) {
+            if let Some(size) = global_uniforms.size(label) {
+                if !Self::check_uniform_size(label, size, contents) {
+                    return false;
+                }
+            }
+            if global_uniforms.update(queue, label, contents) {
+                if let Some((_, initial, _)) = self
+                    .descriptor
+                    .global_uniforms
+                    .iter_mut()
+                    .find(|(name, _, _)| name == label)
+                {
+                    *initial = contents.to_vec();
+                }
+                return true;
+            }
+        }
         if let Some((target_render_scene, _)) =
             self.uniform_buffers.iter().find(|(_, u)| u == label)
         {
@@ -345,8 +1069,26 @@ impl GraphicsProvider {
                 .iter()
                 .find(|(_, s, _, _)| s.name() == target_render_scene)
                 .expect(&format!("RenderScene {:?} vanished", target_render_scene));
+            if let Some(size) = render_scene.uniform_buffer_size(label) {
+                if !Self::check_uniform_size(label, size, contents) {
+                    return false;
+                }
+            }
             let queue = self.queue.as_ref().expect("The queue vanished");
             render_scene.update_uniform_buffer(queue, label, contents);
+            true
+        } else {
+            warn!(
+                "No UniformBuffer {:?} to update. Known uniform buffers: {:?}",
+                label,
+                self.uniform_buffers
+                    .iter()
+                    .map(|(_, u)| u)
+                    .chain(self.global_uniforms.iter().flat_map(|g| g.names()))
+                    .map(|u| u.as_str())
+                    .collect::<Vec<_>>()
+            );
+            false
         }
     }
 }
@@ -1,40 +1,166 @@
+use std::sync::Arc;
+
 use wgpu::util::DeviceExt;
 
 use crate::create_name_struct;
 
-use super::{IndexBufferWriter, VertexBufferWriter, Visibility};
+use super::{accumulation::AccumulationTarget, IndexBufferWriter, VertexBufferWriter, Visibility};
 
 pub mod exports {
+    pub use super::BlendMode;
+    pub use super::SceneLoadOp;
     pub use super::RenderSceneDescriptor;
     pub use super::RenderSceneName;
+    pub use super::RenderStats;
+    pub use super::ScissorRect;
     pub use super::UniformBufferName;
 }
 
 create_name_struct!(RenderSceneName);
 create_name_struct!(UniformBufferName);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    ///Straight alpha, the color is not multiplied with its alpha
+    #[default]
+    Alpha,
+    ///The color is already multiplied with its alpha, see Color::premultiply and
+    ///GraphicsDescriptor::with_premultiplied_textures. Avoids dark fringes on scaled sprites
+    PremultipliedAlpha,
+}
+impl BlendMode {
+    pub(crate) fn blend_state(&self) -> wgpu::BlendState {
+        match self {
+            Self::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            Self::PremultipliedAlpha => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        }
+    }
+}
+
+///What a render scene is drawn onto
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SceneLoadOp {
+    ///The current frame, which starts cleared
+    #[default]
+    Clear,
+    ///Its own frames before, eg. for motion trails. The scene gets a persistent target, which is
+    ///composited over the scenes below it. Draw a translucent quad first to fade the trail. The
+    ///depth buffer is not shared with the other scenes
+    Load,
+}
+
+///Counts of the last frame rendered to a window
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    ///Visible render scenes with a pipeline, one draw call each
+    pub render_scenes_drawn: u32,
+    pub vertices: u32,
+    pub indices: u32,
+    ///Vertex and index buffers recreated because they grew, since the previous frame
+    pub buffer_reallocations: u32,
+}
+
+///Pixel rectangle of the surface, with the origin at the top left. Nothing of the render scene is
+///drawn outside of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScissorRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+impl ScissorRect {
+    ///Part of the rect inside the target, None if nothing is left
+    fn clamp(&self, target_size: (u32, u32)) -> Option<ScissorRect> {
+        let x = self.x.min(target_size.0);
+        let y = self.y.min(target_size.1);
+        let width = self.width.min(target_size.0 - x);
+        let height = self.height.min(target_size.1 - y);
+        if width == 0 || height == 0 {
+            None
+        } else {
+            Some(ScissorRect {
+                x,
+                y,
+                width,
+                height,
+            })
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RenderSceneDescriptor {
     pub index_format: wgpu::IndexFormat,
     pub vertex_buffer_layout: wgpu::VertexBufferLayout<'static>,
     pub use_textures: bool,
+    ///Width / height the scene is drawn at. Draws into the largest centered viewport of that
+    ///aspect ratio, leaving black bars, instead of filling the whole surface
+    pub target_aspect: Option<f32>,
+    ///Test and write depth, so fragments with a higher clip space z are drawn on top, regardless
+    ///of the draw order. The vertex shader has to output z in [0, 1]
+    pub use_depth: bool,
+    pub blend_mode: BlendMode,
+    ///Bind the global uniforms of the GraphicsDescriptor at the @group after the textures
+    pub use_global_uniforms: bool,
+    pub load_op: SceneLoadOp,
+}
+impl RenderSceneDescriptor {
+    ///x, y, width and height of the viewport of its render scenes on a target of the given size
+    pub fn viewport(&self, width: u32, height: u32) -> (f32, f32, f32, f32) {
+        letterbox(self.target_aspect, width, height)
+    }
+}
+
+fn letterbox(target_aspect: Option<f32>, width: u32, height: u32) -> (f32, f32, f32, f32) {
+    let (width, height) = (width as f32, height as f32);
+    match target_aspect {
+        Some(aspect) if aspect > 0.0 && height > 0.0 => {
+            if width / height > aspect {
+                let viewport_width = height * aspect;
+                ((width - viewport_width) / 2.0, 0.0, viewport_width, height)
+            } else {
+                let viewport_height = width / aspect;
+                (0.0, (height - viewport_height) / 2.0, width, viewport_height)
+            }
+        }
+        _ => (0.0, 0.0, width, height),
+    }
 }
 
 pub struct RenderScene {
     name: RenderSceneName,
-    render_pipeline: Option<wgpu::RenderPipeline>,
+    render_pipeline: Option<Arc<wgpu::RenderPipeline>>,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
+    ///Of the last update, the buffers can hold more
     num_indices: u32,
     num_vertices: u32,
+    ///Elements the buffers hold, they are only recreated to grow
+    index_capacity: u32,
+    vertex_capacity: u32,
+    ///Buffers recreated since the last RenderStats were recorded
+    reallocations: u32,
     index_format: wgpu::IndexFormat,
     vertex_buffer_layout: wgpu::VertexBufferLayout<'static>,
     use_textures: bool,
+    target_aspect: Option<f32>,
+    use_depth: bool,
+    blend_mode: BlendMode,
+    scissor: Option<ScissorRect>,
+    z_index: i32,
+    global_uniforms: Option<(Arc<wgpu::BindGroupLayout>, Arc<wgpu::BindGroup>)>,
+    ///Replaces the shared texture bind group without texture arrays
+    bound_texture: Option<Arc<wgpu::BindGroup>>,
+    load_op: SceneLoadOp,
+    ///Created on the first frame with SceneLoadOp::Load
+    accumulation: Option<AccumulationTarget>,
     uniform_buffers: Vec<(
         UniformBufferName,
         wgpu::Buffer,
         wgpu::BindGroupLayout,
         wgpu::BindGroup,
+        wgpu::ShaderStages,
     )>,
     visibility: Visibility,
 }
@@ -66,32 +192,118 @@ impl RenderScene {
             index_buffer,
             num_indices,
             num_vertices,
+            index_capacity: 0,
+            vertex_capacity: 0,
+            reallocations: 0,
             index_format: descriptor.index_format,
             vertex_buffer_layout: descriptor.vertex_buffer_layout,
             use_textures: descriptor.use_textures,
+            target_aspect: descriptor.target_aspect,
+            use_depth: descriptor.use_depth,
+            blend_mode: descriptor.blend_mode,
+            scissor: None,
+            z_index: 0,
+            global_uniforms: None,
+            bound_texture: None,
+            load_op: descriptor.load_op,
+            accumulation: None,
             uniform_buffers: Vec::new(),
             visibility: Visibility::Visible,
         }
     }
 
+    ///Binds the shared group before the own uniform buffers. Has to be set before the pipeline is
+    ///created
+    pub fn set_global_uniforms(
+        &mut self,
+        bind_group_layout: Arc<wgpu::BindGroupLayout>,
+        bind_group: Arc<wgpu::BindGroup>,
+    ) {
+        self.global_uniforms = Some((bind_group_layout, bind_group));
+    }
+
+    ///Draws with this texture instead of the default one, if texture arrays are unsupported
+    pub fn bind_texture(&mut self, bind_group: Arc<wgpu::BindGroup>) {
+        self.bound_texture = Some(bind_group);
+    }
+
+    ///Creates or resizes the accumulation target of scenes with SceneLoadOp::Load
+    pub(crate) fn ensure_accumulation(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+    ) {
+        if self.load_op == SceneLoadOp::Load {
+            AccumulationTarget::ensure(&mut self.accumulation, device, format, size);
+        }
+    }
+
+    ///The accumulation target, if the scene is visible and drawn onto its previous frames
+    pub(crate) fn accumulation(&self) -> Option<&AccumulationTarget> {
+        match self.visibility {
+            Visibility::Visible => self.accumulation.as_ref(),
+            Visibility::Hidden => None,
+        }
+    }
+
     pub fn set_visibility(&mut self, visibility: &Visibility) {
         self.visibility = visibility.clone();
     }
 
+    pub fn visibility(&self) -> &Visibility {
+        &self.visibility
+    }
+
     pub fn use_textures(&self) -> bool {
         self.use_textures
     }
 
+    pub fn use_depth(&self) -> bool {
+        self.use_depth
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    pub fn set_scissor(&mut self, scissor: Option<ScissorRect>) {
+        self.scissor = scissor;
+    }
+
+    pub fn scissor(&self) -> Option<&ScissorRect> {
+        self.scissor.as_ref()
+    }
+
+    ///Render scenes of a window are drawn in ascending z_index, so higher ones end up on top
+    pub fn set_z_index(&mut self, z_index: i32) {
+        self.z_index = z_index;
+    }
+
+    pub fn z_index(&self) -> i32 {
+        self.z_index
+    }
+
+    pub fn target_aspect(&self) -> Option<f32> {
+        self.target_aspect
+    }
+
+    ///x, y, width and height of the viewport on a target of the given size
+    pub fn viewport(&self, width: u32, height: u32) -> (f32, f32, f32, f32) {
+        letterbox(self.target_aspect, width, height)
+    }
+
     fn bind_groups<'a>(
         &'a self,
         texture_bind_group: &'a wgpu::BindGroup,
     ) -> Vec<&'a wgpu::BindGroup> {
         let mut bind_groups = if self.use_textures {
-            vec![texture_bind_group]
+            vec![self.bound_texture.as_deref().unwrap_or(texture_bind_group)]
         } else {
             Vec::new()
         };
-        bind_groups.extend(self.uniform_buffers.iter().map(|(_, _, _, bg)| bg));
+        bind_groups.extend(self.global_uniforms.iter().map(|(_, bg)| bg.as_ref()));
+        bind_groups.extend(self.uniform_buffers.iter().map(|(_, _, _, bg, _)| bg));
         bind_groups
     }
 
@@ -104,7 +316,8 @@ impl RenderScene {
         } else {
             Vec::new()
         };
-        bind_group_layouts.extend(self.uniform_buffers.iter().map(|(_, _, bgl, _)| bgl));
+        bind_group_layouts.extend(self.global_uniforms.iter().map(|(bgl, _)| bgl.as_ref()));
+        bind_group_layouts.extend(self.uniform_buffers.iter().map(|(_, _, bgl, _, _)| bgl));
         bind_group_layouts
     }
 
@@ -112,7 +325,7 @@ impl RenderScene {
         &self.vertex_buffer_layout
     }
 
-    pub fn update_pipeline(&mut self, render_pipeline: wgpu::RenderPipeline) {
+    pub fn update_pipeline(&mut self, render_pipeline: Arc<wgpu::RenderPipeline>) {
         self.render_pipeline = Some(render_pipeline);
     }
 
@@ -120,6 +333,16 @@ impl RenderScene {
         &self.name
     }
 
+    ///Indices drawn, 0 for an empty scene
+    pub fn num_indices(&self) -> u32 {
+        self.num_indices
+    }
+
+    ///Vertices of the last update, 0 for an empty scene
+    pub fn num_vertices(&self) -> u32 {
+        self.num_vertices
+    }
+
     pub fn update(
         &mut self,
         device: &wgpu::Device,
@@ -127,40 +350,76 @@ impl RenderScene {
         vertices: &impl VertexBufferWriter,
         indices: &impl IndexBufferWriter,
     ) {
-        if let Some((index_buffer, num_indices)) = indices.write_buffer(
+        if indices.buffer_data().is_some() {
+            self.num_indices = indices.buffer_len();
+        }
+        if let Some((index_buffer, index_capacity)) = indices.write_buffer(
             device,
             queue,
             &self.index_buffer,
-            self.num_indices,
+            self.index_capacity,
             wgpu::BufferUsages::INDEX,
-            self.num_indices > indices.buffer_len(),
+            false,
         ) {
             self.index_buffer = index_buffer;
-            self.num_indices = num_indices;
+            self.index_capacity = index_capacity;
+            self.reallocations += 1;
         };
-        if let Some((vertex_buffer, num_vertices)) = vertices.write_buffer(
+        if vertices.buffer_data().is_some() {
+            self.num_vertices = vertices.buffer_len();
+        }
+        if let Some((vertex_buffer, vertex_capacity)) = vertices.write_buffer(
             device,
             queue,
             &self.vertex_buffer,
-            self.num_vertices,
+            self.vertex_capacity,
             wgpu::BufferUsages::VERTEX,
             false,
         ) {
             self.vertex_buffer = vertex_buffer;
-            self.num_vertices = num_vertices;
+            self.vertex_capacity = vertex_capacity;
+            self.reallocations += 1;
         };
     }
 
+    ///Adds what write_render_pass draws to the stats
+    pub fn record_stats(&mut self, stats: &mut RenderStats) {
+        stats.buffer_reallocations += self.reallocations;
+        self.reallocations = 0;
+        if matches!(self.visibility, Visibility::Visible) && self.render_pipeline.is_some() {
+            stats.render_scenes_drawn += 1;
+            stats.vertices += self.num_vertices;
+            stats.indices += self.num_indices;
+        }
+    }
+
     pub fn write_render_pass<'a>(
         &'a self,
         render_pass: &mut wgpu::RenderPass<'a>,
         texture_bind_group: &'a wgpu::BindGroup,
+        target_size: (u32, u32),
     ) {
         match self.visibility {
             Visibility::Hidden => return,
             Visibility::Visible => (),
         };
         if let Some(render_pipeline) = &self.render_pipeline {
+            let (x, y, width, height) = self.viewport(target_size.0, target_size.1);
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            //The scissor rect persists between the scenes of a render pass, so it is always set
+            let scissor = match &self.scissor {
+                Some(scissor) => match scissor.clamp(target_size) {
+                    Some(scissor) => scissor,
+                    None => return,
+                },
+                None => ScissorRect {
+                    x: 0,
+                    y: 0,
+                    width: target_size.0,
+                    height: target_size.1,
+                },
+            };
+            render_pass.set_scissor_rect(scissor.x, scissor.y, scissor.width, scissor.height);
             render_pass.set_pipeline(render_pipeline);
             let bind_groups = self.bind_groups(texture_bind_group);
             for (i, bind_group) in bind_groups.iter().enumerate() {
@@ -174,6 +433,8 @@ impl RenderScene {
         }
     }
 
+    ///A uniform buffer of the same name is replaced, as long as its visibility stays the same. The
+    ///pipeline was created with the old bind group layout, which only matches the new one then
     pub fn create_uniform_buffer(
         &mut self,
         device: &wgpu::Device,
@@ -182,6 +443,17 @@ impl RenderScene {
         visibility: wgpu::ShaderStages,
     ) {
         let label: UniformBufferName = label.into();
+        if let Some((_, _, _, _, existing)) =
+            self.uniform_buffers.iter().find(|(n, _, _, _, _)| *n == label)
+        {
+            if *existing != visibility {
+                log::error!(
+                    "RenderScene {:?} already has a UniformBuffer {:?} visible to {:?}, not replacing it with one visible to {:?}",
+                    self.name, label, existing, visibility
+                );
+                return;
+            }
+        }
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(label.as_str()),
             contents,
@@ -208,8 +480,48 @@ impl RenderScene {
                 resource: buffer.as_entire_binding(),
             }],
         });
+        //Replaced in place, so the @group of the uniform stays the same
+        match self.uniform_buffers.iter_mut().find(|(n, _, _, _, _)| *n == label) {
+            Some(existing) => {
+                log::warn!(
+                    "RenderScene {:?} already has a UniformBuffer {:?}. Replacing it",
+                    self.name, label
+                );
+                *existing = (label, buffer, bind_group_layout, bind_group, visibility);
+            }
+            None => self
+                .uniform_buffers
+                .push((label, buffer, bind_group_layout, bind_group, visibility)),
+        }
+    }
+
+    pub fn uniform_buffer_size(&self, name: &UniformBufferName) -> Option<u64> {
         self.uniform_buffers
-            .push((label.clone(), buffer, bind_group_layout, bind_group));
+            .iter()
+            .find(|(n, _, _, _, _)| n == name)
+            .map(|(_, buffer, _, _, _)| buffer.size())
+    }
+
+    ///Shifts the @group of the uniforms after it down by one. The pipeline has to be recreated
+    ///with the new bind group layouts, and its shader must not declare the uniform anymore.
+    ///Returns false if the render scene has no such uniform
+    pub fn remove_uniform_buffer(&mut self, name: &UniformBufferName) -> bool {
+        let len = self.uniform_buffers.len();
+        self.uniform_buffers.retain(|(n, _, _, _, _)| n != name);
+        self.uniform_buffers.len() != len
+    }
+
+    ///In the order of their @group
+    pub fn uniform_names(&self) -> impl Iterator<Item = &UniformBufferName> {
+        self.uniform_buffers.iter().map(|(n, _, _, _, _)| n)
+    }
+
+    pub fn uniform_visibilities(&self) -> Vec<wgpu::ShaderStages> {
+        self.uniform_buffers.iter().map(|(_, _, _, _, v)| *v).collect()
+    }
+
+    pub fn use_global_uniforms(&self) -> bool {
+        self.global_uniforms.is_some()
     }
 
     pub fn update_uniform_buffer(
@@ -218,11 +530,38 @@ impl RenderScene {
         name: &UniformBufferName,
         data: &[u8],
     ) {
-        let (_, buffer, _, _) = self
+        let (_, buffer, _, _, _) = self
             .uniform_buffers
             .iter()
-            .find(|(n, _, _, _)| n == name)
+            .find(|(n, _, _, _, _)| n == name)
             .expect("Uniform buffer not found");
         queue.write_buffer(buffer, 0, data);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> ScissorRect {
+        ScissorRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn scissor_clamp() {
+        let target = (100, 50);
+        assert_eq!(rect(10, 10, 20, 20).clamp(target), Some(rect(10, 10, 20, 20)));
+        //Partly outside to the right and the bottom
+        assert_eq!(rect(90, 40, 20, 20).clamp(target), Some(rect(90, 40, 10, 10)));
+        assert_eq!(rect(0, 0, 500, 500).clamp(target), Some(rect(0, 0, 100, 50)));
+        //Fully outside or empty
+        assert_eq!(rect(100, 10, 20, 20).clamp(target), None);
+        assert_eq!(rect(200, 200, 20, 20).clamp(target), None);
+        assert_eq!(rect(10, 10, 0, 20).clamp(target), None);
+    }
+}
@@ -1,5 +1,7 @@
+use std::{error::Error, fmt::Display, fs};
+
 pub mod exports {
-    pub use super::ShaderDescriptor;
+    pub use super::{ShaderDescriptor, ShaderDescriptorBuilder, ShaderDescriptorError};
 }
 
 #[derive(Debug, Clone)]
@@ -13,4 +15,449 @@ pub struct ShaderDescriptor {
     ///index uniforms.len()
     pub uniforms: &'static [&'static str],
 }
+impl ShaderDescriptor {
+    ///Parses the shader file and checks that the entry points exist
+    pub fn validate(&self) -> Result<(), ShaderDescriptorError> {
+        let source = fs::read_to_string(self.file)
+            .map_err(|e| ShaderDescriptorError::UnreadableFile(self.file, e.to_string()))?;
+        self.validate_source(&source)
+    }
+
+    pub fn validate_source(&self, source: &str) -> Result<(), ShaderDescriptorError> {
+        let module = self.parse(source)?;
+        self.validate_module(&module)
+    }
+
+    pub(crate) fn validate_module(&self, module: &naga::Module) -> Result<(), ShaderDescriptorError> {
+        check_entry_point(
+            self.file,
+            module,
+            naga::ShaderStage::Vertex,
+            self.vertex_shader,
+        )?;
+        check_entry_point(
+            self.file,
+            module,
+            naga::ShaderStage::Fragment,
+            self.fragment_shader,
+        )?;
+        let found = uniform_groups(module).len();
+        if found < self.uniforms.len() {
+            return Err(ShaderDescriptorError::UniformCountMismatch(
+                self.file,
+                self.uniforms.len(),
+                found,
+            ));
+        }
+        self.check_uniform_bindings(module)
+    }
+
+    ///Every UniformBuffer is bound alone at @binding(0) of its @group. Only the global uniforms
+    ///share a @group, so at most one @group may hold several uniforms
+    fn check_uniform_bindings(&self, module: &naga::Module) -> Result<(), ShaderDescriptorError> {
+        let bindings = module
+            .global_variables
+            .iter()
+            .filter(|(_, variable)| variable.space == naga::AddressSpace::Uniform)
+            .filter_map(|(_, variable)| {
+                variable.binding.as_ref().map(|b| {
+                    (
+                        b.group,
+                        b.binding,
+                        variable.name.clone().unwrap_or_else(|| "<unnamed>".to_string()),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        let shared_groups = uniform_groups(module)
+            .into_iter()
+            .filter(|group| bindings.iter().filter(|(g, _, _)| g == group).count() > 1)
+            .collect::<Vec<_>>();
+        let mut mismatches = Vec::new();
+        if shared_groups.len() > 1 {
+            mismatches.push(format!(
+                "Only the global uniforms share a @group, but @groups {:?} hold several uniforms",
+                shared_groups
+            ));
+        }
+        for (group, binding, variable) in bindings.iter() {
+            if *binding != 0 && !shared_groups.contains(group) {
+                mismatches.push(format!(
+                    "Shader uniform '{}' is the only one at @group({}), but uses @binding({}) instead of @binding(0)",
+                    variable, group, binding
+                ));
+            }
+        }
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(ShaderDescriptorError::UniformGroupMismatch(
+                self.file, mismatches,
+            ))
+        }
+    }
+
+    ///Checks that the uniform at index i of `uniforms` is declared at @group(group_offset + i)
+    ///@binding(0) in the shader and that the shader has no uniforms which are not backed by a
+    ///buffer. `group_offset` is the amount of bind groups preceding the uniforms, eg. the texture
+    ///bind group. Variables at the `global_group` belong to the global uniforms and are not checked
+    pub(crate) fn check_uniform_groups(
+        &self,
+        module: &naga::Module,
+        uniforms: &[&str],
+        group_offset: u32,
+        global_group: Option<u32>,
+    ) -> Result<(), ShaderDescriptorError> {
+        let shader_uniforms = module
+            .global_variables
+            .iter()
+            .filter(|(_, variable)| variable.space == naga::AddressSpace::Uniform)
+            .filter_map(|(_, variable)| {
+                variable.binding.as_ref().map(|b| {
+                    (
+                        b.group,
+                        b.binding,
+                        variable.name.clone().unwrap_or_else(|| "<unnamed>".to_string()),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut mismatches = Vec::new();
+        for (i, name) in uniforms.iter().enumerate() {
+            let group = group_offset + i as u32;
+            match shader_uniforms.iter().find(|(g, _, _)| *g == group) {
+                None => mismatches.push(format!(
+                    "UniformBuffer '{}' is bound to @group({}), but the shader declares no uniform there",
+                    name, group
+                )),
+                Some((_, binding, variable)) if *binding != 0 => mismatches.push(format!(
+                    "UniformBuffer '{}' is bound to @group({}) @binding(0), but the shader declares '{}' at @binding({})",
+                    name, group, variable, binding
+                )),
+                Some(_) => {}
+            }
+        }
+        let end = group_offset + uniforms.len() as u32;
+        for (group, _, variable) in shader_uniforms.iter() {
+            if Some(*group) == global_group {
+                continue;
+            }
+            if *group < group_offset {
+                mismatches.push(format!(
+                    "Shader uniform '{}' at @group({}) collides with the texture bind group",
+                    variable, group
+                ));
+            } else if *group >= end {
+                mismatches.push(format!(
+                    "Shader uniform '{}' at @group({}) is not backed by a UniformBuffer ({} declared, starting at @group({}))",
+                    variable,
+                    group,
+                    uniforms.len(),
+                    group_offset
+                ));
+            }
+        }
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(ShaderDescriptorError::UniformGroupMismatch(
+                self.file, mismatches,
+            ))
+        }
+    }
+
+    pub(crate) fn parse(&self, source: &str) -> Result<naga::Module, ShaderDescriptorError> {
+        naga::front::wgsl::parse_str(source).map_err(|e| {
+            ShaderDescriptorError::InvalidShader(self.file, e.emit_to_string(source))
+        })
+    }
+}
+
+///Returns the sorted, deduplicated @group indices of all uniform variables in the module
+pub(crate) fn uniform_groups(module: &naga::Module) -> Vec<u32> {
+    let mut groups = module
+        .global_variables
+        .iter()
+        .filter(|(_, variable)| variable.space == naga::AddressSpace::Uniform)
+        .filter_map(|(_, variable)| variable.binding.as_ref().map(|b| b.group))
+        .collect::<Vec<_>>();
+    groups.sort();
+    groups.dedup();
+    groups
+}
+
+fn check_entry_point(
+    file: &'static str,
+    module: &naga::Module,
+    stage: naga::ShaderStage,
+    name: &'static str,
+) -> Result<(), ShaderDescriptorError> {
+    if module
+        .entry_points
+        .iter()
+        .any(|entry_point| entry_point.stage == stage && entry_point.name == name)
+    {
+        Ok(())
+    } else {
+        let available = module
+            .entry_points
+            .iter()
+            .filter(|entry_point| entry_point.stage == stage)
+            .map(|entry_point| entry_point.name.clone())
+            .collect();
+        Err(ShaderDescriptorError::MissingEntryPoint(
+            file,
+            format!("{:?}", stage),
+            name,
+            available,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderDescriptorError {
+    MissingField(&'static str),
+    UnreadableFile(&'static str, String),
+    InvalidShader(&'static str, String),
+    ///File, stage, requested entry point and the available entry points of that stage
+    MissingEntryPoint(&'static str, String, &'static str, Vec<String>),
+    ///File, declared uniforms and uniform groups found in the shader
+    UniformCountMismatch(&'static str, usize, usize),
+    ///File and a description of every mismatch between the uniforms and the shader's @groups
+    UniformGroupMismatch(&'static str, Vec<String>),
+}
+impl Display for ShaderDescriptorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderDescriptorError::MissingField(field) => {
+                write!(f, "ShaderDescriptorBuilder is missing the field '{}'", field)
+            }
+            ShaderDescriptorError::UnreadableFile(file, error) => {
+                write!(f, "Could not read shader '{}': {}", file, error)
+            }
+            ShaderDescriptorError::InvalidShader(file, error) => {
+                write!(f, "Could not parse shader '{}':\n{}", file, error)
+            }
+            ShaderDescriptorError::MissingEntryPoint(file, stage, name, available) => {
+                write!(
+                    f,
+                    "No {} entry point '{}' in shader '{}'. Available: {:?}",
+                    stage, name, file, available
+                )
+            }
+            ShaderDescriptorError::UniformCountMismatch(file, declared, found) => {
+                write!(
+                    f,
+                    "{} uniforms declared for shader '{}', but it only has {} uniform @groups",
+                    declared, file, found
+                )
+            }
+            ShaderDescriptorError::UniformGroupMismatch(file, mismatches) => {
+                write!(
+                    f,
+                    "Uniform @groups of shader '{}' do not match its UniformBuffers:\n{}",
+                    file,
+                    mismatches.join("\n")
+                )
+            }
+        }
+    }
+}
+impl Error for ShaderDescriptorError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShaderDescriptorBuilder {
+    file: Option<&'static str>,
+    vertex_shader: Option<&'static str>,
+    fragment_shader: Option<&'static str>,
+    uniforms: &'static [&'static str],
+}
+impl ShaderDescriptorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file(mut self, file: &'static str) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    pub fn vertex(mut self, entry_point: &'static str) -> Self {
+        self.vertex_shader = Some(entry_point);
+        self
+    }
+
+    pub fn fragment(mut self, entry_point: &'static str) -> Self {
+        self.fragment_shader = Some(entry_point);
+        self
+    }
+
+    ///Uniforms are assigned their @group in the order of the slice
+    pub fn uniforms(mut self, uniforms: &'static [&'static str]) -> Self {
+        self.uniforms = uniforms;
+        self
+    }
+
+    ///Reads and parses the shader file to check the entry points and uniforms against it
+    pub fn build(self) -> Result<ShaderDescriptor, ShaderDescriptorError> {
+        let descriptor = ShaderDescriptor {
+            file: self.file.ok_or(ShaderDescriptorError::MissingField("file"))?,
+            vertex_shader: self
+                .vertex_shader
+                .ok_or(ShaderDescriptorError::MissingField("vertex"))?,
+            fragment_shader: self
+                .fragment_shader
+                .ok_or(ShaderDescriptorError::MissingField("fragment"))?,
+            uniforms: self.uniforms,
+        };
+        descriptor.validate()?;
+        Ok(descriptor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/triangle.wgsl");
+
+    fn descriptor(uniforms: &'static [&'static str]) -> ShaderDescriptor {
+        ShaderDescriptor {
+            file: "test.wgsl",
+            vertex_shader: "vs_main",
+            fragment_shader: "fs_main",
+            uniforms,
+        }
+    }
+
+    fn shader(uniforms: &str) -> String {
+        format!(
+            "struct Data {{ value: vec4<f32> }}
+{}
+@vertex
+fn vs_main() -> @builtin(position) vec4<f32> {{
+    return vec4<f32>(0.0);
+}}
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {{
+    return vec4<f32>(1.0);
+}}",
+            uniforms
+        )
+    }
 
+    #[test]
+    fn build() {
+        let descriptor = ShaderDescriptorBuilder::new()
+            .file(TRIANGLE)
+            .vertex("vs_main")
+            .fragment("fs_main")
+            .build()
+            .expect("The triangle shader should be valid");
+        assert_eq!(descriptor.file, TRIANGLE);
+        assert!(descriptor.uniforms.is_empty());
+    }
+
+    #[test]
+    fn build_errors() {
+        let builder = ShaderDescriptorBuilder::new().file(TRIANGLE).vertex("vs_main");
+        assert!(matches!(
+            builder.clone().build(),
+            Err(ShaderDescriptorError::MissingField("fragment"))
+        ));
+        assert!(matches!(
+            builder.clone().fragment("missing").build(),
+            Err(ShaderDescriptorError::MissingEntryPoint(_, _, "missing", _))
+        ));
+        assert!(matches!(
+            builder.fragment("fs_main").uniforms(&["Camera"]).build(),
+            Err(ShaderDescriptorError::UniformCountMismatch(_, 1, 0))
+        ));
+        assert!(matches!(
+            ShaderDescriptorBuilder::new().file("missing.wgsl").vertex("a").fragment("b").build(),
+            Err(ShaderDescriptorError::UnreadableFile("missing.wgsl", _))
+        ));
+    }
+
+    #[test]
+    fn uniform_bindings() {
+        let descriptor = descriptor(&["A", "B"]);
+        let valid = shader(
+            "@group(0) @binding(0) var<uniform> a: Data;
+@group(1) @binding(0) var<uniform> b: Data;",
+        );
+        assert!(descriptor.validate_source(&valid).is_ok());
+        let wrong_binding = shader(
+            "@group(0) @binding(0) var<uniform> a: Data;
+@group(1) @binding(1) var<uniform> b: Data;",
+        );
+        match descriptor.validate_source(&wrong_binding) {
+            Err(ShaderDescriptorError::UniformGroupMismatch(_, mismatches)) => {
+                assert_eq!(mismatches.len(), 1)
+            }
+            result => panic!("Expected a binding mismatch, got {:?}", result),
+        }
+        let global = shader(
+            "@group(0) @binding(0) var<uniform> a: Data;
+@group(0) @binding(1) var<uniform> time: Data;
+@group(1) @binding(0) var<uniform> b: Data;",
+        );
+        assert!(descriptor.validate_source(&global).is_ok());
+        let two_shared = shader(
+            "@group(0) @binding(0) var<uniform> a: Data;
+@group(0) @binding(1) var<uniform> c: Data;
+@group(1) @binding(0) var<uniform> b: Data;
+@group(1) @binding(1) var<uniform> d: Data;",
+        );
+        assert!(descriptor.validate_source(&two_shared).is_err());
+    }
+
+    fn check(source: &str, group_offset: u32, global_group: Option<u32>) -> Vec<String> {
+        let descriptor = descriptor(&["Camera", "Tint"]);
+        let module = descriptor.parse(&shader(source)).expect("The shader should parse");
+        let uniforms = &descriptor.uniforms;
+        match descriptor.check_uniform_groups(&module, uniforms, group_offset, global_group) {
+            Ok(()) => vec![],
+            Err(ShaderDescriptorError::UniformGroupMismatch(_, mismatches)) => mismatches,
+            Err(e) => panic!("Unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn uniform_groups_match() {
+        let uniforms = "@group(1) @binding(0) var<uniform> camera: Data;
+@group(2) @binding(0) var<uniform> tint: Data;";
+        assert!(check(uniforms, 1, None).is_empty());
+        assert_eq!(check(uniforms, 0, None).len(), 2);
+    }
+
+    #[test]
+    fn uniform_group_missing() {
+        let mismatches = check("@group(1) @binding(0) var<uniform> camera: Data;", 1, None);
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].contains("'Tint'"), "{}", mismatches[0]);
+    }
+
+    #[test]
+    fn uniform_group_wrong() {
+        let uniforms = "@group(1) @binding(0) var<uniform> camera: Data;
+@group(3) @binding(0) var<uniform> tint: Data;";
+        let mismatches = check(uniforms, 1, None);
+        assert_eq!(mismatches.len(), 2);
+        assert!(mismatches.iter().any(|m| m.contains("'Tint'")));
+        assert!(mismatches.iter().any(|m| m.contains("'tint' at @group(3)")));
+    }
+
+    #[test]
+    fn uniform_groups_after_global_uniforms() {
+        //Textures at @group(0), the global uniforms at @group(1)
+        let uniforms = "@group(1) @binding(0) var<uniform> time: Data;
+@group(1) @binding(1) var<uniform> screen: Data;
+@group(2) @binding(0) var<uniform> camera: Data;
+@group(3) @binding(0) var<uniform> tint: Data;";
+        assert!(check(uniforms, 2, Some(1)).is_empty());
+        let mismatches = check(uniforms, 2, None);
+        assert_eq!(mismatches.len(), 2);
+        assert!(mismatches.iter().all(|m| m.contains("collides with the texture bind group")));
+    }
+}
@@ -1,71 +1,80 @@
 use super::ShaderDescriptor;
-use std::fmt::Debug;
+use std::{fmt::Debug, iter, sync::mpsc};
 
-use super::RenderScene;
+use super::{upscaler::Upscaler, RenderScene};
 
 pub  mod exports {
 }
 
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+///Depth buffer matching the size of a render target. Cleared to 0.0 every frame
+#[derive(Debug)]
+struct DepthBuffer {
+    width: u32,
+    height: u32,
+    view: wgpu::TextureView,
+}
+impl DepthBuffer {
+    ///Reuses the buffer if it still matches the size
+    fn ensure(
+        depth_buffer: &mut Option<DepthBuffer>,
+        device: &wgpu::Device,
+        (width, height): (u32, u32),
+    ) -> &wgpu::TextureView {
+        let stale = match depth_buffer {
+            Some(buffer) => buffer.width != width || buffer.height != height,
+            None => true,
+        };
+        if stale {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Depth Buffer"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            *depth_buffer = Some(DepthBuffer {
+                width,
+                height,
+                view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            });
+        }
+        &depth_buffer.as_ref().expect("Depth buffer vanished").view
+    }
+}
+
 pub trait WindowSurface: Debug {
-    fn surface<'a, 'b: 'a>(&'b self) -> &'a wgpu::Surface<'a>;
     fn config(&self) -> &wgpu::SurfaceConfiguration;
     fn config_mut(&mut self) -> &mut wgpu::SurfaceConfiguration;
+    ///Apply the current config to the render target
+    fn configure(&mut self, device: &wgpu::Device);
+    ///Render at the internal resolution of the upscaler, instead of the size of the target
+    fn set_upscaler(&mut self, upscaler: Upscaler);
     fn resize(&mut self, new_size: &winit::dpi::PhysicalSize<u32>, device: &wgpu::Device) {
         if new_size.width == 0 || new_size.height == 0 {
             return;
         }
         self.config_mut().width = new_size.width;
         self.config_mut().height = new_size.height;
-        self.surface().configure(device, self.config());
+        self.configure(device);
     }
     fn create_render_pipeline<'a>(
-        &self,
-        device: &wgpu::Device,
-        bind_group_layout: &[&wgpu::BindGroupLayout],
-        shader: &wgpu::ShaderModule,
-        shader_descriptor: &ShaderDescriptor,
-        vertex_buffer_layout: wgpu::VertexBufferLayout<'a>,
-    ) -> wgpu::RenderPipeline;
-    fn render(
-        &mut self,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        render_scenes: &[&RenderScene],
-        texture_bind_group: &wgpu::BindGroup,
-    );
-}
-
-pub struct Surface<'a> {
-    pub wgpu_surface: wgpu::Surface<'a>,
-    pub config: wgpu::SurfaceConfiguration,
-}
-impl Debug for Surface<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Surface")
-            .field("config", &self.config)
-            .finish()
-    }
-}
-impl<'a> WindowSurface for Surface<'a> {
-    fn surface<'b, 'c: 'b>(&'c self) -> &'b wgpu::Surface<'b> {
-        &self.wgpu_surface
-    }
-
-    fn config(&self) -> &wgpu::SurfaceConfiguration {
-        &self.config
-    }
-
-    fn config_mut(&mut self) -> &mut wgpu::SurfaceConfiguration {
-        &mut self.config
-    }
-
-    fn create_render_pipeline<'b>(
         &self,
         device: &wgpu::Device,
         bind_group_layouts: &[&wgpu::BindGroupLayout],
         shader: &wgpu::ShaderModule,
         shader_descriptor: &ShaderDescriptor,
-        vertex_buffer_layout: wgpu::VertexBufferLayout<'b>,
+        vertex_buffer_layout: wgpu::VertexBufferLayout<'a>,
+        use_depth: bool,
+        blend: wgpu::BlendState,
     ) -> wgpu::RenderPipeline {
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Pipeline Layout"),
@@ -84,8 +93,8 @@ impl<'a> WindowSurface for Surface<'a> {
                 module: shader,
                 entry_point: shader_descriptor.fragment_shader,
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: self.config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    format: self.config().format,
+                    blend: Some(blend),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -99,7 +108,18 @@ impl<'a> WindowSurface for Surface<'a> {
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: None,
+            //Every render pass has a depth attachment, scenes without depth ignore it
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: use_depth,
+                depth_compare: if use_depth {
+                    wgpu::CompareFunction::GreaterEqual
+                } else {
+                    wgpu::CompareFunction::Always
+                },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
             multisample: wgpu::MultisampleState {
                 count: 1,
                 mask: !0,
@@ -110,6 +130,186 @@ impl<'a> WindowSurface for Surface<'a> {
 
         render_pipeline
     }
+    fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        render_scenes: &[&RenderScene],
+        texture_bind_group: &wgpu::BindGroup,
+    );
+    ///Read back the last rendered frame as tightly packed rows of 4 byte pixels. Only supported
+    ///by offscreen targets
+    fn read_pixels(&self, _device: &wgpu::Device, _queue: &wgpu::Queue) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+fn encode_render_pass<'a>(
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    depth_view: &wgpu::TextureView,
+    target_size: (u32, u32),
+    render_scenes: &[&'a RenderScene],
+    texture_bind_group: &'a wgpu::BindGroup,
+) {
+    //The bars around letterboxed scenes are left in the clear color
+    let clear_color = if render_scenes
+        .iter()
+        .any(|render_scene| render_scene.target_aspect().is_some())
+    {
+        wgpu::Color::BLACK
+    } else {
+        wgpu::Color::WHITE
+    };
+    //Scenes drawn onto their previous frames split the pass, so they are composited in z order.
+    //Each pass clears the depth buffer, so depth tested scenes only occlude each other within the
+    //same pass, eg. not across an accumulating scene between them
+    let mut load = wgpu::LoadOp::Clear(clear_color);
+    let mut start = 0;
+    for (i, render_scene) in render_scenes.iter().enumerate() {
+        if let Some(accumulation) = render_scene.accumulation() {
+            draw_pass(
+                encoder,
+                view,
+                depth_view,
+                target_size,
+                &render_scenes[start..i],
+                texture_bind_group,
+                load,
+            );
+            accumulation.accumulate(encoder, depth_view, render_scene, texture_bind_group);
+            accumulation.composite(encoder, view);
+            load = wgpu::LoadOp::Load;
+            start = i + 1;
+        }
+    }
+    //Without accumulating scenes this also clears an empty frame
+    if start == 0 || start < render_scenes.len() {
+        draw_pass(
+            encoder,
+            view,
+            depth_view,
+            target_size,
+            &render_scenes[start..],
+            texture_bind_group,
+            load,
+        );
+    }
+}
+
+fn draw_pass<'a>(
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    depth_view: &wgpu::TextureView,
+    target_size: (u32, u32),
+    render_scenes: &[&'a RenderScene],
+    texture_bind_group: &'a wgpu::BindGroup,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Render Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: depth_view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0.0),
+                store: wgpu::StoreOp::Discard,
+            }),
+            stencil_ops: None,
+        }),
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+
+    for render_scene in render_scenes {
+        render_scene.write_render_pass(&mut render_pass, texture_bind_group, target_size);
+    }
+}
+
+///Renders the scenes to the view, or to the internal resolution of the upscaler and blits that
+fn encode_frame(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    target_size: (u32, u32),
+    depth_buffer: &mut Option<DepthBuffer>,
+    upscaler: Option<&Upscaler>,
+    render_scenes: &[&RenderScene],
+    texture_bind_group: &wgpu::BindGroup,
+) {
+    match upscaler {
+        Some(upscaler) => {
+            let depth_view = DepthBuffer::ensure(depth_buffer, device, upscaler.size());
+            encode_render_pass(
+                encoder,
+                upscaler.view(),
+                depth_view,
+                upscaler.size(),
+                render_scenes,
+                texture_bind_group,
+            );
+            upscaler.blit(encoder, view, target_size);
+        }
+        None => {
+            let depth_view = DepthBuffer::ensure(depth_buffer, device, target_size);
+            encode_render_pass(
+                encoder,
+                view,
+                depth_view,
+                target_size,
+                render_scenes,
+                texture_bind_group,
+            );
+        }
+    }
+}
+
+pub struct Surface<'a> {
+    pub wgpu_surface: wgpu::Surface<'a>,
+    pub config: wgpu::SurfaceConfiguration,
+    depth_buffer: Option<DepthBuffer>,
+    upscaler: Option<Upscaler>,
+}
+impl<'a> Surface<'a> {
+    pub fn new(wgpu_surface: wgpu::Surface<'a>, config: wgpu::SurfaceConfiguration) -> Self {
+        Self {
+            wgpu_surface,
+            config,
+            depth_buffer: None,
+            upscaler: None,
+        }
+    }
+}
+impl Debug for Surface<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Surface")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+impl<'a> WindowSurface for Surface<'a> {
+    fn config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.config
+    }
+
+    fn config_mut(&mut self) -> &mut wgpu::SurfaceConfiguration {
+        &mut self.config
+    }
+
+    fn configure(&mut self, device: &wgpu::Device) {
+        self.wgpu_surface.configure(device, &self.config);
+    }
+
+    fn set_upscaler(&mut self, upscaler: Upscaler) {
+        self.upscaler = Some(upscaler);
+    }
 
     fn render(
         &mut self,
@@ -119,7 +319,7 @@ impl<'a> WindowSurface for Surface<'a> {
         texture_bind_group: &wgpu::BindGroup,
     ) {
         let output = self
-            .surface()
+            .wgpu_surface
             .get_current_texture()
             .expect("Our food has no texture");
         let view = output
@@ -129,28 +329,177 @@ impl<'a> WindowSurface for Surface<'a> {
             label: Some("Render Encoder"),
         });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+        encode_frame(
+            device,
+            &mut encoder,
+            &view,
+            (self.config.width, self.config.height),
+            &mut self.depth_buffer,
+            self.upscaler.as_ref(),
+            render_scenes,
+            texture_bind_group,
+        );
+
+        queue.submit(iter::once(encoder.finish()));
+        output.present();
+    }
+}
 
-            for render_scene in render_scenes {
-                render_scene.write_render_pass(&mut render_pass, texture_bind_group);
-            }
+///Offscreen render target standing in for a window, eg. in tests
+pub struct HeadlessSurface {
+    pub texture: wgpu::Texture,
+    pub config: wgpu::SurfaceConfiguration,
+    depth_buffer: Option<DepthBuffer>,
+    upscaler: Option<Upscaler>,
+}
+impl HeadlessSurface {
+    ///Only 4 byte rgba formats can be read back with read_pixels
+    pub fn new(
+        device: &wgpu::Device,
+        size: &winit::dpi::PhysicalSize<u32>,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        Self {
+            texture: Self::create_texture(device, &config),
+            config,
+            depth_buffer: None,
+            upscaler: None,
         }
+    }
 
-        queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+    fn create_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Surface Texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+            view_formats: &[],
+        })
+    }
+}
+impl Debug for HeadlessSurface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeadlessSurface")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+impl WindowSurface for HeadlessSurface {
+    fn config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.config
+    }
+
+    fn config_mut(&mut self) -> &mut wgpu::SurfaceConfiguration {
+        &mut self.config
+    }
+
+    fn configure(&mut self, device: &wgpu::Device) {
+        self.texture = Self::create_texture(device, &self.config);
+    }
+
+    fn set_upscaler(&mut self, upscaler: Upscaler) {
+        self.upscaler = Some(upscaler);
+    }
+
+    fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        render_scenes: &[&RenderScene],
+        texture_bind_group: &wgpu::BindGroup,
+    ) {
+        let view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Render Encoder"),
+        });
+
+        encode_frame(
+            device,
+            &mut encoder,
+            &view,
+            (self.config.width, self.config.height),
+            &mut self.depth_buffer,
+            self.upscaler.as_ref(),
+            render_scenes,
+            texture_bind_group,
+        );
+
+        queue.submit(iter::once(encoder.finish()));
+    }
+
+    fn read_pixels(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Vec<u8>> {
+        let (width, height) = (self.config.width, self.config.height);
+        let unpadded_bytes_per_row = 4 * width;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (unpadded_bytes_per_row + alignment - 1) / alignment * alignment;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Readback Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        match receiver.recv() {
+            Ok(Ok(())) => {}
+            _ => return None,
+        }
+        let pixels = {
+            let data = slice.get_mapped_range();
+            data.chunks(padded_bytes_per_row as usize)
+                .flat_map(|row| row[..unpadded_bytes_per_row as usize].iter().copied())
+                .collect::<Vec<_>>()
+        };
+        buffer.unmap();
+        Some(pixels)
     }
 }
@@ -1,55 +1,64 @@
-use std::{fs, num::NonZeroU32, path::Path};
+use std::{fs, num::NonZeroU32, path::Path, sync::Arc};
 
 use image::GenericImageView;
+use log::{error, warn};
+
+use super::DefaultTexture;
+use crate::game_engine::Color;
 
 pub mod exports {
-    pub use super::DEFAULT_TEXTURE;
+    pub use super::{DEFAULT_TEXTURE, PLACEHOLDER_TEXTURE, PLACEHOLDER_TEXTURE_INDEX};
 }
 
 pub const DEFAULT_TEXTURE: &str = "Default Texture Provider Texture";
+///Opaque magenta, drawn in place of sprite sheets that are not loaded
+pub const PLACEHOLDER_TEXTURE: &str = "Placeholder Texture Provider Texture";
+pub const PLACEHOLDER_TEXTURE_INDEX: u32 = 1;
 
+///With texture arrays all textures are bound at once and indexed in the shader. Without them one
+///texture and its sampler are bound per render scene, `bind_group` holding the default texture.
+///Removed textures leave an empty slot, so the indices of the others stay valid
 pub struct TextureProvider {
     pub bind_group_layout: Option<wgpu::BindGroupLayout>,
     pub bind_group: Option<wgpu::BindGroup>,
-    textures: Vec<Texture>,
-    current_id: u32,
+    textures: Vec<Option<Texture>>,
+    ///One per texture, only without texture arrays
+    texture_bind_groups: Vec<Option<Arc<wgpu::BindGroup>>>,
+    max_textures: u32,
+    texture_arrays: bool,
+    premultiply: bool,
+    srgb: bool,
 }
 impl TextureProvider {
-    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
-        let bytes = [255; 4];
-        let size = wgpu::Extent3d {
-            width: 1,
-            height: 1,
-            depth_or_array_layers: 1,
-        };
-        let texture = Texture::from_bytes(device, queue, &bytes, size, Some(DEFAULT_TEXTURE));
-        let mut provider = Self {
-            bind_group_layout: None,
-            bind_group: None,
-            textures: Vec::new(),
-            current_id: 0,
-        };
-        provider.register_texture(device, texture);
-        provider
-    }
-
-    pub fn get_texture_index(&self, label: Option<&str>) -> Option<u32> {
-        self.textures
-            .iter()
-            .enumerate()
-            .find(|(_, texture)| texture.label.as_ref().map(|l| l.as_str()) == label)
-            .map(|(index, _)| index as u32)
-    }
-
-    fn register_texture(
-        &mut self,
+    pub fn new(
         device: &wgpu::Device,
-        texture: Texture,
-    ) -> u32 {
-        if let Some(index) = self.get_texture_index(texture.label.as_deref()) {
-            return index as u32;
-        }
-        self.textures.push(texture);
+        queue: &wgpu::Queue,
+        max_textures: u32,
+        premultiply: bool,
+        srgb: bool,
+        default_texture: &DefaultTexture,
+        texture_arrays: bool,
+    ) -> Self {
+        let (bytes, width, height) = default_texture.pixels();
+        let texture = Texture::from_bytes(
+            device,
+            queue,
+            &bytes,
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            Some(DEFAULT_TEXTURE),
+            premultiply,
+            srgb,
+        );
+        //The layout never changes, so pipelines created with it stay valid when textures are added
+        let count = if texture_arrays {
+            NonZeroU32::new(max_textures)
+        } else {
+            None
+        };
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Texture Bind Group Layout"),
             entries: &[
@@ -61,23 +70,147 @@ impl TextureProvider {
                         view_dimension: wgpu::TextureViewDimension::D2,
                         sample_type: wgpu::TextureSampleType::Float { filterable: true },
                     },
-                    count: NonZeroU32::new(self.current_id + 1),
+                    count,
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: NonZeroU32::new(self.current_id + 1),
+                    count,
                 },
             ],
         });
+        let mut provider = Self {
+            bind_group_layout: Some(bind_group_layout),
+            bind_group: None,
+            textures: Vec::new(),
+            texture_bind_groups: Vec::new(),
+            max_textures,
+            texture_arrays,
+            premultiply,
+            srgb,
+        };
+        provider.register_texture(device, texture);
+        let placeholder = Texture::from_bytes(
+            device,
+            queue,
+            &[255, 0, 255, 255],
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            Some(PLACEHOLDER_TEXTURE),
+            premultiply,
+            srgb,
+        );
+        provider.register_texture(device, placeholder);
+        provider
+    }
+
+    pub fn max_textures(&self) -> u32 {
+        self.max_textures
+    }
+
+    ///If the shaders index `binding_array`s, or bind a single `texture_2d` and `sampler`
+    pub fn supports_texture_arrays(&self) -> bool {
+        self.texture_arrays
+    }
+
+    ///The group binding only the texture at the index, None with texture arrays
+    pub fn texture_bind_group(&self, index: u32) -> Option<Arc<wgpu::BindGroup>> {
+        self.texture_bind_groups.get(index as usize).cloned().flatten()
+    }
+
+    pub fn get_texture_index(&self, label: Option<&str>) -> Option<u32> {
+        self.textures
+            .iter()
+            .enumerate()
+            .filter_map(|(index, texture)| texture.as_ref().map(|texture| (index, texture)))
+            .find(|(_, texture)| texture.label.as_deref() == label)
+            .map(|(index, _)| index as u32)
+    }
+
+    fn register_texture(&mut self, device: &wgpu::Device, texture: Texture) -> Option<u32> {
+        if let Some(index) = self.get_texture_index(texture.label.as_deref()) {
+            return Some(index);
+        }
+        let index = match self.textures.iter().position(Option::is_none) {
+            Some(index) => index,
+            None if (self.textures.len() as u32) < self.max_textures => {
+                self.textures.push(None);
+                self.textures.len() - 1
+            }
+            None => {
+                error!(
+                    "Cannot register texture {:?}. All {} texture slots are in use",
+                    texture.label, self.max_textures
+                );
+                return None;
+            }
+        };
+        if !self.texture_arrays {
+            let bind_group_layout = self
+                .bind_group_layout
+                .as_ref()
+                .expect("Texture bind group layout vanished");
+            if self.bind_group.is_none() {
+                self.bind_group = Some(Self::single_bind_group(
+                    device,
+                    bind_group_layout,
+                    &texture,
+                ));
+            }
+            let bind_group = Self::single_bind_group(device, bind_group_layout, &texture);
+            if self.texture_bind_groups.len() <= index {
+                self.texture_bind_groups.resize(index + 1, None);
+            }
+            self.texture_bind_groups[index] = Some(Arc::new(bind_group));
+        }
+        self.textures[index] = Some(texture);
+        if self.texture_arrays {
+            self.update_bind_group(device);
+        }
+        Some(index as u32)
+    }
+
+    ///Frees the slot of the texture for the next one. The default and the placeholder texture
+    ///stay, as everything falls back to them
+    pub fn remove_texture(&mut self, device: &wgpu::Device, label: &str) -> bool {
+        let index = match self.get_texture_index(Some(label)) {
+            Some(index) => index as usize,
+            None => return false,
+        };
+        if index <= PLACEHOLDER_TEXTURE_INDEX as usize {
+            warn!("The texture {} cannot be removed", label);
+            return false;
+        }
+        self.textures[index] = None;
+        if self.texture_arrays {
+            self.update_bind_group(device);
+        } else {
+            self.texture_bind_groups[index] = None;
+        }
+        true
+    }
+
+    fn update_bind_group(&mut self, device: &wgpu::Device) {
+        let bind_group_layout = self
+            .bind_group_layout
+            .as_ref()
+            .expect("Texture bind group layout vanished");
+        let default = self.textures[0].as_ref().expect("The default texture vanished");
+        //Unused slots are filled with the default texture at index 0
+        let slots = (0..self.max_textures as usize)
+            .map(|i| self.textures.get(i).and_then(Option::as_ref).unwrap_or(default))
+            .collect::<Vec<_>>();
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
+            layout: bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
                     resource: wgpu::BindingResource::TextureViewArray(
-                        self.textures
+                        slots
                             .iter()
                             .map(|texture| &texture.view)
                             .collect::<Vec<_>>()
@@ -87,7 +220,7 @@ impl TextureProvider {
                 wgpu::BindGroupEntry {
                     binding: 1,
                     resource: wgpu::BindingResource::SamplerArray(
-                        self.textures
+                        slots
                             .iter()
                             .map(|texture| &texture.sampler)
                             .collect::<Vec<_>>()
@@ -95,12 +228,30 @@ impl TextureProvider {
                     ),
                 },
             ],
-            label: Some(self.current_id.to_string().as_str()),
+            label: Some(self.textures.len().to_string().as_str()),
         });
-        self.bind_group_layout = Some(bind_group_layout);
         self.bind_group = Some(bind_group);
-        self.current_id += 1;
-        self.current_id - 1
+    }
+
+    fn single_bind_group(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        texture: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: texture.label.as_deref(),
+        })
     }
 
     pub fn create_texture(
@@ -109,11 +260,11 @@ impl TextureProvider {
         queue: &wgpu::Queue,
         path: &Path,
         label: Option<&str>,
-    ) -> u32 {
+    ) -> Option<u32> {
         if let Some(index) = self.get_texture_index(label) {
-            return index as u32;
+            return Some(index);
         }
-        let texture = Texture::new(device, queue, path, label);
+        let texture = Texture::new(device, queue, path, label, self.premultiply, self.srgb);
 
         self.register_texture(device, texture)
     }
@@ -133,14 +284,27 @@ impl Texture {
         bytes: &[u8],
         size: wgpu::Extent3d,
         label: Option<&str>,
+        premultiply: bool,
+        srgb: bool,
     ) -> Self {
+        let premultiplied;
+        let bytes = if premultiply {
+            premultiplied = premultiply_alpha(bytes, srgb);
+            premultiplied.as_slice()
+        } else {
+            bytes
+        };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: if srgb {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            },
             usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
@@ -180,7 +344,14 @@ impl Texture {
         }
     }
 
-    fn new(device: &wgpu::Device, queue: &wgpu::Queue, path: &Path, label: Option<&str>) -> Self {
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        label: Option<&str>,
+        premultiply: bool,
+        srgb: bool,
+    ) -> Self {
         let bytes = fs::read(path).expect(&format!("Could not read: '{:?}' for texture {:?}", path, label));
         let img =
             image::load_from_memory(&bytes).expect(&format!("Could not load image: '{:?}", path));
@@ -193,6 +364,18 @@ impl Texture {
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
-        Self::from_bytes(device, queue, &rgba, size, label)
+        Self::from_bytes(device, queue, &rgba, size, label, premultiply, srgb)
     }
 }
+
+///An sRGB texture is sampled in linear space, so its color is multiplied there
+fn premultiply_alpha(bytes: &[u8], srgb: bool) -> Vec<u8> {
+    bytes
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            Color::new_rgba(pixel[0], pixel[1], pixel[2], pixel[3])
+                .premultiply(srgb)
+                .to_slice()
+        })
+        .collect()
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use twod::Vector;
+use wgpu::util::DeviceExt;
+
+use crate::game_engine::Color;
+
+use super::UniformBufferName;
+
+pub mod exports {
+    pub use super::{TimeUniform, TintUniform, TransformUniform, Uniform};
+}
+
+///Typed uniform, that can be registered without hand rolling the bytes
+pub trait Uniform {
+    fn to_bytes(&self) -> Vec<u8>;
+    fn shader_stages(&self) -> wgpu::ShaderStages;
+    ///Initial uniform, as expected by the RessourceDescriptor and new render scenes
+    fn describe(
+        &self,
+        name: UniformBufferName,
+    ) -> (UniformBufferName, Vec<u8>, wgpu::ShaderStages) {
+        (name, self.to_bytes(), self.shader_stages())
+    }
+}
+
+///2D affine transform, `mat3x2<f32>` in the shader, like the camera
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TransformUniform {
+    matrix: [[f32; 2]; 3],
+}
+impl TransformUniform {
+    pub fn identity() -> Self {
+        Self {
+            matrix: [[1.0, 0.0], [0.0, 1.0], [0.0, 0.0]],
+        }
+    }
+
+    ///Scales first, then rotates counter clockwise by rotation in radians, then translates
+    pub fn new(translation: Vector<f32>, rotation: f32, scale: Vector<f32>) -> Self {
+        let (sin, cos) = rotation.sin_cos();
+        Self {
+            matrix: [
+                [cos * scale.x, sin * scale.x],
+                [-sin * scale.y, cos * scale.y],
+                [translation.x, translation.y],
+            ],
+        }
+    }
+
+    pub fn apply(&self, point: &Vector<f32>) -> Vector<f32> {
+        let [a, b, t] = self.matrix;
+        Vector::new(
+            a[0] * point.x + b[0] * point.y + t[0],
+            a[1] * point.x + b[1] * point.y + t[1],
+        )
+    }
+}
+impl Uniform for TransformUniform {
+    fn to_bytes(&self) -> Vec<u8> {
+        bytemuck::bytes_of(self).to_vec()
+    }
+    fn shader_stages(&self) -> wgpu::ShaderStages {
+        wgpu::ShaderStages::VERTEX
+    }
+}
+
+///Elapsed time in seconds, `struct { elapsed: f32 }` in the shader
+///Padded to 16 bytes, the minimum uniform size on some backends
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TimeUniform {
+    pub elapsed: f32,
+    _padding: [f32; 3],
+}
+impl TimeUniform {
+    pub fn new(elapsed: f32) -> Self {
+        Self {
+            elapsed,
+            _padding: [0.0; 3],
+        }
+    }
+}
+impl Uniform for TimeUniform {
+    fn to_bytes(&self) -> Vec<u8> {
+        bytemuck::bytes_of(self).to_vec()
+    }
+    fn shader_stages(&self) -> wgpu::ShaderStages {
+        wgpu::ShaderStages::VERTEX_FRAGMENT
+    }
+}
+
+///Color multiplied onto the fragments, `vec4<f32>` in the shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TintUniform {
+    color: [f32; 4],
+}
+impl TintUniform {
+    pub fn new(color: &Color) -> Self {
+        Self {
+            color: color.to_rgba_f32(),
+        }
+    }
+}
+impl Uniform for TintUniform {
+    fn to_bytes(&self) -> Vec<u8> {
+        bytemuck::bytes_of(self).to_vec()
+    }
+    fn shader_stages(&self) -> wgpu::ShaderStages {
+        wgpu::ShaderStages::FRAGMENT
+    }
+}
+
+///The uniforms of the GraphicsDescriptor shared by all render scenes using global uniforms. They
+///form one bind group, with the uniform at index i of the descriptor at @binding(i)
+pub(crate) struct GlobalUniforms {
+    buffers: Vec<(UniformBufferName, wgpu::Buffer)>,
+    pub(crate) bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    pub(crate) bind_group: Arc<wgpu::BindGroup>,
+}
+impl GlobalUniforms {
+    ///Without any uniforms the bind group is empty, so the group indices of the shaders stay valid
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        uniforms: &[(UniformBufferName, Vec<u8>, wgpu::ShaderStages)],
+    ) -> Self {
+        let buffers = uniforms
+            .iter()
+            .map(|(name, contents, _)| {
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(name.as_str()),
+                    contents,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                (name.clone(), buffer)
+            })
+            .collect::<Vec<_>>();
+        let layout_entries = uniforms
+            .iter()
+            .enumerate()
+            .map(|(i, (_, _, visibility))| wgpu::BindGroupLayoutEntry {
+                binding: i as u32,
+                visibility: *visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>();
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Global Uniforms"),
+            entries: &layout_entries,
+        });
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(i, (_, buffer))| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Global Uniforms"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+        Self {
+            buffers,
+            bind_group_layout: Arc::new(bind_group_layout),
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub(crate) fn contains(&self, name: &UniformBufferName) -> bool {
+        self.buffers.iter().any(|(n, _)| n == name)
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = &UniformBufferName> {
+        self.buffers.iter().map(|(name, _)| name)
+    }
+
+    pub(crate) fn size(&self, name: &UniformBufferName) -> Option<u64> {
+        self.buffers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, buffer)| buffer.size())
+    }
+
+    ///Returns false if there is no global uniform with the name
+    pub(crate) fn update(&self, queue: &wgpu::Queue, name: &UniformBufferName, data: &[u8]) -> bool {
+        match self.buffers.iter().find(|(n, _)| n == name) {
+            Some((_, buffer)) => {
+                queue.write_buffer(buffer, 0, data);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform() {
+        let transform = TransformUniform::new(
+            Vector::new(1.0, 2.0),
+            std::f32::consts::FRAC_PI_2,
+            Vector::new(2.0, 2.0),
+        );
+        let point = transform.apply(&Vector::new(1.0, 0.0));
+        assert!((point.x - 1.0).abs() < 1e-5);
+        assert!((point.y - 4.0).abs() < 1e-5);
+        assert_eq!(TransformUniform::identity().to_bytes().len(), 24);
+        assert_eq!(TimeUniform::new(1.0).to_bytes().len(), 16);
+    }
+}
@@ -14,7 +14,7 @@ mod game;
 pub mod game_engine {
     pub use super::game::exports::*;
     pub use super::game::{
-        example, Game, State,
+        example, time_uniform_name, Game, State, DEFAULT_MAX_DELTA_T, TIME_UNIFORM,
     };
 }
 
@@ -38,6 +38,20 @@ impl <T: Numeric> Size<T> {
     pub fn height(&self) -> T {
         self.0.y
     }
+    ///Logical to physical pixels, with the scale factor of the window
+    pub fn to_physical(&self, scale_factor: f64) -> Size<f32> {
+        Size::new(
+            (self.width().into() * scale_factor) as f32,
+            (self.height().into() * scale_factor) as f32,
+        )
+    }
+    ///Physical to logical pixels, with the scale factor of the window
+    pub fn to_logical(&self, scale_factor: f64) -> Size<f32> {
+        Size::new(
+            (self.width().into() / scale_factor) as f32,
+            (self.height().into() / scale_factor) as f32,
+        )
+    }
 }
 impl <T: Numeric> From<twod::Vector<T>> for Size<T> {
     fn from(value: twod::Vector<T>) -> Self {
@@ -72,6 +86,20 @@ impl <T: Numeric> Position<T> {
     pub fn y(&self) -> T {
         self.0.y
     }
+    ///Logical to physical pixels, with the scale factor of the window
+    pub fn to_physical(&self, scale_factor: f64) -> Position<f32> {
+        Position::new(
+            (self.x().into() * scale_factor) as f32,
+            (self.y().into() * scale_factor) as f32,
+        )
+    }
+    ///Physical to logical pixels, with the scale factor of the window
+    pub fn to_logical(&self, scale_factor: f64) -> Position<f32> {
+        Position::new(
+            (self.x().into() / scale_factor) as f32,
+            (self.y().into() / scale_factor) as f32,
+        )
+    }
 }
 impl <T: Numeric> Debug for Position<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -106,7 +134,7 @@ pub mod reexports {
 #[macro_export]
 macro_rules! create_name_struct {
     ($name: ident) => {
-        #[derive(Debug, Clone, PartialEq)]
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
         pub struct $name(String);
         impl $name {
             #[allow(dead_code)]
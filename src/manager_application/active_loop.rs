@@ -0,0 +1,32 @@
+use winit::{
+    event_loop::ActiveEventLoop,
+    monitor::MonitorHandle,
+    window::{CustomCursor, CustomCursorSource},
+};
+
+pub mod exports {
+    pub use super::ActiveLoop;
+}
+
+///Access to the running event loop. It only exists during an event loop callback, so it cannot
+///be stored. Everything obtained through it, like monitors and cursors, can be kept
+pub struct ActiveLoop<'a> {
+    event_loop: &'a ActiveEventLoop,
+}
+impl<'a> ActiveLoop<'a> {
+    pub fn new(event_loop: &'a ActiveEventLoop) -> Self {
+        Self { event_loop }
+    }
+
+    pub fn available_monitors(&self) -> Vec<MonitorHandle> {
+        self.event_loop.available_monitors().collect()
+    }
+
+    pub fn primary_monitor(&self) -> Option<MonitorHandle> {
+        self.event_loop.primary_monitor()
+    }
+
+    pub fn create_custom_cursor(&self, source: CustomCursorSource) -> CustomCursor {
+        self.event_loop.create_custom_cursor(source)
+    }
+}
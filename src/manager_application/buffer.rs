@@ -1,29 +1,63 @@
-use crate::graphics::{
-    BufferWriter, Index, IndexBufferWriter, Vertex, VertexBufferWriter,
+use twod::Vector;
+use wgpu::COPY_BUFFER_ALIGNMENT;
+
+use crate::{
+    graphics::{BufferWriter, Index, IndexBufferWriter, Vertex, VertexBufferWriter},
+    Size,
 };
 
 pub mod exports {
     pub use super::IndexBuffer;
     pub use super::VertexBuffer;
-    pub use super::write_regular_ngon_u16;
+    pub use super::{
+        fits_u16_indices, rotated_quad_corners, write_regular_ngon_u16, write_rotated_quad_u16,
+        MAX_U16_VERTICES,
+    };
+}
+
+///Vertices a buffer with u16 indices can address
+pub const MAX_U16_VERTICES: usize = u16::MAX as usize + 1;
+
+///If `additional` vertices still fit behind the vertices, eg. before computing u16 indices
+///starting at `vertices.len()`
+pub fn fits_u16_indices(vertices: &VertexBuffer, additional: usize) -> bool {
+    vertices.len() as usize + additional <= MAX_U16_VERTICES
+}
+
+///Extends the data, keeping it zero padded to COPY_BUFFER_ALIGNMENT, so it can be written to
+///the GPU without copying it
+fn extend_aligned(data: &mut Vec<u8>, data_len: &mut usize, new_data: &[u8]) {
+    data.truncate(*data_len);
+    data.extend_from_slice(new_data);
+    *data_len = data.len();
+    let misalignment = *data_len % COPY_BUFFER_ALIGNMENT as usize;
+    if misalignment != 0 {
+        data.resize(*data_len + COPY_BUFFER_ALIGNMENT as usize - misalignment, 0);
+    }
 }
 
 #[derive(Debug)]
 pub struct IndexBuffer {
+    ///Padded, the indices end at indices_len
     indices: Vec<u8>,
+    indices_len: usize,
     num_indices: u32,
 }
 impl IndexBuffer {
     pub fn new() -> Self {
         Self {
             indices: Vec::new(),
+            indices_len: 0,
             num_indices: 0,
         }
     }
     pub fn extend_from_slice<I: Index>(&mut self, new_indices: &[I]) {
         self.num_indices += new_indices.len() as u32;
-        self.indices
-            .extend_from_slice(bytemuck::cast_slice(new_indices));
+        extend_aligned(
+            &mut self.indices,
+            &mut self.indices_len,
+            bytemuck::cast_slice(new_indices),
+        );
     }
     pub fn len(&self) -> u32 {
         self.num_indices
@@ -42,20 +76,26 @@ impl IndexBufferWriter for IndexBuffer {}
 
 #[derive(Debug)]
 pub struct VertexBuffer {
+    ///Padded, the vertices end at vertices_len
     vertices: Vec<u8>,
+    vertices_len: usize,
     num_vertices: u32,
 }
 impl VertexBuffer {
     pub fn new() -> Self {
         Self {
             vertices: Vec::new(),
+            vertices_len: 0,
             num_vertices: 0,
         }
     }
     pub fn extend_from_slice<V: Vertex>(&mut self, new_vertices: &[V]) {
         self.num_vertices += new_vertices.len() as u32;
-        self.vertices
-            .extend_from_slice(bytemuck::cast_slice(new_vertices));
+        extend_aligned(
+            &mut self.vertices,
+            &mut self.vertices_len,
+            bytemuck::cast_slice(new_vertices),
+        );
     }
     pub fn len(&self) -> u32 {
         self.num_vertices
@@ -89,3 +129,103 @@ pub fn write_regular_ngon_u16<V: Vertex>(
     vertices.extend_from_slice(&new_vertices);
     indices.extend_from_slice(&new_indices)
 }
+
+///Corners of the quad rotated counter clockwise by `rotation` in radians around its center.
+///Ordered top left, top right, bottom right, bottom left before the rotation, like the texture
+///coordinates of SpriteSheet::get_sprite_coordinates
+pub fn rotated_quad_corners(
+    center: &Vector<f32>,
+    size: &Size<f32>,
+    rotation: f32,
+) -> [Vector<f32>; 4] {
+    let (sin, cos) = rotation.sin_cos();
+    let (half_width, half_height) = (size.width() / 2.0, size.height() / 2.0);
+    [
+        (-half_width, half_height),
+        (half_width, half_height),
+        (half_width, -half_height),
+        (-half_width, -half_height),
+    ]
+    .map(|(x, y)| Vector::new(center.x + x * cos - y * sin, center.y + x * sin + y * cos))
+}
+
+///Write a quad rotated around its center. `create_vertex` gets each corner and its index in the
+///order of rotated_quad_corners, eg. to look up its texture coordinates. Using u16 indices,
+///returns false without writing the quad if it does not fit into them
+pub fn write_rotated_quad_u16<V: Vertex>(
+    vertices: &mut VertexBuffer,
+    indices: &mut IndexBuffer,
+    center: &Vector<f32>,
+    size: &Size<f32>,
+    rotation: f32,
+    create_vertex: impl Fn(Vector<f32>, usize) -> V,
+) -> bool {
+    if !fits_u16_indices(vertices, 4) {
+        return false;
+    }
+    let [a, b, c, d] = rotated_quad_corners(center, size, rotation);
+    write_regular_ngon_u16(
+        vertices,
+        indices,
+        &[create_vertex(a, 0), create_vertex(b, 1), create_vertex(c, 2), create_vertex(d, 3)],
+    );
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::example::{Color, SimpleVertex};
+
+    #[test]
+    fn u16_limit() {
+        let mut vertices = VertexBuffer::new();
+        assert!(fits_u16_indices(&vertices, MAX_U16_VERTICES));
+        let white = Color::new_rgba(255, 255, 255, 255);
+        let vertex = SimpleVertex::new(Vector::new(0.0, 0.0), white);
+        vertices.extend_from_slice(&[vertex; 4]);
+        assert!(fits_u16_indices(&vertices, MAX_U16_VERTICES - 4));
+        assert!(!fits_u16_indices(&vertices, MAX_U16_VERTICES - 3));
+    }
+
+    #[test]
+    fn rotated_corners() {
+        let center = Vector::new(1.0, 1.0);
+        let size = Size::new(2.0, 4.0);
+        let corners = rotated_quad_corners(&center, &size, 0.0);
+        assert_eq!((corners[0].x, corners[0].y), (0.0, 3.0));
+        assert_eq!((corners[2].x, corners[2].y), (2.0, -1.0));
+        let corners = rotated_quad_corners(&center, &size, std::f32::consts::FRAC_PI_2);
+        //The top left corner turns to the bottom left
+        assert!((corners[0].x - -1.0).abs() < 1e-5);
+        assert!((corners[0].y - 0.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rotated_quad_u16_limit() {
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        let white = Color::new_rgba(255, 255, 255, 255);
+        let vertex = SimpleVertex::new(Vector::new(0.0, 0.0), white.clone());
+        vertices.extend_from_slice(&vec![vertex; MAX_U16_VERTICES - 4]);
+        let (center, size) = (Vector::new(0.0, 0.0), Size::new(1.0, 1.0));
+        let create_vertex = |corner, _| SimpleVertex::new(corner, white.clone());
+        assert!(write_rotated_quad_u16(
+            &mut vertices,
+            &mut indices,
+            &center,
+            &size,
+            0.0,
+            create_vertex
+        ));
+        assert!(!write_rotated_quad_u16(
+            &mut vertices,
+            &mut indices,
+            &center,
+            &size,
+            0.0,
+            create_vertex
+        ));
+        assert_eq!((vertices.len() as usize, indices.len()), (MAX_U16_VERTICES, 6));
+    }
+}
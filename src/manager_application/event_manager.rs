@@ -1,14 +1,18 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, time::Instant};
 
-use winit::{event::{ElementState, MouseButton, WindowEvent}, event_loop::ActiveEventLoop, window::WindowId};
+use winit::{event::{ElementState, KeyEvent, MouseButton, TouchPhase, WindowEvent}, event_loop::ActiveEventLoop, keyboard::ModifiersState, window::WindowId};
 pub mod winit_reexports {
     pub use winit::event;
-    pub use winit::event::{ElementState, MouseButton};
+    pub use winit::event::{ElementState, MouseButton, TouchPhase};
+    pub use winit::keyboard::ModifiersState;
 }
 
 pub mod exports {
     pub use super::EventManager;
     pub use super::MouseEvent;
+    pub use super::TouchEvent;
+    pub use super::TextInputEvent;
+    pub use super::typed_text;
 }
 
 use crate::{graphics_provider::GraphicsProvider, Position};
@@ -19,7 +23,51 @@ use super::window_manager::WindowManager;
 pub struct MouseEvent {
     pub state: ElementState,
     pub button: MouseButton,
-    pub position: Position<i32>
+    ///Window-centered pixels, growing to the right and downwards
+    pub position: Position<i32>,
+    ///The position in the world as seen by the camera of the receiving scene. None if the scene
+    ///has no camera
+    pub world_position: Option<twod::Vector<f32>>,
+    ///Modifier keys held during the click, eg. for shift-click
+    pub modifiers: ModifiersState,
+}
+
+#[derive(Debug)]
+pub struct TouchEvent {
+    ///Identifies the finger for the duration of the touch
+    pub id: u64,
+    ///Window-centered pixels, like `MouseEvent::position`
+    pub position: Position<i32>,
+    pub phase: TouchPhase,
+    ///The position in the world as seen by the camera of the receiving scene. None if the scene
+    ///has no camera
+    pub world_position: Option<twod::Vector<f32>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TextInputEvent {
+    ///Text the user finished typing, eg. through an input method
+    Commit(String),
+    ///Text still being composed and the byte range of the cursor in it. Empty text means the
+    ///composition was cleared. Only sent if enabled with `Game::with_ime_preedit`
+    Preedit(String, Option<(usize, usize)>),
+    ///Text typed with a key press, with the keyboard layout and shift applied, eg. "A" instead of
+    ///"a". Control characters like backspace or enter only arrive as key input. Not sent for
+    ///windows with IME allowed, their text arrives as Commit
+    Character(String),
+}
+
+///The printable text a key press produced, respecting the keyboard layout and modifiers. None for
+///releases and keys without text, like the arrows, backspace or enter
+pub fn typed_text(event: &KeyEvent) -> Option<&str> {
+    if event.state != ElementState::Pressed {
+        return None;
+    }
+    event.text.as_ref().map(|text| text.as_str()).filter(|text| is_printable(text))
+}
+
+fn is_printable(text: &str) -> bool {
+    !text.is_empty() && !text.chars().any(char::is_control)
 }
 
 pub trait EventManager<E: 'static + Debug> {
@@ -34,6 +82,18 @@ pub trait EventManager<E: 'static + Debug> {
     ) -> bool
     where
         Self: Sized;
+    ///Called when the event loop is about to wait for new events. Return the next instant the
+    ///manager needs to be woken up at, when running with `ControlFlow::Wait` or `WaitUntil`
+    fn about_to_wait(
+        &mut self,
+        _window_manager: &mut WindowManager<E>,
+        _event_loop: &ActiveEventLoop,
+    ) -> Option<Instant>
+    where
+        Self: Sized,
+    {
+        None
+    }
     fn user_event(
         &mut self,
         _window_manager: &mut WindowManager<E>,
@@ -45,3 +105,17 @@ pub trait EventManager<E: 'static + Debug> {
     {
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printable() {
+        assert!(is_printable("A"));
+        assert!(is_printable("é"));
+        assert!(!is_printable("\u{8}"));
+        assert!(!is_printable("\r"));
+        assert!(!is_printable(""));
+    }
+}
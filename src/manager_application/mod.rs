@@ -1,4 +1,8 @@
-use std::{fmt::Debug, path::Path};
+use std::{
+    fmt::Debug,
+    path::Path,
+    time::{Duration, Instant},
+};
 use winit::{
     application::ApplicationHandler,
     event::{ElementState, KeyEvent, WindowEvent},
@@ -11,9 +15,13 @@ pub mod winit_reexports {
     pub use super::window_descriptor::winit_reexports::*;
     pub use super::event_manager::winit_reexports::*;
     pub use winit::keyboard;
+    pub use winit::event_loop::ControlFlow;
+    pub use winit::monitor::MonitorHandle;
+    pub use winit::window::{CursorGrabMode, CursorIcon, CustomCursor, CustomCursorSource};
 }
 
 
+mod active_loop;
 mod window_descriptor;
 use window_descriptor::WindowDescriptor;
 mod event_manager;
@@ -24,22 +32,43 @@ mod buffer;
 use buffer::{IndexBuffer, VertexBuffer};
 
 use crate::graphics::{
-    GraphicsProvider, RenderSceneDescriptor, RenderSceneName, ShaderDescriptor, UniformBufferName,
+    GraphicsDescriptor, GraphicsProvider, RenderSceneDescriptor, RenderSceneName, ShaderDescriptor, UniformBufferName,
     Visibility,
 };
 
 pub mod exports {
+    pub use super::active_loop::exports::*;
     pub use super::window_descriptor::exports::*;
     pub use super::window_manager::exports::*;
     pub use super::buffer::exports::*;
     pub use super::event_manager::exports::*;
-    pub use super::{ApplicationEvent, ManagerApplication};
+    pub use super::{ApplicationEvent, CoalesceKey, ManagerApplication, RedrawMode};
+}
+
+///How often the loop checks if the device of `GraphicsDescriptor::async_init` is ready
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedrawMode {
+    ///Request the next redraw right after drawing a window
+    Continuous,
+    ///Only redraw a window when its render scenes changed or a redraw was requested through
+    ///`WindowManager::request_redraw`
+    OnDemand,
 }
 
 pub struct ManagerApplication<E: ApplicationEvent + 'static, M: EventManager<E>> {
     event_manager: M,
     window_manager: WindowManager<E>,
     graphics_provider: GraphicsProvider,
+    redraw_mode: RedrawMode,
+    control_flow: ControlFlow,
+    ///Shortest time between two continuous redraws of a window
+    frame_interval: Option<Duration>,
+    ///When the next continuous redraw of each window is due
+    next_frames: Vec<(WindowId, Instant)>,
+    ///Graphics requests held back until the device requested asynchronously is ready
+    deferred_events: Vec<E>,
 }
 
 impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E>
@@ -47,9 +76,70 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E
 {
     fn resumed(&mut self, _active_loop: &ActiveEventLoop) {
         self.window_manager.send_event(E::app_resumed());
+        self.window_manager.flush_events();
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        self.handle_window_event(event_loop, id, event);
+        self.window_manager.flush_events();
+        self.request_dirty_redraws();
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: E) {
+        self.handle_user_event(event_loop, event);
+        self.window_manager.flush_events();
+        self.request_dirty_redraws();
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.recover_lost_device();
+        self.finish_async_init(event_loop);
+        let wakeup = self
+            .event_manager
+            .about_to_wait(&mut self.window_manager, event_loop);
+        self.window_manager.flush_events();
+        self.request_dirty_redraws();
+        let next_frame = self.request_due_frames();
+        //The deadline of run_with wakes the loop once, afterwards it waits like ControlFlow::Wait
+        if let ControlFlow::WaitUntil(deadline) = self.control_flow {
+            if deadline <= Instant::now() {
+                self.control_flow = ControlFlow::Wait;
+            }
+        }
+        let wakeup = match (wakeup, next_frame) {
+            (Some(wakeup), Some(next_frame)) => Some(wakeup.min(next_frame)),
+            (wakeup, next_frame) => wakeup.or(next_frame),
+        };
+        //The worker thread cannot wake the loop, so it checks for the device regularly
+        let wakeup = if self.graphics_provider.is_initializing() {
+            let poll_device = Instant::now() + DEVICE_POLL_INTERVAL;
+            Some(wakeup.map_or(poll_device, |wakeup| wakeup.min(poll_device)))
+        } else {
+            wakeup
+        };
+        let control_flow = match (self.control_flow, wakeup) {
+            //Polling would redraw as fast as without a frame limit
+            (ControlFlow::Poll, Some(wakeup)) if next_frame.is_some() => {
+                ControlFlow::WaitUntil(wakeup)
+            }
+            (ControlFlow::Poll, _) => ControlFlow::Poll,
+            (ControlFlow::Wait, Some(wakeup)) => ControlFlow::WaitUntil(wakeup),
+            (ControlFlow::WaitUntil(deadline), Some(wakeup)) => {
+                ControlFlow::WaitUntil(deadline.min(wakeup))
+            }
+            (control_flow, None) => control_flow,
+        };
+        event_loop.set_control_flow(control_flow);
+    }
+}
+
+impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ManagerApplication<E, M> {
+    fn handle_window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        id: WindowId,
+        event: WindowEvent,
+    ) {
         if self
             .event_manager
             .window_event(&mut self.window_manager, event_loop, &id, &event)
@@ -61,6 +151,7 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E
                     } else {
                         self.graphics_provider.remove_window(&id);
                         self.window_manager.remove_window(&id);
+                        self.next_frames.retain(|(i, _)| *i != id);
                     }
                 }
                 WindowEvent::Resized(size) => self.graphics_provider.resize_window(&id, &size),
@@ -68,11 +159,21 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E
                     //TODO: I think the window will be resized  on its own, which fires a Resized event
                 }
                 WindowEvent::RedrawRequested => {
+                    let frame_start = Instant::now();
                     self.graphics_provider.render_window(&id);
-                    self.window_manager
-                        .get_window(&id)
-                        .expect("The window dissapeared")
-                        .request_redraw();
+                    if self.redraw_mode == RedrawMode::Continuous {
+                        match self.frame_interval {
+                            Some(interval) => {
+                                self.next_frames.retain(|(i, _)| *i != id);
+                                self.next_frames.push((id, frame_start + interval));
+                            }
+                            None => self
+                                .window_manager
+                                .get_window(&id)
+                                .expect("The window dissapeared")
+                                .request_redraw(),
+                        }
+                    }
                 }
                 WindowEvent::KeyboardInput {
                     event:
@@ -101,7 +202,11 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E
         }
     }
 
-    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: E) {
+    fn handle_user_event(&mut self, event_loop: &ActiveEventLoop, event: E) {
+        if self.graphics_provider.is_initializing() && Self::needs_device(&event) {
+            self.deferred_events.push(event);
+            return;
+        }
         match event.is_request_new_window() {
             Some((window_descriptor, name)) => {
                 self.create_window(window_descriptor, event_loop, name)
@@ -114,6 +219,15 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E
                 .update_scene(&render_scene, &vertices, &indices);
             return;
         }
+        if event.is_render_batch() {
+            let updates = event.consume_render_batch();
+            self.graphics_provider.update_scenes(
+                updates
+                    .iter()
+                    .map(|(render_scene, vertices, indices)| (render_scene, vertices, indices)),
+            );
+            return;
+        }
         match event.is_request_new_texture() {
             Some((path, label)) => {
                 let id = self.graphics_provider.create_texture(path, label);
@@ -121,6 +235,12 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E
             }
             None => {}
         }
+        match event.is_request_remove_texture() {
+            Some(label) => {
+                self.graphics_provider.remove_texture(label);
+            }
+            None => {}
+        }
         match event.is_request_new_render_scene() {
             Some((
                 window_id,
@@ -148,6 +268,10 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E
             }
             None => {}
         }
+        if let Some(max_fps) = event.is_request_frame_limit() {
+            self.set_frame_limit(max_fps);
+            return;
+        }
         if event.is_quit() {
             event_loop.exit();
             return;
@@ -160,17 +284,142 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ApplicationHandler<E
             event,
         );
     }
-}
 
-impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ManagerApplication<E, M> {
     pub fn new(event_manager: M) -> Self {
         Self {
             event_manager,
             window_manager: Default::default(),
             graphics_provider: GraphicsProvider::new(),
+            redraw_mode: RedrawMode::Continuous,
+            control_flow: ControlFlow::Poll,
+            frame_interval: None,
+            next_frames: Vec::new(),
+            deferred_events: Vec::new(),
         }
     }
 
+    ///Cap the continuous redraws of each window, independent from the cadence of the game logic.
+    ///The loop waits with `ControlFlow::WaitUntil` until the next frame is due, even when polling.
+    ///A rate that is not positive and finite removes the limit
+    pub fn with_frame_limit(mut self, max_fps: f32) -> Self {
+        self.set_frame_limit(Some(max_fps));
+        self
+    }
+
+    ///Change the limit of with_frame_limit while the application runs, eg. from the settings of
+    ///a game. None removes it. The EventManager requests it with
+    ///`ApplicationEvent::is_request_frame_limit`
+    pub fn set_frame_limit(&mut self, max_fps: Option<f32>) {
+        self.frame_interval = max_fps.and_then(|max_fps| {
+            match Duration::try_from_secs_f32(1.0 / max_fps) {
+                Ok(frame_interval) if max_fps.is_finite() => Some(frame_interval),
+                _ => {
+                    log::warn!("Ignoring the frame limit of {} fps", max_fps);
+                    None
+                }
+            }
+        });
+    }
+
+    ///The EventManager passed to `new`, eg. to inspect the Game after `run` returned
+    pub fn event_manager(&self) -> &M {
+        &self.event_manager
+    }
+
+    pub fn event_manager_mut(&mut self) -> &mut M {
+        &mut self.event_manager
+    }
+
+    ///Requests the redraws that are due and returns when the next one is
+    fn request_due_frames(&mut self) -> Option<Instant> {
+        let now = Instant::now();
+        let window_manager = &self.window_manager;
+        self.next_frames.retain(|(id, next_frame)| {
+            if *next_frame <= now {
+                window_manager.request_redraw(id);
+                false
+            } else {
+                true
+            }
+        });
+        self.next_frames.iter().map(|(_, next_frame)| *next_frame).min()
+    }
+
+    pub fn with_redraw_mode(mut self, redraw_mode: RedrawMode) -> Self {
+        self.redraw_mode = redraw_mode;
+        self
+    }
+
+    ///Initialize the windows on a new device and let the EventManager recreate its render
+    ///scenes and textures
+    fn recover_lost_device(&mut self) {
+        if !self.graphics_provider.take_device_lost() {
+            return;
+        }
+        for window in self.window_manager.windows() {
+            self.graphics_provider.init_window(window);
+            self.graphics_provider
+                .resize_window(&window.id(), &window.inner_size());
+            window.request_redraw();
+        }
+        if let Some(event) = E::device_lost() {
+            self.window_manager.send_event(event);
+        }
+    }
+
+    fn needs_device(event: &E) -> bool {
+        event.is_render_update()
+            || event.is_render_batch()
+            || event.is_request_new_texture().is_some()
+            || event.is_request_remove_texture().is_some()
+            || event.is_request_new_render_scene().is_some()
+            || event.is_request_set_visibility_render_scene().is_some()
+            || event.needs_device()
+    }
+
+    ///Handles the graphics requests held back, in order, once the device is ready
+    fn finish_async_init(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.graphics_provider.poll_device() {
+            return;
+        }
+        for event in std::mem::take(&mut self.deferred_events) {
+            //Queued behind the replies to the graphics requests, eg. the new render scenes
+            if event.needs_device() {
+                self.window_manager.send_event(event);
+            } else {
+                self.handle_user_event(event_loop, event);
+            }
+        }
+        for window in self.window_manager.windows() {
+            window.request_redraw();
+        }
+        if let Some(event) = E::graphics_ready() {
+            self.window_manager.send_event(event);
+        }
+    }
+
+    fn request_dirty_redraws(&mut self) {
+        let dirty_windows = self.graphics_provider.take_dirty_windows();
+        if self.redraw_mode == RedrawMode::OnDemand {
+            for id in dirty_windows.iter() {
+                self.window_manager.request_redraw(id);
+            }
+        }
+    }
+
+    ///Hold back events sent through the WindowManager until the end of each event loop callback
+    ///and only keep the latest RenderUpdate per RenderScene and uniform update per UniformBuffer
+    pub fn with_event_coalescing(mut self) -> Self {
+        self.window_manager.set_event_coalescing(true);
+        self
+    }
+
+    ///Must be called before `run`, as the graphics are initialized with the first window
+    pub fn with_graphics_descriptor(mut self, descriptor: GraphicsDescriptor) -> Self {
+        self.graphics_provider = GraphicsProvider::with_descriptor(descriptor);
+        self
+    }
+
     fn create_window(
         &mut self,
         descriptor: &WindowDescriptor,
@@ -188,18 +437,36 @@ impl<'a, E: ApplicationEvent + 'static, M: EventManager<E>> ManagerApplication<E
     }
 
     pub fn run(&mut self) {
+        self.run_with(ControlFlow::Poll)
+    }
+
+    ///With `ControlFlow::Wait` or `ControlFlow::WaitUntil` the loop sleeps until an event
+    ///arrives or the wakeup returned by `EventManager::about_to_wait` is reached. Combine it with
+    ///`RedrawMode::OnDemand`, otherwise the continuous redraws keep the loop busy. The deadline of
+    ///`ControlFlow::WaitUntil` applies once, after it passed the loop waits for the next event
+    pub fn run_with(&mut self, control_flow: ControlFlow) {
         let event_loop = EventLoop::<E>::with_user_event()
             .build()
             .expect("No loop for you");
         let event_loop_proxy = event_loop.create_proxy();
         self.window_manager.set_event_loop(event_loop_proxy);
 
-        event_loop.set_control_flow(ControlFlow::Poll);
+        self.control_flow = control_flow;
+        event_loop.set_control_flow(control_flow);
 
         event_loop.run_app(self).expect("No App for you");
     }
 }
 
+///Identifies an event that supersedes earlier events with the same key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CoalesceKey {
+    RenderScene(RenderSceneName),
+    UniformBuffer(UniformBufferName),
+    ///All render batches share the key, an earlier batch is merged into the later one
+    RenderBatch,
+}
+
 pub trait ApplicationEvent: Debug {
     fn app_resumed() -> Self;
     fn new_window(id: &WindowId, name: &str) -> Self;
@@ -208,7 +475,23 @@ pub trait ApplicationEvent: Debug {
     fn is_request_new_window<'a>(&'a self) -> Option<(&'a WindowDescriptor, &'a str)>;
     fn is_render_update(&self) -> bool;
     fn consume_render_update(self) -> (RenderSceneName, VertexBuffer, IndexBuffer);
+    ///Several render updates, which are written together
+    fn is_render_batch(&self) -> bool {
+        false
+    }
+    ///Should only be called if is_render_batch returns true
+    fn consume_render_batch(self) -> Vec<(RenderSceneName, VertexBuffer, IndexBuffer)>
+    where
+        Self: Sized,
+    {
+        log::error!("Consumed {:?} as a render batch, but it is none", self);
+        Vec::new()
+    }
     fn is_request_new_texture<'a>(&'a self) -> Option<(&'a Path, &'a str)>;
+    ///Its index may be handed out to the next texture
+    fn is_request_remove_texture<'a>(&'a self) -> Option<&'a str> {
+        None
+    }
     fn is_request_new_render_scene<'a>(
         &'a self,
     ) -> Option<(
@@ -222,4 +505,40 @@ pub trait ApplicationEvent: Debug {
         &'a self,
     ) -> Option<(&'a RenderSceneName, &'a Visibility)>;
     fn is_quit(&self) -> bool;
+    ///Change the frame limit of `ManagerApplication::with_frame_limit`, None removes it
+    fn is_request_frame_limit(&self) -> Option<Option<f32>> {
+        None
+    }
+    ///Requests the EventManager passes on to the GraphicsProvider, eg. uniform updates. They are
+    ///held back with the graphics requests until the device is ready
+    fn needs_device(&self) -> bool {
+        false
+    }
+    ///Events with the same key are coalesced, if coalescing is enabled on the WindowManager
+    fn coalesce_key(&self) -> Option<CoalesceKey> {
+        None
+    }
+    ///Takes over what is still needed of the earlier event with the same coalesce key, which is
+    ///dropped afterwards
+    fn coalesce(&mut self, _earlier: Self)
+    where
+        Self: Sized,
+    {
+    }
+    ///Sent after the graphics device was lost and the windows were initialized on a new one.
+    ///All render scenes and textures have to be requested again
+    fn device_lost() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    ///Sent once the device requested with `GraphicsDescriptor::async_init` is ready and the
+    ///render scenes and textures requested in the meantime were created
+    fn graphics_ready() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
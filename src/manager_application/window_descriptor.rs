@@ -1,6 +1,7 @@
 use crate::{Numeric, Position, Size};
 use image::imageops::{resize, FilterType};
-use std::fs;
+use log::warn;
+use std::{borrow::Cow, fs};
 pub mod winit_reexports {
     pub use winit:: window::{Fullscreen, Icon, Theme, WindowButtons, WindowLevel};
 }
@@ -14,11 +15,38 @@ pub mod exports {
     pub use super::WindowDescriptor;
 }
 
+///Where an icon or cursor image comes from
+#[derive(Clone, Debug)]
+enum ImageSource {
+    File(&'static str),
+    Bytes(&'static [u8]),
+}
+impl ImageSource {
+    fn read(&self) -> Result<Cow<'static, [u8]>, String> {
+        match self {
+            ImageSource::File(path) => fs::read(path)
+                .map(Cow::Owned)
+                .map_err(|e| format!("Could not read image file at '{}': {}", path, e)),
+            ImageSource::Bytes(bytes) => Ok(Cow::Borrowed(*bytes)),
+        }
+    }
+}
+impl std::fmt::Display for ImageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageSource::File(path) => write!(f, "file '{}'", path),
+            ImageSource::Bytes(bytes) => write!(f, "{} embedded bytes", bytes.len()),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct WindowDescriptor {
     attributes: WindowAttributes,
-    cursor_path: Option<&'static str>,
-    icon_path: Option<&'static str>,
+    cursor: Option<ImageSource>,
+    cursor_size: Option<(u32, u32)>,
+    cursor_hotspot: (u16, u16),
+    icon: Option<ImageSource>,
 }
 impl WindowDescriptor {
     pub fn new() -> Self {
@@ -28,52 +56,96 @@ impl WindowDescriptor {
     }
 
     pub fn with_cursor(mut self, path: &'static str) -> Self {
-        self.cursor_path = Some(path);
+        self.cursor = Some(ImageSource::File(path));
+        self
+    }
+
+    ///Like with_cursor, but from an encoded image, eg. embedded with include_bytes!
+    pub fn with_cursor_bytes(mut self, bytes: &'static [u8]) -> Self {
+        self.cursor = Some(ImageSource::Bytes(bytes));
+        self
+    }
+
+    ///Scale the cursor image to this size. Without it the image is used at its own size
+    pub fn with_cursor_size(mut self, width: u32, height: u32) -> Self {
+        self.cursor_size = Some((width, height));
+        self
+    }
+
+    ///The pixel of the cursor (after scaling) that is the click point. Defaults to the top left
+    pub fn with_cursor_hotspot(mut self, x: u16, y: u16) -> Self {
+        self.cursor_hotspot = (x, y);
         self
     }
 
     pub fn with_icon(mut self, path: &'static str) -> Self {
-        self.icon_path = Some(path);
+        self.icon = Some(ImageSource::File(path));
         self
     }
 
-    fn decode_icon(&self, path: &'static str) -> Icon {
-        let bytes = fs::read(path).expect(&format!("Could not read icon file at '{}'", path));
+    ///Like with_icon, but from an encoded image, eg. embedded with include_bytes!
+    pub fn with_icon_bytes(mut self, bytes: &'static [u8]) -> Self {
+        self.icon = Some(ImageSource::Bytes(bytes));
+        self
+    }
+
+    fn decode_icon(&self, source: &ImageSource) -> Result<Icon, String> {
+        let bytes = source.read()?;
 
         let (icon_rgba, icon_width, icon_height) = {
             let image = image::load_from_memory(&bytes)
-                .expect(&format!("Could not parse icon file at '{}'", path))
+                .map_err(|e| format!("Could not parse icon from {}: {}", source, e))?
                 .into_rgba8();
             let (width, height) = image.dimensions();
             let rgba = image.into_raw();
             (rgba, width, height)
         };
         Icon::from_rgba(icon_rgba, icon_width, icon_height)
-            .expect(&format!("Could not make icon from file at '{}'", path))
+            .map_err(|e| format!("Could not make icon from {}: {}", source, e))
     }
 
-    fn decode_cursor(&self, path: &'static str) -> CustomCursorSource {
-        let bytes = fs::read(path).expect(&format!("Could not read cursor file at '{}'", path));
+    fn decode_cursor(&self, source: &ImageSource) -> Result<CustomCursorSource, String> {
+        let bytes = source.read()?;
         let img = image::load_from_memory(&bytes)
-            .expect(&format!("Could not parse cursor file at '{}'", path))
+            .map_err(|e| format!("Could not parse cursor from {}: {}", source, e))?
             .into_rgba8();
-        let img = resize(&img, 32, 32, FilterType::Gaussian);
+        let img = match self.cursor_size {
+            Some((width, height)) if (width, height) != img.dimensions() => {
+                //Nearest keeps pixel art crisp when scaling up
+                let filter = if width > img.width() || height > img.height() {
+                    FilterType::Nearest
+                } else {
+                    FilterType::Triangle
+                };
+                resize(&img, width, height, filter)
+            }
+            _ => img,
+        };
         let samples = img.into_flat_samples();
         let (_, w, h) = samples.extents();
         let (w, h) = (w as u16, h as u16);
-        CustomCursor::from_rgba(samples.samples, w, h, w / 4, 0)
-            .expect(&format!("Could not make cursor from file at '{}'", path))
+        let (hotspot_x, hotspot_y) = self.cursor_hotspot;
+        CustomCursor::from_rgba(samples.samples, w, h, hotspot_x, hotspot_y)
+            .map_err(|e| format!("Could not make cursor from {}: {}", source, e))
     }
 
+    ///An icon or cursor that cannot be loaded is logged and left out, so the window still opens
     pub fn get_attributes(&self, event_loop: &ActiveEventLoop) -> WindowAttributes {
         let mut attributes = self.attributes.clone();
-        if let Some(cursor_path) = self.cursor_path {
-            let cursor_source = self.decode_cursor(cursor_path);
-            attributes = attributes.with_cursor(event_loop.create_custom_cursor(cursor_source));
+        if let Some(cursor) = &self.cursor {
+            match self.decode_cursor(cursor) {
+                Ok(cursor_source) => {
+                    attributes =
+                        attributes.with_cursor(event_loop.create_custom_cursor(cursor_source));
+                }
+                Err(e) => warn!("{}. Using the default cursor", e),
+            }
         }
-        if let Some(icon_path) = self.icon_path {
-            let icon = self.decode_icon(icon_path);
-            attributes = attributes.with_window_icon(Some(icon));
+        if let Some(icon) = &self.icon {
+            match self.decode_icon(icon) {
+                Ok(icon) => attributes = attributes.with_window_icon(Some(icon)),
+                Err(e) => warn!("{}. Opening the window without an icon", e),
+            }
         }
         attributes
     }
@@ -82,8 +154,10 @@ impl Default for WindowDescriptor {
     fn default() -> Self {
         Self {
             attributes: WindowAttributes::default(),
-            cursor_path: None,
-            icon_path: None,
+            cursor: None,
+            cursor_size: None,
+            cursor_hotspot: (0, 0),
+            icon: None,
         }
     }
 }
@@ -1,19 +1,38 @@
-use std::fmt::Debug;
+use std::{cell::RefCell, collections::HashMap, fmt::Debug};
 
+use log::warn;
 use winit::{
     event_loop::EventLoopProxy,
-    window::{Window, WindowId},
+    window::{CursorGrabMode, CursorIcon, Window, WindowId, WindowLevel},
 };
 
+use super::{ApplicationEvent, CoalesceKey};
+
 pub mod exports {
     pub use super::WindowManager;
 }
 
+///Events held back until the end of the current event loop callback. An event with a
+///CoalesceKey replaces the earlier event with the same key
+struct CoalescingQueue<E> {
+    events: Vec<Option<E>>,
+    keys: HashMap<CoalesceKey, usize>,
+}
+impl<E> Default for CoalescingQueue<E> {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            keys: HashMap::new(),
+        }
+    }
+}
+
 pub struct WindowManager<E: 'static + Debug> {
     windows: Vec<Window>,
     event_loop: Option<EventLoopProxy<E>>,
+    coalescing_queue: Option<RefCell<CoalescingQueue<E>>>,
 }
-impl<E: 'static + Debug> WindowManager<E> {
+impl<E: ApplicationEvent + 'static> WindowManager<E> {
     pub fn set_event_loop(&mut self, event_loop: EventLoopProxy<E>) {
         self.event_loop = Some(event_loop);
     }
@@ -24,12 +43,72 @@ impl<E: 'static + Debug> WindowManager<E> {
             .expect("WindowManger must be initialized with '.set_event_loop' before sendind events")
     }
 
-    pub fn send_event(&self, event: E) {
+    ///With coalescing, events are sent at the end of the event loop callback, in which they were
+    ///created. Of the events sharing a CoalesceKey only the latest is sent
+    pub fn set_event_coalescing(&mut self, coalesce: bool) {
+        if coalesce {
+            if self.coalescing_queue.is_none() {
+                self.coalescing_queue = Some(RefCell::new(CoalescingQueue::default()));
+            }
+        } else {
+            self.flush_events();
+            self.coalescing_queue = None;
+        }
+    }
+
+    pub fn send_event(&self, mut event: E) {
+        match &self.coalescing_queue {
+            Some(queue) => {
+                let mut queue = queue.borrow_mut();
+                let index = queue.events.len();
+                if let Some(key) = event.coalesce_key() {
+                    if let Some(previous) = queue.keys.insert(key, index) {
+                        if let Some(earlier) = queue.events[previous].take() {
+                            event.coalesce(earlier);
+                        }
+                    }
+                }
+                queue.events.push(Some(event));
+            }
+            None => self.send_event_now(event),
+        }
+    }
+
+    fn send_event_now(&self, event: E) {
         self.get_event_loop()
             .send_event(event)
             .expect("The event loop has been closed. Cannot send an event");
     }
 
+    ///Send all events held back by coalescing
+    pub fn flush_events(&self) {
+        let events = match &self.coalescing_queue {
+            Some(queue) => {
+                let mut queue = queue.borrow_mut();
+                queue.keys.clear();
+                std::mem::take(&mut queue.events)
+            }
+            None => return,
+        };
+        for event in events.into_iter().flatten() {
+            self.send_event_now(event);
+        }
+    }
+
+    ///Takes the events held back by coalescing instead of sending them, to check them without an
+    ///event loop
+    #[cfg(test)]
+    pub(crate) fn take_queued_events(&self) -> Vec<E> {
+        match &self.coalescing_queue {
+            Some(queue) => {
+                let mut queue = queue.borrow_mut();
+                queue.keys.clear();
+                std::mem::take(&mut queue.events).into_iter().flatten().collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
     pub fn create_event_loop_proxy(&self) -> EventLoopProxy<E> {
         self.get_event_loop().clone()
     }
@@ -38,10 +117,58 @@ impl<E: 'static + Debug> WindowManager<E> {
         self.windows.len()
     }
 
+    pub fn windows(&self) -> impl Iterator<Item = &Window> {
+        self.windows.iter()
+    }
+
     pub fn get_window(&self, id: &WindowId) -> Option<&Window> {
         self.windows.iter().find(|window| window.id() == *id)
     }
 
+    ///Schedule a redraw of the window, eg. when redrawing on demand
+    pub fn request_redraw(&self, id: &WindowId) {
+        if let Some(window) = self.get_window(id) {
+            window.request_redraw();
+        }
+    }
+
+    pub fn set_cursor_visible(&self, id: &WindowId, visible: bool) {
+        if let Some(window) = self.get_window(id) {
+            window.set_cursor_visible(visible);
+        }
+    }
+
+    ///Not every platform supports every mode. If the mode is unsupported, a warning is logged and
+    ///the cursor is left as is
+    pub fn set_cursor_grab(&self, id: &WindowId, mode: CursorGrabMode) {
+        if let Some(window) = self.get_window(id) {
+            if let Err(e) = window.set_cursor_grab(mode) {
+                warn!("Could not set cursor grab mode {:?}: {}", mode, e);
+            }
+        }
+    }
+
+    pub fn set_cursor_icon(&self, id: &WindowId, icon: CursorIcon) {
+        if let Some(window) = self.get_window(id) {
+            window.set_cursor(icon);
+        }
+    }
+
+    ///Eg. toggle always on top for an overlay, without recreating the window
+    pub fn set_window_level(&self, id: &WindowId, level: WindowLevel) {
+        if let Some(window) = self.get_window(id) {
+            window.set_window_level(level);
+        }
+    }
+
+    ///Input methods are needed for composed text, like accented or CJK characters. While
+    ///allowed, some keys are consumed by the input method instead of producing KeyboardInput
+    pub fn set_ime_allowed(&self, id: &WindowId, allowed: bool) {
+        if let Some(window) = self.get_window(id) {
+            window.set_ime_allowed(allowed);
+        }
+    }
+
     pub fn remove_window(&mut self, id: &WindowId) {
         self.windows.retain(|window| window.id() != *id)
     }
@@ -55,6 +182,7 @@ impl<E: 'static + Debug> Default for WindowManager<E> {
         Self {
             windows: Vec::new(),
             event_loop: None,
+            coalescing_queue: None,
         }
     }
 }
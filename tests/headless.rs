@@ -0,0 +1,335 @@
+use std::path::PathBuf;
+
+use ferride_core::{
+    app::{IndexBuffer, VertexBuffer},
+    game_engine::{
+        example::{
+            Color, EmptyEntityType, EmptyExternalEvent, SimpleVertex, SpriteEntity, TexturedVertex,
+            TEXTURED_SHADER, TEXTURED_SINGLE_SHADER,
+        },
+        Entity, SpritePosition, SpriteSheet, SpriteSheetDimensions,
+    },
+    graphics::{
+        BlendMode, GraphicsDescriptor, GraphicsProvider, RenderSceneDescriptor, RenderSceneName,
+        SceneLoadOp, ShaderDescriptor, Vertex,
+    },
+    Size,
+};
+use twod::Vector;
+use winit::{dpi::PhysicalSize, window::WindowId};
+
+const SIZE: u32 = 64;
+
+fn pixel(pixels: &[u8], x: u32, y: u32) -> [u8; 4] {
+    let i = ((y * SIZE + x) * 4) as usize;
+    [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+}
+
+///Writes a texture of a single color to the temp directory, to load it like a sprite sheet
+fn write_png(name: &str, color: [u8; 4]) -> PathBuf {
+    let file = format!("ferride_core_{}_{}.png", name, std::process::id());
+    let path = std::env::temp_dir().join(file);
+    image::RgbaImage::from_pixel(2, 2, image::Rgba(color))
+        .save(&path)
+        .expect("Could not write the test texture");
+    path
+}
+
+fn textured_descriptor() -> RenderSceneDescriptor {
+    RenderSceneDescriptor {
+        index_format: wgpu::IndexFormat::Uint16,
+        vertex_buffer_layout: TexturedVertex::describe_buffer_layout(),
+        use_textures: true,
+        target_aspect: None,
+        use_depth: false,
+        blend_mode: BlendMode::Alpha,
+        use_global_uniforms: false,
+        load_op: SceneLoadOp::Clear,
+    }
+}
+
+#[test]
+fn render_triangle() {
+    let Some(mut graphics_provider) = GraphicsProvider::new_headless() else {
+        eprintln!("No adapter available, skipping headless rendering test");
+        return;
+    };
+    //Never handed to winit, only used as a key for the offscreen target
+    let id = unsafe { WindowId::dummy() };
+    graphics_provider.init_headless_target(id, &PhysicalSize::new(SIZE, SIZE));
+
+    let render_scene: RenderSceneName = "triangle".into();
+    graphics_provider.add_render_scene(
+        &id,
+        render_scene.clone(),
+        ShaderDescriptor {
+            file: concat!(env!("CARGO_MANIFEST_DIR"), "/tests/triangle.wgsl"),
+            vertex_shader: "vs_main",
+            fragment_shader: "fs_main",
+            uniforms: &[],
+        },
+        RenderSceneDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffer_layout: SimpleVertex::describe_buffer_layout(),
+            use_textures: false,
+            target_aspect: None,
+            use_depth: false,
+            blend_mode: BlendMode::Alpha,
+            use_global_uniforms: false,
+            load_op: SceneLoadOp::Clear,
+        },
+        &[],
+    );
+
+    let red = Color::new_rgba(255, 0, 0, 255);
+    let mut vertices = VertexBuffer::new();
+    let mut indices = IndexBuffer::new();
+    vertices.extend_from_slice(&[
+        SimpleVertex::new(Vector::new(-0.5, -0.5), red.clone()),
+        SimpleVertex::new(Vector::new(0.5, -0.5), red.clone()),
+        SimpleVertex::new(Vector::new(0.0, 0.5), red),
+    ]);
+    indices.extend_from_slice(&[0u16, 1, 2]);
+    graphics_provider.update_scene(&render_scene, &vertices, &indices);
+    graphics_provider.render_window(&id);
+
+    let pixels = graphics_provider
+        .read_pixels(&id)
+        .expect("Headless target should support reading pixels");
+    assert_eq!(pixels.len(), (SIZE * SIZE * 4) as usize);
+    assert_eq!(pixel(&pixels, SIZE / 2, SIZE / 2), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, 0, 0), [255, 255, 255, 255]);
+}
+
+#[test]
+fn render_textured_sprite() {
+    let Some(mut graphics_provider) = GraphicsProvider::new_headless() else {
+        eprintln!("No adapter available, skipping headless rendering test");
+        return;
+    };
+    if !graphics_provider.supports_texture_arrays() {
+        eprintln!("No texture arrays available, skipping textured rendering test");
+        return;
+    }
+    let id = unsafe { WindowId::dummy() };
+    graphics_provider.init_headless_target(id, &PhysicalSize::new(SIZE, SIZE));
+
+    let render_scene: RenderSceneName = "sprite".into();
+    graphics_provider.add_render_scene(
+        &id,
+        render_scene.clone(),
+        TEXTURED_SHADER,
+        textured_descriptor(),
+        &[],
+    );
+
+    let path = write_png("textured_sprite", [0, 0, 255, 255]);
+    let texture = graphics_provider
+        .create_texture(&path, "blue")
+        .expect("The texture should load");
+    let sprite_sheet = SpriteSheet::new(texture, &SpriteSheetDimensions::new(1, 1));
+    let mut sprite = SpriteEntity::new(
+        "sprite".into(),
+        "blue".into(),
+        SpritePosition::new(0, 0),
+        Vector::new(0.0, 0.0),
+        Size::new(1.0, 1.0),
+    );
+    let mut render = |sprite_sheet: Option<&SpriteSheet>| {
+        let mut vertices = VertexBuffer::new();
+        let mut indices = IndexBuffer::new();
+        Entity::<EmptyEntityType, EmptyExternalEvent>::render(
+            &mut sprite,
+            &mut vertices,
+            &mut indices,
+            vec![sprite_sheet],
+        );
+        graphics_provider.update_scene(&render_scene, &vertices, &indices);
+        graphics_provider.render_window(&id);
+        graphics_provider
+            .read_pixels(&id)
+            .expect("Headless target should support reading pixels")
+    };
+
+    let pixels = render(Some(&sprite_sheet));
+    assert_eq!(pixel(&pixels, SIZE / 2, SIZE / 2), [0, 0, 255, 255]);
+    assert_eq!(pixel(&pixels, 0, 0), [255, 255, 255, 255]);
+    //Not loaded sprite sheets are drawn with the placeholder at texture index 1
+    let pixels = render(None);
+    assert_eq!(pixel(&pixels, SIZE / 2, SIZE / 2), [255, 0, 255, 255]);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn render_single_texture() {
+    let descriptor = GraphicsDescriptor::new().with_texture_arrays(false);
+    let Some(mut graphics_provider) = GraphicsProvider::new_headless_with_descriptor(descriptor)
+    else {
+        eprintln!("No adapter available, skipping headless rendering test");
+        return;
+    };
+    assert!(!graphics_provider.supports_texture_arrays());
+    let id = unsafe { WindowId::dummy() };
+    graphics_provider.init_headless_target(id, &PhysicalSize::new(SIZE, SIZE));
+
+    let render_scene: RenderSceneName = "single texture".into();
+    graphics_provider.add_render_scene(
+        &id,
+        render_scene.clone(),
+        TEXTURED_SINGLE_SHADER,
+        textured_descriptor(),
+        &[],
+    );
+
+    let path = write_png("single_texture", [0, 255, 0, 255]);
+    let texture = graphics_provider
+        .create_texture(&path, "green")
+        .expect("The texture should load");
+    assert!(graphics_provider.bind_texture_render_scene(&render_scene, texture));
+    let sprite_sheet = SpriteSheet::new(texture, &SpriteSheetDimensions::new(1, 1));
+    let sprite = SpriteEntity::new(
+        "sprite".into(),
+        "green".into(),
+        SpritePosition::new(0, 0),
+        Vector::new(0.0, 0.0),
+        Size::new(1.0, 1.0),
+    );
+    let mut vertices = VertexBuffer::new();
+    let mut indices = IndexBuffer::new();
+    vertices.extend_from_slice(&sprite.vertices(&sprite_sheet));
+    indices.extend_from_slice(&[0u16, 1, 2, 0, 2, 3]);
+    graphics_provider.update_scene(&render_scene, &vertices, &indices);
+    graphics_provider.render_window(&id);
+
+    let pixels = graphics_provider
+        .read_pixels(&id)
+        .expect("Headless target should support reading pixels");
+    assert_eq!(pixel(&pixels, SIZE / 2, SIZE / 2), [0, 255, 0, 255]);
+    assert_eq!(pixel(&pixels, 0, 0), [255, 255, 255, 255]);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn render_stats_after_shrinking() {
+    let Some(mut graphics_provider) = GraphicsProvider::new_headless() else {
+        eprintln!("No adapter available, skipping headless rendering test");
+        return;
+    };
+    let id = unsafe { WindowId::dummy() };
+    graphics_provider.init_headless_target(id, &PhysicalSize::new(SIZE, SIZE));
+
+    let render_scene: RenderSceneName = "shrinking".into();
+    graphics_provider.add_render_scene(
+        &id,
+        render_scene.clone(),
+        ShaderDescriptor {
+            file: concat!(env!("CARGO_MANIFEST_DIR"), "/tests/triangle.wgsl"),
+            vertex_shader: "vs_main",
+            fragment_shader: "fs_main",
+            uniforms: &[],
+        },
+        RenderSceneDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffer_layout: SimpleVertex::describe_buffer_layout(),
+            use_textures: false,
+            target_aspect: None,
+            use_depth: false,
+            blend_mode: BlendMode::Alpha,
+            use_global_uniforms: false,
+            load_op: SceneLoadOp::Clear,
+        },
+        &[],
+    );
+
+    let red = Color::new_rgba(255, 0, 0, 255);
+    let triangle = [
+        SimpleVertex::new(Vector::new(-0.5, -0.5), red.clone()),
+        SimpleVertex::new(Vector::new(0.5, -0.5), red.clone()),
+        SimpleVertex::new(Vector::new(0.0, 0.5), red),
+    ];
+    let mut vertices = VertexBuffer::new();
+    let mut indices = IndexBuffer::new();
+    vertices.extend_from_slice(&triangle);
+    vertices.extend_from_slice(&triangle);
+    indices.extend_from_slice(&[0u16, 1, 2, 3, 4, 5]);
+    graphics_provider.update_scene(&render_scene, &vertices, &indices);
+    graphics_provider.render_window(&id);
+    let stats = graphics_provider.render_stats(&id).expect("The window was rendered");
+    assert_eq!((stats.vertices, stats.indices, stats.buffer_reallocations), (6, 6, 2));
+
+    let mut vertices = VertexBuffer::new();
+    let mut indices = IndexBuffer::new();
+    vertices.extend_from_slice(&triangle);
+    indices.extend_from_slice(&[0u16, 1, 2]);
+    graphics_provider.update_scene(&render_scene, &vertices, &indices);
+    graphics_provider.render_window(&id);
+    let stats = graphics_provider.render_stats(&id).expect("The window was rendered");
+    assert_eq!((stats.vertices, stats.indices, stats.buffer_reallocations), (3, 3, 0));
+    assert_eq!(graphics_provider.render_scene_geometry(&render_scene), Some((3, 3)));
+}
+
+///Draws a half transparent red triangle over the white clear color, directly or accumulated
+fn translucent_triangle_pixel(load_op: SceneLoadOp) -> Option<[u8; 4]> {
+    let mut graphics_provider = GraphicsProvider::new_headless()?;
+    let id = unsafe { WindowId::dummy() };
+    graphics_provider.init_headless_target(id, &PhysicalSize::new(SIZE, SIZE));
+
+    let render_scene: RenderSceneName = "translucent".into();
+    graphics_provider.add_render_scene(
+        &id,
+        render_scene.clone(),
+        ShaderDescriptor {
+            file: concat!(env!("CARGO_MANIFEST_DIR"), "/tests/triangle.wgsl"),
+            vertex_shader: "vs_main",
+            fragment_shader: "fs_main",
+            uniforms: &[],
+        },
+        RenderSceneDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffer_layout: SimpleVertex::describe_buffer_layout(),
+            use_textures: false,
+            target_aspect: None,
+            use_depth: false,
+            blend_mode: BlendMode::Alpha,
+            use_global_uniforms: false,
+            load_op,
+        },
+        &[],
+    );
+
+    let red = Color::new_rgba(255, 0, 0, 128);
+    let mut vertices = VertexBuffer::new();
+    let mut indices = IndexBuffer::new();
+    vertices.extend_from_slice(&[
+        SimpleVertex::new(Vector::new(-0.5, -0.5), red.clone()),
+        SimpleVertex::new(Vector::new(0.5, -0.5), red.clone()),
+        SimpleVertex::new(Vector::new(0.0, 0.5), red),
+    ]);
+    indices.extend_from_slice(&[0u16, 1, 2]);
+    graphics_provider.update_scene(&render_scene, &vertices, &indices);
+    graphics_provider.render_window(&id);
+
+    let pixels = graphics_provider
+        .read_pixels(&id)
+        .expect("Headless target should support reading pixels");
+    Some(pixel(&pixels, SIZE / 2, SIZE / 2))
+}
+
+#[test]
+fn composite_accumulated_scene() {
+    let Some(direct) = translucent_triangle_pixel(SceneLoadOp::Clear) else {
+        eprintln!("No adapter available, skipping headless rendering test");
+        return;
+    };
+    let accumulated = translucent_triangle_pixel(SceneLoadOp::Load)
+        .expect("The adapter was available before");
+    //The first accumulated frame matches drawing the scene directly, its alpha is applied once
+    for (accumulated, direct) in accumulated.iter().zip(direct.iter()) {
+        assert!(
+            accumulated.abs_diff(*direct) <= 2,
+            "Accumulated {:?}, direct {:?}",
+            accumulated,
+            direct
+        );
+    }
+}